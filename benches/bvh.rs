@@ -0,0 +1,108 @@
+#![feature(test)]
+
+extern crate test;
+
+use ppm::scene::material::Material;
+use ppm::scene::primitive::Triangle;
+use ppm::scene::Scene;
+use ppm::util::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::Arc;
+use test::{black_box, Bencher};
+
+const RAYS: usize = 1000;
+
+// 在边长为 1000 的立方体内随机放置 n 个边长约为 size 的三角形
+fn random_scene(n: usize, build_bvh: bool) -> Scene {
+    let mut rng = StdRng::seed_from_u64(n as u64);
+    let material = Arc::new(Material::new(Color::new(0.75, 0.75, 0.75), 1.0, 0.0, 0.0, 1.0));
+    let size = 1000.0 / (n as f64).cbrt();
+    let mut scene = Scene::new();
+    for i in 0..n {
+        let base = Vector3::new(
+            rng.gen_range(0.0, 1000.0),
+            rng.gen_range(0.0, 1000.0),
+            rng.gen_range(0.0, 1000.0),
+        );
+        let mut vertex = || {
+            base + Vector3::new(
+                rng.gen_range(-size, size),
+                rng.gen_range(-size, size),
+                rng.gen_range(-size, size),
+            )
+        };
+        let (a, b, c) = (vertex(), vertex(), vertex());
+        scene.add_object(Box::new(Triangle::new(i, a, b, c, material.clone())));
+    }
+    if build_bvh {
+        scene.build_bvh();
+    }
+    scene
+}
+
+fn random_rays() -> Vec<Ray> {
+    let mut rng = StdRng::seed_from_u64(0);
+    (0..RAYS)
+        .map(|_| {
+            let o = Vector3::new(
+                rng.gen_range(-500.0, 1500.0),
+                rng.gen_range(-500.0, 1500.0),
+                -500.0,
+            );
+            let target = Vector3::new(
+                rng.gen_range(0.0, 1000.0),
+                rng.gen_range(0.0, 1000.0),
+                rng.gen_range(0.0, 1000.0),
+            );
+            Ray::new(o, (target - o).normalize())
+        })
+        .collect()
+}
+
+// 每次迭代对 RAYS 条射线求交
+fn bench_intersect(b: &mut Bencher, n: usize, build_bvh: bool) {
+    let scene = random_scene(n, build_bvh);
+    let rays = random_rays();
+    b.iter(|| {
+        for ray in rays.iter() {
+            black_box(scene.intersect(ray));
+        }
+    });
+}
+
+#[bench]
+fn linear_1k(b: &mut Bencher) {
+    bench_intersect(b, 1_000, false);
+}
+
+#[bench]
+fn linear_10k(b: &mut Bencher) {
+    bench_intersect(b, 10_000, false);
+}
+
+#[bench]
+fn bvh_1k(b: &mut Bencher) {
+    bench_intersect(b, 1_000, true);
+}
+
+#[bench]
+fn bvh_10k(b: &mut Bencher) {
+    bench_intersect(b, 10_000, true);
+}
+
+#[bench]
+fn bvh_100k(b: &mut Bencher) {
+    bench_intersect(b, 100_000, true);
+}
+
+#[bench]
+fn bvh_1m(b: &mut Bencher) {
+    bench_intersect(b, 1_000_000, true);
+}
+
+#[bench]
+fn build_100k(b: &mut Bencher) {
+    let mut scene = random_scene(100_000, false);
+    b.iter(|| scene.build_bvh());
+}
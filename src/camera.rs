@@ -1,5 +1,6 @@
 use super::util::*;
 
+#[derive(Clone)]
 pub struct Camera {
     position: Vector3,
     direction: Vector3,
@@ -28,6 +29,9 @@ impl Camera {
     pub fn set_size(&mut self, width: usize, height: usize) {
         self.height = height;
         self.width = width;
+        if !self.direction.is_zero() {  // 宽高比变化后重新计算成像平面
+            self.set_dir(self.direction);
+        }
     }
 
     pub fn set_dir(&mut self, direction: Vector3) {
@@ -1,3 +1 @@
 pub const EPS : f64 = 1e-10;    // 参数不为0的阈值
-//pub const MAX_PH_RADIUS2 : f64 = 256_0000.0;
-pub const MAX_PH_RADIUS2 : f64 = 20_0000.0;
@@ -0,0 +1,67 @@
+use super::{PathTracer, ProgressivePhotonTracer, RayTracer, RenderMode, RenderSettings};
+use crate::camera::Camera;
+use crate::scene::Scene;
+use crate::util::*;
+use std::sync::{mpsc::channel, Arc};
+use std::thread::spawn;
+
+// 确定性地展开镜面反射与折射时，权重低于该值的分支被舍弃
+pub(crate) const MIN_BRANCH_WEIGHT: f64 = 1e-3;
+
+// 渲染算法的统一接口：给定相机和场景，得到一帧图像
+pub trait Integrator {
+    fn render(&mut self, camera: Arc<Camera>, scene: Arc<Scene>) -> Framebuffer;
+}
+
+// 根据设置中的渲染算法构造对应的积分器
+pub fn new_integrator(settings: RenderSettings) -> Box<dyn Integrator> {
+    match settings.mode {
+        RenderMode::Ppm | RenderMode::Sppm => Box::new(ProgressivePhotonTracer::new(settings)),
+        RenderMode::Pt => Box::new(PathTracer::new(settings)),
+        RenderMode::Rt => Box::new(RayTracer::new(settings)),
+    }
+}
+
+/*
+ * 按行将像素分给多个线程计算，每个像素在其范围内抖动发出 samples_per_pixel 条视线，
+ * radiance 用线程各自的采样器给出一条视线的辐射亮度。样本先累加到各线程自己的胶片上，
+ * 再按线程编号依次合并，滤波器跨越行的边界时结果也与线程的执行顺序无关
+ */
+pub(crate) fn render_pixels<F>(camera: Arc<Camera>, settings: &RenderSettings, radiance: F) -> Framebuffer
+where
+    F: Fn(&Ray, &mut Sampler) -> Color + Send + Sync + 'static,
+{
+    let mut film = Film::new(camera.width, camera.height, settings.filter);
+    let threads = settings.threads.max(1);
+    let spp = settings.samples_per_pixel.max(1);
+    let (kind, seed, filter) = (settings.sampler, settings.seed, settings.filter);
+    let radiance = Arc::new(radiance);
+    let mut handle_vec = Vec::new();
+    for t in 0..threads {
+        let camera = camera.clone();
+        let radiance = radiance.clone();
+        let (sender, receiver) = channel();
+        spawn(move || {
+            let mut sampler = Sampler::derive(kind, seed, 0, t as u64);
+            let mut film = Film::new(camera.width, camera.height, filter);
+            for j in (t..camera.height).step_by(threads) {
+                for i in 0..camera.width {
+                    sampler.start_sequence((j * camera.width + i) as u64, spp as u64);
+                    for s in 0..spp {
+                        sampler.start_sample(s as u64);
+                        let (u, v) = sampler.get_2d();
+                        let ray = camera.super_emitting(i, j, u - 0.5, v - 0.5);
+                        let color = radiance(&ray, &mut sampler);
+                        film.add_sample(i as f64 + u - 0.5, j as f64 + v - 0.5, color);
+                    }
+                }
+            }
+            sender.send(film).unwrap();
+        });
+        handle_vec.push(receiver);
+    }
+    for receiver in handle_vec.iter_mut() {
+        film.merge(&receiver.recv().unwrap());
+    }
+    film.to_framebuffer()
+}
@@ -1,7 +1,11 @@
+mod integrator;
 mod path_tracer;
 mod photon_tracer;
 mod progressive_photon_mapper;
+mod render_settings;
 
+pub use integrator::{new_integrator, Integrator};
 pub use path_tracer::{PathTracer, RayTracer};
 pub use photon_tracer::PhotonTracer;
-pub use progressive_photon_mapper::ProgressivePhotonTracer;
+pub use progressive_photon_mapper::{PixelStat, ProgressivePhotonTracer};
+pub use render_settings::{RenderMode, RenderSettings};
@@ -1,74 +1,176 @@
+use super::integrator::{render_pixels, MIN_BRANCH_WEIGHT};
+use super::{Integrator, RenderSettings};
+use crate::camera::Camera;
+use crate::scene::bsdf::{Shading, Transport};
 use crate::scene::Scene;
 use crate::util::*;
 use std::sync::Arc;
 
+#[derive(Clone)]
 pub struct PathTracer {
     scene : Arc<Scene>,
+    settings : RenderSettings,
 }
 
 impl PathTracer {
-    pub fn new(scene : Arc<Scene>) -> Self {
-        PathTracer { scene ,}
+    pub fn new(settings : RenderSettings) -> Self {
+        PathTracer { scene : Arc::new(Scene::new()), settings }
     }
 
-    pub fn trace_ray(&self, ray: &Ray, weight : f64, depth : u32) -> Color {
+    /*
+     * 沿一条视线追踪一条完整的路径，返回该视线方向的辐射亮度估计
+     * 每次碰撞对材质的 BSDF 采样得到下一个方向，材质各分量权重之和不足 1 的部分视为被吸收；
+     * 超过 3 次反弹之后使用俄罗斯轮盘赌终止路径。
+     * 非镜面分量的直接光照同时通过对光源采样和对 BSDF 采样得到，两者用幂启发式合并
+     */
+    pub fn trace_ray(&self, ray: &Ray, sampler: &mut Sampler) -> Color {
         let mut ret = Color::default();
-        if depth > 20 { 
-            return ret; 
-        }
-        let mut dist = 1e20;
-        let mut light_power = Color::default();
-        if let Some(collider) = self.scene.intersect_light(ray) {
-            dist = collider.dist;
-            light_power = collider.power.mult(weight);
-        }
-        if let Some(collider) = self.scene.intersect(ray) {
-            if collider.distance > dist { 
-                ret += light_power;
-                return ret; 
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut ray = ray.clone();
+        let mut media = MediumStack::new();
+        let mut bsdf_pdf = 0.0; // 上一次对 BSDF 采样的概率密度，为 0 表示上一次是镜面散射或相机
+        for depth in 0..=self.settings.max_trace_depth {
+            let light = self.scene.intersect_light(&ray);
+            let collider = self.scene.intersect(&ray);
+            if let Some(lgt) = light {
+                let closer = match &collider {
+                    Some(collider) => lgt.dist < collider.distance + 1e-6,
+                    None => true,
+                };
+                if closer {
+                    let mut weight = 1.0;
+                    if bsdf_pdf > 0.0 {
+                        let light_pdf = self.scene.light_pdf(&ray.o, &ray.d);
+                        weight = power_heuristic(bsdf_pdf, light_pdf);
+                    }
+                    ret += (throughput * lgt.power).mult(weight);
+                    break;
+                }
             }
-            if collider.material.is_diffuse() {
-                let diff_ray = Ray::new(
-                    collider.pos,
-                    collider.material.cal_diffuse_ray(&collider.norm_vec).unwrap()
-                );
-                ret += self.trace_ray(&diff_ray, weight * collider.material.diffuse, depth + 1) * collider.color; // TODO correct weight
+            let collider = match collider {
+                Some(collider) => collider,
+                None => break,
+            };
+
+            let material = &collider.material;
+            let shading = collider.shading(&media, Transport::Radiance);
+            let wo = ray.d.mult(-1.0);
+            if !material.is_delta() {
+                ret += throughput * self.direct_light(&collider, &shading, sampler);
             }
-            if collider.material.is_specular() {
-                let spec_ray = Ray::new(
-                    collider.pos,
-                    collider.material.cal_specular_ray(&ray.d, &collider.norm_vec).unwrap()
-                );
-                ret += self.trace_ray(&spec_ray, weight * collider.material.specular, depth + 1); // TODO correct weight
+
+            let sample = match material.sample(&shading, &wo, sampler.get_1d(), sampler.get_2d()) {
+                Some(sample) => sample,
+                None => break, // 被吸收
+            };
+            bsdf_pdf = if sample.delta { 0.0 } else { sample.pdf };
+            if sample.transmitted {
+                media.cross(&collider);
             }
+            let dir = sample.dir;
+            throughput = throughput * sample.weight;
+
+            if depth >= 3 {
+                let p = throughput.r.max(throughput.g).max(throughput.b).min(0.95);
+                if sampler.get_1d() >= p {
+                    break;
+                }
+                throughput = throughput.mult(1.0 / p);
+            }
+            ray = Ray::new(collider.pos + dir.mult(RAY_OFFSET), dir.normalize());
         }
         ret
     }
+
+    // 对光源采样计算碰撞点非镜面分量接收到的直接光照
+    fn direct_light(&self, collider: &Collider, shading: &Shading, sampler: &mut Sampler) -> Color {
+        let sample = match self.scene.sample_light(&collider.pos, sampler) {
+            Some(sample) => sample,
+            None => return Color::default(),
+        };
+        let material = &collider.material;
+        let wo = collider.in_direction.mult(-1.0);
+        let f = material.eval(shading, &wo, &sample.dir);
+        if f.is_zero_vec() || !self.scene.visible(&collider.pos, &sample.pos) {
+            return Color::default();
+        }
+        let mut weight = 1.0;
+        if !sample.delta {
+            weight = power_heuristic(sample.pdf, material.pdf(shading, &wo, &sample.dir));
+        }
+        let cos = sample.dir.dot(&shading.normal).abs();
+        (f * sample.radiance).mult(cos * weight / sample.pdf)
+    }
 }
 
+// 新射线的起点沿出射方向偏移一小段距离，避免与出发的表面再次相交
+const RAY_OFFSET: f64 = 1e-4;
+
+// 多重重要性采样的幂启发式（β = 2），pdf_f 为当前所用策略的概率密度
+fn power_heuristic(pdf_f: f64, pdf_g: f64) -> f64 {
+    let f2 = pdf_f * pdf_f;
+    let g2 = pdf_g * pdf_g;
+    if f2 + g2 <= 0.0 {
+        return 0.0;
+    }
+    f2 / (f2 + g2)
+}
+
+impl Integrator for PathTracer {
+    fn render(&mut self, camera : Arc<Camera>, scene : Arc<Scene>) -> Framebuffer {
+        self.scene = scene;
+        let tracer = self.clone();
+        render_pixels(camera, &self.settings, move |ray, sampler| tracer.trace_ray(ray, sampler))
+    }
+}
+
+#[derive(Clone)]
 pub struct RayTracer {
     scene : Arc<Scene>,
+    settings : RenderSettings,
 }
 
 impl RayTracer {
-    pub fn new(scene : Arc<Scene>) -> Self {
-        RayTracer { scene ,}
+    pub fn new(settings : RenderSettings) -> Self {
+        RayTracer { scene : Arc::new(Scene::new()), settings }
     }
 
-    pub fn trace_ray<F : Copy>(&self, ray: &Ray, weight : f64, depth : u32, mut func : F) -> Color where F : FnMut(&Collider) -> Color {
+    // 非镜面的表面由 func 着色，理想镜面反射与折射按各自的期望贡献展开；media 为射线当前所在的介质
+    pub fn trace_ray<F>(&self, ray: &Ray, weight : Color, depth : u32, media : &MediumStack, mut func : F) -> Color where F : Copy + FnMut(&Collider) -> Color {
         let mut ret = Color::default();
+        if depth > self.settings.max_trace_depth || weight.r.max(weight.g).max(weight.b) < MIN_BRANCH_WEIGHT {
+            return ret;
+        }
         if let Some(collider) = self.scene.intersect(ray) {
-            if collider.material.is_diffuse() {
-                ret += func(&collider) * collider.color.mult(weight);
+            if !collider.material.is_delta() {
+                ret += func(&collider) * collider.color * weight;
             }
-            if collider.material.is_specular() {
-                let spec_ray = Ray::new(
-                    collider.pos,
-                    collider.material.cal_specular_ray(&ray.d, &collider.norm_vec).unwrap()
-                );
-                ret += self.trace_ray(&spec_ray, weight * collider.material.specular, depth + 1, func); // TODO correct weight
+            let shading = collider.shading(media, Transport::Radiance);
+            for branch in collider.material.delta_branches(&shading, &ray.d.mult(-1.0)) {
+                let next = Ray::new(collider.pos, branch.dir);
+                if branch.transmitted {
+                    let mut inner = media.clone();
+                    inner.cross(&collider);
+                    ret += self.trace_ray(&next, weight * branch.weight, depth + 1, &inner, func);
+                } else {
+                    ret += self.trace_ray(&next, weight * branch.weight, depth + 1, media, func);
+                }
             }
         }
         ret
     }
 }
+
+impl Integrator for RayTracer {
+    fn render(&mut self, camera : Arc<Camera>, scene : Arc<Scene>) -> Framebuffer {
+        self.scene = scene;
+        let tracer = self.clone();
+        render_pixels(camera, &self.settings, move |ray, _| {
+            // 漫反射表面只按视线与法向量的夹角着色
+            tracer.trace_ray(ray, Color::new(1.0, 1.0, 1.0), 0, &MediumStack::new(), |collider : &Collider| {
+                let cos = collider.shading_normal.dot(&collider.in_direction).abs();
+                Color::new(cos, cos, cos)
+            })
+        })
+    }
+}
@@ -1,3 +1,4 @@
+use crate::scene::bsdf::Transport;
 use crate::scene::Scene;
 use crate::util::*;
 use std::sync::Arc;
@@ -5,106 +6,89 @@ use kdtree::kdtree::KdTree as Kd;
 use kdtree::distance::squared_euclidean;
 use spin::Mutex;
 
-use rand::Rng;
-
 pub struct PhotonTracer {
     scene : Arc<Scene>,
     hit_point_map : Arc<Kd<f64, Arc<Mutex<ViewPoint>>, [f64;3]>>,
     max_radius : f64,
+    max_depth : u32,
+    gathered : Vec<(f64, Color)>, // 按视点序号记录本线程的光子数量与通量，最后按线程顺序合并，使结果与线程调度无关
 }
 
 impl PhotonTracer {
-    fn photon_tracing(&self, mut photon : Photon, depth : u32, refracted : bool) {
-        if depth > 10 || photon.power.power() < 1e-7 { return; }   // 最大递归深度
+    /*
+     * 光子在非镜面的表面上留下记录，然后按材质的 BSDF 采样继续传播
+     * 用俄罗斯轮盘赌决定光子是否被吸收，存活的光子按概率放大能量，使各颜色分量的最大值保持不变
+     */
+    fn photon_tracing(&mut self, mut photon : Photon, depth : u32, mut media : MediumStack, sampler : &mut Sampler) {
+        if depth > self.max_depth || photon.power.power() < 1e-7 { return; }   // 最大递归深度
         if let Some(collider) = self.scene.intersect(&photon.ray) {
             photon.ray.o = collider.pos;
-            if collider.material.is_diffuse() {    // 到达漫反射平面
+            if !collider.material.is_delta() {    // 到达非镜面的表面
                 let mut new_photon = photon.clone();
                 new_photon.ray.d = photon.ray.d.mult(-1.0); // 方向设置为指向光源的方向
                 self.insert_photon(&new_photon);    // 计算该光子对碰撞点的影响
             }
 
-            let mut prob = 1.0;
-            if !self.photon_diffusion(&collider, photon.clone(), depth, &mut prob, refracted) {
-                if !self.photon_reflection(&collider, photon.clone(), depth, &mut prob, refracted) {
-                    self.photon_refraction(&collider, photon.clone(), depth, &mut prob, refracted);
-                }
+            let shading = collider.shading(&media, Transport::Importance);
+            let wo = photon.ray.d.mult(-1.0);
+            let sample = match collider.material.sample(&shading, &wo, sampler.get_1d(), sampler.get_2d()) {
+                Some(sample) => sample,
+                None => return,    // 被吸收
+            };
+            let weight = sample.weight;
+            let survive = weight.r.max(weight.g).max(weight.b).min(1.0);
+            if survive < sampler.get_1d() {
+                return;
             }
+            photon.ray.d = sample.dir;
+            photon.power = photon.power * weight.mult(1.0 / survive);
+            if sample.transmitted {
+                media.cross(&collider);
+            }
+            self.photon_tracing(photon, depth + 1, media, sampler);
         }
     }
 
-    fn photon_reflection(&self, collider : &Collider, mut photon : Photon, depth : u32, prob : &mut f64, refracted : bool) -> bool {
-        let eta = collider.material.specular * collider.color.power();
-        if eta < rand::thread_rng().gen_range(0.0, 1.0) * ( *prob) {
-            *prob -= eta;
-            return false;
-        }
-
-        if let Some(spec_ray) = collider.get_specular_ray() {
-            photon.ray.d = spec_ray;
-            photon.power = photon.power * collider.color.refresh_by_power();
-            self.photon_tracing(photon, depth + 1,refracted);
-        }
-        return true;
-    }
-
-    fn photon_diffusion(&self, collider : &Collider, mut photon : Photon, depth : u32, prob : &mut f64, refracted : bool) -> bool {
-        let eta = collider.material.diffuse * collider.color.power();
-        if eta < rand::thread_rng().gen_range(0.0, 1.0) * ( *prob) {
-            *prob -= eta;
-            return false;
-        }
-
-        if let Some(diff_ray) = collider.get_diffuse_ray() {
-            photon.ray.d = diff_ray;
-            photon.power = photon.power * collider.color.refresh_by_power();
-            self.photon_tracing(photon, depth + 1,refracted);
-        }
-        return true;
-    }
-
-    fn photon_refraction(&self, collider : &Collider, mut photon : Photon, depth : u32, prob : &mut f64, refracted : bool) -> bool {
-        let eta = collider.material.refraction * collider.color.power();
-        if eta < rand::thread_rng().gen_range(0.0, 1.0) * ( *prob) {
-            *prob -= eta;
-            return false;
-        }
-
-        if let Some(refr_ray) = collider.get_refractive_ray(refracted) {
-            photon.ray.d = refr_ray;
-            photon.power = photon.power * collider.color.refresh_by_power();
-            self.photon_tracing(photon, depth + 1, !refracted);
-        }
-        return true;
-    }
-
-    fn insert_photon(&self, photon : &Photon) {
+    fn insert_photon(&mut self, photon : &Photon) {
         let mut coord : [f64;3] = [0.0, 0.0, 0.0];
         coord[0] = photon.ray.o.x;
         coord[1] = photon.ray.o.y;
         coord[2] = photon.ray.o.z;
-        // TODO mutex
         let result = self.hit_point_map.within(&coord, self.max_radius, &squared_euclidean).unwrap();
         for (_, vp_ptr) in result.iter() {
-            let mut vp = vp_ptr.lock();
-            vp.handle(photon);
+            let vp = vp_ptr.lock();
+            if let Some(flux) = vp.contribution(photon) {
+                let entry = &mut self.gathered[vp.index];
+                entry.0 += 1.0;
+                entry.1 += flux;
+            }
         }
     }
 
-    pub fn photon_tracing_pass(&self, photon_number : usize, power : f64) {
+    /*
+     * 每个光源发射 photon_number 个光子，使用该光源长度为 total 的样本序列中从 first 开始的样本
+     * 各轮各线程取序列中互不重叠的区间，所有光子合起来构成一个样本序列
+     */
+    pub fn photon_tracing_pass(&mut self, photon_number : usize, first : usize, total : usize, sampler : &mut Sampler) {
         let number = self.scene.get_light_num();
         for i in 0..number {
             let illumiant = self.scene.get_light(i);
+            sampler.start_sequence(i as u64, total as u64);
             for j in 0..photon_number {
-                info!("{} photons ", j);
-                let mut photon = illumiant.gen_photon();
-                photon.power = photon.power.mult(power);
-                self.photon_tracing(photon, 0, false);
+                sampler.start_sample((first + j) as u64);
+                let photon = illumiant.gen_photon(sampler);
+                self.photon_tracing(photon, 0, MediumStack::new(), sampler);
             }
         }
     }
 
-    pub fn new(scene : Arc<Scene>, hit_point_map : Arc<Kd<f64, Arc<Mutex<ViewPoint>>, [f64;3]>>, max_radius : f64) -> Self {
-        PhotonTracer { scene, hit_point_map, max_radius }
+    // point_number : 视点的总数
+    pub fn new(scene : Arc<Scene>, hit_point_map : Arc<Kd<f64, Arc<Mutex<ViewPoint>>, [f64;3]>>, max_radius : f64, max_depth : u32, point_number : usize) -> Self {
+        PhotonTracer { scene, hit_point_map, max_radius, max_depth, gathered : vec![(0.0, Color::default()); point_number] }
+    }
+
+    // 各视点收到的光子数量与通量，下标为视点的序号
+    pub fn into_gathered(self) -> Vec<(f64, Color)> {
+        self.gathered
     }
 }
@@ -1,19 +1,59 @@
-use super::PhotonTracer;
+use super::integrator::MIN_BRANCH_WEIGHT;
+use super::{Integrator, PhotonTracer, RenderMode, RenderSettings};
 use crate::camera::Camera;
 use crate::consts::EPS;
+use crate::scene::bsdf::Transport;
 use crate::scene::Scene;
 use crate::util::*;
-use kdtree::distance::squared_euclidean;
 use kdtree::kdtree::KdTree as Kd;
 use spin::Mutex;
-use std::path::Path;
 use std::sync::{mpsc::channel, Arc};
 use std::thread::spawn;
 use std::vec::Vec;
 
+// 视线追踪在主线程中进行，使用与光子追踪的各线程不同的随机数流
+const EYE_STREAM: u64 = u64::MAX;
+
+// 光子追踪的各线程共用的随机数流
+const PHOTON_STREAM: u64 = 0;
+
+// PPM 中位于物体边缘的像素额外发出的视线数
+const EDGE_SAMPLES: u64 = 9;
+
+// SPPM 中每个像素在各轮之间共享的统计量
+#[derive(Clone, Debug, Default)]
+pub struct PixelStat {
+    pub radius2: f64,      // 共享的半径平方
+    pub count: f64,        // 已累计的光子数量
+    pub flux_color: Color, // 累计的光子通量
+}
+
+impl PixelStat {
+    // 合并一轮中该像素收集到的 delta 个光子和通量 flux，只保留其中 alpha 比例的光子，半径平方按同一比例收缩
+    pub fn renew(&mut self, delta: f64, flux: Color, alpha: f64) {
+        if delta > 1e-8 {
+            let count = self.count + delta * alpha;
+            let k = count / (self.count + delta);
+            self.radius2 *= k;
+            self.flux_color = (self.flux_color + flux).mult(k);
+            self.count = count;
+        }
+    }
+}
+
+// 从相机发出的一条视线，radiance 累加它直接看到的光源和收集到的光子
+struct EyeSample {
+    x: f64, // 在成像平面上的位置，以像素为单位
+    y: f64,
+    pixel: usize, // 所在的像素
+    weight: f64,  // 在胶片上的权重，同一像素的多条视线可以分摊一个样本的权重
+    radiance: Color,
+}
+
 pub struct ProgressivePhotonTracer {
     camera: Arc<Camera>, // 相机，只读
-    picture: Vec<Color>, // 所有线程结束之后才会写回,无需互斥
+    samples: Vec<EyeSample>, // 本轮的视线样本
+    film: Film,              // 所有线程结束之后才会写回,无需互斥
     width: usize,
     height: usize,
     scene: Arc<Scene>, // 场景，只读
@@ -23,58 +63,88 @@ pub struct ProgressivePhotonTracer {
     total_photon: f64,                     // 发射的总光子数量
     max_radius: f64,
     hash_table: Vec<u64>,
-    super_sampled: Vec<bool>,
+    pixel_stats: Vec<PixelStat>,
+    settings: RenderSettings,
 }
 
 impl ProgressivePhotonTracer {
-    pub fn new(camera: Arc<Camera>, scene: Arc<Scene>) -> Self {
+    pub fn new(settings: RenderSettings) -> Self {
         ProgressivePhotonTracer {
-            camera,
-            picture: Vec::new(),
+            camera: Arc::new(Camera::new()),
+            samples: Vec::new(),
+            film: Film::new(0, 0, settings.filter),
             width: 0,
             height: 0,
-            scene,
+            scene: Arc::new(Scene::new()),
             hit_point_map: Arc::new(Kd::new(3)),
             points: Vec::new(),
             photon_map: Kd::new(3),
             total_photon: 0.0,
             max_radius: 0.0,
             hash_table: Vec::new(),
-            super_sampled: Vec::new(),
+            pixel_stats: Vec::new(),
+            settings,
         }
     }
 
+    // 上一次 SPPM 渲染结束时各像素的统计量，按行存储；PPM 渲染后为空
+    pub fn pixel_stats(&self) -> &[PixelStat] {
+        &self.pixel_stats
+    }
+
     pub fn ray_tracing_pass(&mut self) {
+        let mut sampler = Sampler::derive(self.settings.sampler, self.settings.seed, 0, EYE_STREAM);
+        let mut centers = vec![0; self.width * self.height]; // 各像素中心的视线样本
         for i in 0..self.width {
             for j in 0..self.height {
-                let ray = self.camera.emitting(i, j);
+                let (ray, sample) = self.eye_sample(i, j, 0.0, 0.0);
                 let mut hash = 0u64;
                 let idx = j * self.width + i;
-                self.trace_ray(&ray, idx, 1.0, 0, false, &mut hash);
+                centers[idx] = sample;
+                self.trace_ray(&ray, sample, Color::new(1.0, 1.0, 1.0), 0, &MediumStack::new(), &mut hash);
                 self.hash_table[idx] = hash;
-                info!("{} {}", i, j);
             }
         }
+        // 边缘像素的所有视线分摊一个样本的权重，宽的滤波器不会因此偏向边缘像素
+        let weight = 1.0 / (EDGE_SAMPLES + 1) as f64;
         for i in 0..self.width {
             for j in 0..self.height {
                 if self.judge_hash(i, j) {
                     let idx = j * self.width + i;
                     let mut hash = 0u64;
-                    self.super_sampled[idx] = true;
-                    for ii in 0..9 {
-                        let ray = self.camera.super_emitting(
-                            i,
-                            j,
-                            (ii % 3) as f64 / 3.0 - 1.0 / 3.0,
-                            (ii / 3) as f64 / 3.0 - 1.0 / 3.0,
-                        );
-                        self.trace_ray(&ray, idx, 1.0, 0, false, &mut hash);
+                    self.samples[centers[idx]].weight = weight;
+                    sampler.start_sequence(idx as u64, EDGE_SAMPLES);
+                    for ii in 0..EDGE_SAMPLES {
+                        sampler.start_sample(ii);
+                        let (u, v) = sampler.get_2d();
+                        let (ray, sample) = self.eye_sample(i, j, u - 0.5, v - 0.5);
+                        self.samples[sample].weight = weight;
+                        self.trace_ray(&ray, sample, Color::new(1.0, 1.0, 1.0), 0, &MediumStack::new(), &mut hash);
                     }
                 }
             }
         }
     }
 
+    // 在像素 (i, j) 中偏移 (du, dv) 处发出的视线及其样本序号
+    fn eye_sample(&mut self, i: usize, j: usize, du: f64, dv: f64) -> (Ray, usize) {
+        self.samples.push(EyeSample {
+            x: i as f64 + du,
+            y: j as f64 + dv,
+            pixel: j * self.width + i,
+            weight: 1.0,
+            radiance: Color::default(),
+        });
+        (self.camera.super_emitting(i, j, du, dv), self.samples.len() - 1)
+    }
+
+    // 把所有视线样本按重建滤波器累加到胶片上
+    fn develop(&mut self) {
+        for sample in self.samples.iter() {
+            self.film.add_weighted_sample(sample.x, sample.y, sample.radiance, sample.weight);
+        }
+    }
+
     fn judge_hash(&self, x: usize, y: usize) -> bool {
         if x != 0 && self.hash_table[y * self.width + x] != self.hash_table[y * self.width + x - 1]
         {
@@ -98,151 +168,259 @@ impl ProgressivePhotonTracer {
         false
     }
 
+    /*
+     * 视线追踪：在有非镜面分量的表面上留下视点，理想镜面反射与折射按各自的期望贡献确定性地展开，
+     * 玻璃的反射与折射由 Fresnel 反射率分配，全反射时只有反射；权重过小的分支不再继续
+     */
     pub fn trace_ray(
         &mut self,
         ray: &Ray,
-        pixel_pos: usize,
-        weight: f64,
+        sample: usize,
+        weight: Color,
         depth: u32,
-        refracted: bool,
+        media: &MediumStack,
         hash: &mut u64,
     ) {
-        if depth > 20 {
+        if depth > self.settings.max_trace_depth || weight.r.max(weight.g).max(weight.b) < MIN_BRANCH_WEIGHT {
             return;
         }
         let lgt_collider = self.scene.intersect_light(ray);
         let obj_collider = self.scene.intersect(ray);
-        if obj_collider.is_some() {
-            // 与物体相交
-            let collider = obj_collider.unwrap();
-            if lgt_collider.is_some() {
-                let lgt = lgt_collider.unwrap();
-                if (collider.distance - lgt.dist) < EPS {
-                    // 光源的交点更近
-                    self.picture[pixel_pos] = lgt.power.mult(0.7);
-                    return;
-                }
-            }
-            if collider.material.is_diffuse() {
-                *hash = *hash * 13 + collider.get_hash();
-                let vp = ViewPoint::new(&collider, pixel_pos, weight * collider.material.diffuse);
-                let vp_ptr = Arc::new(Mutex::new(vp));
-                let mut coord: [f64; 3] = [0.0, 0.0, 0.0];
-                coord[0] = collider.pos.x;
-                coord[1] = collider.pos.y;
-                coord[2] = collider.pos.z;
-                Arc::get_mut(&mut self.hit_point_map)
-                    .unwrap()
-                    .add(coord, vp_ptr.clone())
-                    .unwrap();
-                self.points.push(vp_ptr);
-            }
-            if collider.material.is_specular() {
-                *hash = *hash * 17 + collider.get_hash();
-                let spec_ray = Ray::new(
-                    collider.pos,
-                    collider
-                        .material
-                        .cal_specular_ray(&ray.d, &collider.norm_vec)
-                        .unwrap(),
-                );
-                self.trace_ray(
-                    &spec_ray,
-                    pixel_pos,
-                    weight * collider.material.specular,
-                    depth + 1,
-                    refracted,
-                    hash,
-                );
+        if let Some(lgt) = &lgt_collider {
+            let closer = match &obj_collider {
+                Some(collider) => lgt.dist < collider.distance + EPS,
+                None => true,
+            };
+            if closer {
+                // 光源的交点更近
+                self.hit_light(sample, lgt, weight);
+                return;
             }
-            if collider.material.is_refractive() {
-                *hash = *hash * 19 + collider.get_hash();
-                if let Some(dir) =
-                    collider
-                        .material
-                        .cal_refractive_ray(&ray.d, &collider.norm_vec, refracted)
-                {
-                    let spec_ray = Ray::new(collider.pos, dir);
-                    self.trace_ray(
-                        &spec_ray,
-                        pixel_pos,
-                        weight * collider.material.refraction,
-                        depth + 1,
-                        !refracted,
-                        hash,
-                    );
-                }
+        }
+        let collider = match obj_collider {
+            Some(collider) => collider,
+            None => return,
+        };
+        if !collider.material.is_delta() {
+            // 路径上各物体 hash 的组合，只用于比较，溢出时回绕
+            *hash = hash.wrapping_mul(13).wrapping_add(collider.get_hash());
+            // 半径稍后由 cal_hp_radius 或所在像素的统计量确定
+            let mut vp = ViewPoint::new(&collider, media, self.samples[sample].pixel, weight, 0.0);
+            vp.index = self.points.len();
+            vp.sample = sample;
+            let vp_ptr = Arc::new(Mutex::new(vp));
+            let mut coord: [f64; 3] = [0.0, 0.0, 0.0];
+            coord[0] = collider.pos.x;
+            coord[1] = collider.pos.y;
+            coord[2] = collider.pos.z;
+            Arc::get_mut(&mut self.hit_point_map)
+                .unwrap()
+                .add(coord, vp_ptr.clone())
+                .unwrap();
+            self.points.push(vp_ptr);
+        }
+        let shading = collider.shading(media, Transport::Radiance);
+        let wo = ray.d.mult(-1.0);
+        for branch in collider.material.delta_branches(&shading, &wo) {
+            *hash = hash.wrapping_mul(if branch.transmitted { 19 } else { 17 }).wrapping_add(collider.get_hash());
+            let mut next_media = media.clone();
+            if branch.transmitted {
+                next_media.cross(&collider);
             }
-        } else if lgt_collider.is_some() {
-            // 只与光源相交
-            let lgt = lgt_collider.unwrap();
-            self.picture[pixel_pos] = lgt.power.mult(0.7);
+            self.trace_ray(
+                &Ray::new(collider.pos, branch.dir),
+                sample,
+                weight * branch.weight,
+                depth + 1,
+                &next_media,
+                hash,
+            );
         }
     }
 
-    pub fn run(&mut self, times: usize, threads: usize) {
+    // 视线击中光源
+    fn hit_light(&mut self, sample: usize, lgt: &LightCollider, weight: Color) {
+        self.samples[sample].radiance += lgt.power * weight;
+    }
+
+    // 清空上一次渲染留下的状态
+    fn reset(&mut self) {
         self.width = self.camera.width;
         self.height = self.camera.height;
-        self.picture
-            .resize((self.width * self.height) as usize, Color::default());
-        self.hash_table
-            .resize((self.width * self.height) as usize, 0u64);
-        self.super_sampled
-            .resize((self.width * self.height) as usize, false);
+        let size = self.width * self.height;
+        self.samples.clear();
+        self.film = Film::new(self.width, self.height, self.settings.filter);
+        self.hash_table = vec![0u64; size];
+        self.hit_point_map = Arc::new(Kd::new(3));
+        self.points.clear();
+        self.pixel_stats.clear();
+        self.total_photon = 0.0;
+        self.max_radius = 0.0;
+    }
 
+    fn run_ppm(&mut self) {
         self.ray_tracing_pass(); // 从眼睛发射光线
 
         info!("sampling over!");
 
         self.cal_hp_radius();
 
-        for i in 0..times {
-            self.photon_tracing_pass(10_0000, threads);
-            self.total_photon += 10_0000.0;
+        let photons = self.settings.photons_per_pass;
+        for i in 0..self.settings.rounds {
+            self.photon_tracing_pass(photons, self.settings.threads, i);
+            self.total_photon += photons as f64;
             self.renew_hp_map();
             info!("{} rounds, {} photons ", i, self.total_photon);
         }
 
-        self.gen_png();
+        self.collect_flux();
+        self.develop();
+    }
+
+    // 每轮用抖动后的视线重新生成视点，半径和通量按像素保存
+    fn run_sppm(&mut self) {
+        let photons = self.settings.photons_per_pass;
+        for i in 0..self.settings.rounds {
+            self.jittered_ray_tracing_pass(i);
+            if i == 0 {
+                self.cal_hp_radius();
+                let stat = PixelStat {
+                    radius2: self.max_radius,
+                    ..Default::default()
+                };
+                self.pixel_stats = vec![stat; self.width * self.height];
+            }
+            self.max_radius = 1e-20;
+            for vp_ptr in self.points.iter() {
+                let mut vp = vp_ptr.lock();
+                vp.radius2 = self.pixel_stats[vp.px_pos].radius2;
+                self.max_radius = self.max_radius.max(vp.radius2);
+            }
+
+            self.photon_tracing_pass(photons, self.settings.threads, i);
+            self.total_photon += photons as f64;
+            self.renew_pixel_stats();
+            info!("{} rounds, {} photons ", i, self.total_photon);
+        }
+        self.develop_pixel_stats();
     }
 
-    fn photon_tracing_pass(&mut self, photon_number: usize, threads: usize) {
+    // SPPM 第 pass 轮中像素 (i, j) 的视线在像素内的偏移，同一像素在各轮中的抖动构成一个样本序列
+    fn jitter(&self, sampler: &mut Sampler, pass: usize, i: usize, j: usize) -> (f64, f64) {
+        sampler.start_sequence((j * self.width + i) as u64, self.settings.rounds as u64);
+        sampler.start_sample(pass as u64);
+        let (u, v) = sampler.get_2d();
+        (u - 0.5, v - 0.5)
+    }
+
+    /*
+     * 第 pass 轮的视线追踪
+     * 各轮直接看到光源的部分按样本的位置累加到胶片上，光子的估计则按像素统计，最后由 develop_pixel_stats 补充
+     */
+    fn jittered_ray_tracing_pass(&mut self, pass: usize) {
+        self.samples.clear();
+        self.points.clear();
+        self.hit_point_map = Arc::new(Kd::new(3));
+        let mut sampler = Sampler::derive(self.settings.sampler, self.settings.seed, pass as u64, EYE_STREAM);
+        for i in 0..self.width {
+            for j in 0..self.height {
+                let (du, dv) = self.jitter(&mut sampler, pass, i, j);
+                let (ray, sample) = self.eye_sample(i, j, du, dv);
+                let mut hash = 0u64;
+                self.trace_ray(&ray, sample, Color::new(1.0, 1.0, 1.0), 0, &MediumStack::new(), &mut hash);
+            }
+        }
+        self.develop();
+    }
+
+    /*
+     * 把各像素最终的光子估计补充到该像素每一轮的视线样本上：重新生成各轮的抖动，
+     * 在样本的位置上只累加颜色，结果与每个样本带着完整的辐射亮度经过滤波相同
+     */
+    fn develop_pixel_stats(&mut self) {
+        let estimates: Vec<Color> = self
+            .pixel_stats
+            .iter()
+            .map(|stat| stat.flux_color.div(std::f64::consts::PI * self.total_photon * stat.radius2))
+            .collect();
+        for pass in 0..self.settings.rounds {
+            let mut sampler = Sampler::derive(self.settings.sampler, self.settings.seed, pass as u64, EYE_STREAM);
+            for i in 0..self.width {
+                for j in 0..self.height {
+                    let (du, dv) = self.jitter(&mut sampler, pass, i, j);
+                    let estimate = estimates[j * self.width + i];
+                    self.film.add_splat(i as f64 + du, j as f64 + dv, estimate);
+                }
+            }
+        }
+    }
+
+    // 将本轮各视点收集到的光子合并到所属像素，并收缩像素的半径
+    fn renew_pixel_stats(&mut self) {
+        let mut delta = vec![0.0; self.width * self.height];
+        let mut flux = vec![Color::default(); self.width * self.height];
+        for vp_ptr in self.points.iter() {
+            let vp = vp_ptr.lock();
+            delta[vp.px_pos] += vp.delta;
+            flux[vp.px_pos] += vp.flux_color;
+        }
+        for (idx, stat) in self.pixel_stats.iter_mut().enumerate() {
+            stat.renew(delta[idx], flux[idx], self.settings.alpha);
+        }
+    }
+
+    /*
+     * 第 pass 轮的光子追踪，各线程的结果按线程编号依次合并到视点上
+     * 每轮恰好发射 photon_number 个光子，不能整除时前 photon_number % threads 个线程各多发射一个
+     * 第 pass 轮第 t 个线程的光子是同一个样本序列中紧接在前面各线程之后的一段
+     */
+    fn photon_tracing_pass(&mut self, photon_number: usize, threads: usize, pass: usize) {
+        let (per_thread, rest) = (photon_number / threads, photon_number % threads);
+        let total = self.settings.rounds * photon_number;
         let mut handle_vec = Vec::new();
-        for _ in 0..threads {
-            let photon_tracer = PhotonTracer::new(
+        for t in 0..threads {
+            let count = per_thread + (t < rest) as usize;
+            let first = pass * photon_number + t * per_thread + t.min(rest);
+            let mut photon_tracer = PhotonTracer::new(
                 self.scene.clone(),
                 self.hit_point_map.clone(),
                 self.max_radius,
+                self.settings.max_photon_depth,
+                self.points.len(),
             );
+            // 所有线程使用相同的 stream 才能共用一个样本序列，各段的随机数由 pass 参数区分
+            let segment = pass * threads + t;
+            let mut sampler = Sampler::derive(self.settings.sampler, self.settings.seed, segment as u64, PHOTON_STREAM);
             let (sender, receiver) = channel();
             spawn(move || {
-                photon_tracer.photon_tracing_pass(photon_number / threads, photon_number as f64);
-                sender.send(1).unwrap();
+                photon_tracer.photon_tracing_pass(count, first, total, &mut sampler);
+                sender.send(photon_tracer.into_gathered()).unwrap();
             });
             handle_vec.push(receiver);
         }
         for receiver in handle_vec.iter_mut() {
-            receiver.recv().unwrap();
+            let gathered = receiver.recv().unwrap();
+            for (vp_ptr, (delta, flux)) in self.points.iter().zip(gathered) {
+                if delta > 0.0 {
+                    let mut vp = vp_ptr.lock();
+                    vp.delta += delta;
+                    vp.flux_color += flux;
+                }
+            }
         }
     }
 
     fn cal_hp_radius(&mut self) {
-        // TODO， 可以根据视点的分布范围来估计半径
-        let mut max = Vector3::new(-1e20, -1e20, -1e20);
-        let mut min = Vector3::new(1e20, 1e20, 1e20);
+        // 根据视点的分布范围来估计半径，没有视点时退而使用场景中有界物体的范围，两者都没有时使用 fallback_radius
+        let mut bounds = AABB::empty();
         for vp_ptr in self.points.iter() {
-            let vp = vp_ptr.lock();
-            max.x = max.x.max(vp.pos.x);
-            max.y = max.y.max(vp.pos.y);
-            max.z = max.z.max(vp.pos.z);
-            min.x = min.x.min(vp.pos.x);
-            min.y = min.y.min(vp.pos.y);
-            min.z = min.z.min(vp.pos.z);
+            bounds = bounds.grow(&vp_ptr.lock().pos);
+        }
+        if bounds.is_empty() {
+            bounds = self.scene.bounds();
         }
-        info!("{:?}, {:?}", max, min);
-        let irad = (((max.x - min.x) + (max.y - min.y) + (max.z - min.z)) / 3.0)
-            / ((self.width + self.height) as f64 / 2.0)
-            * 2.0;
+        info!("{:?}, {:?}", bounds.max, bounds.min);
+        let irad = self.settings.initial_radius(&bounds, self.width, self.height);
         for vp_ptr in self.points.iter_mut() {
             let mut vp = vp_ptr.lock();
             vp.radius2 = irad * irad;
@@ -250,38 +428,12 @@ impl ProgressivePhotonTracer {
         self.max_radius = irad * irad;
     }
 
-    fn gen_png(&mut self) {
-        let buffer: &mut [u8] = &mut vec![0; self.width * self.height * 3];
+    // 将各视点累积的光子通量加到产生它的视线样本上
+    fn collect_flux(&mut self) {
         for vp_ptr in self.points.iter() {
             let vp = vp_ptr.lock();
             let to_div = std::f64::consts::PI * self.total_photon * vp.radius2;
-            self.picture[vp.px_pos] += vp.flux_color.div(to_div);
-        }
-
-        //将结果写入png
-        for i in 0..self.width {
-            for j in 0..self.height {
-                let idx = j * self.width + i;
-                let mut res = self.picture[idx];
-                if self.super_sampled[idx] {
-                    res = res.mult(0.1);
-                }
-                let (r, g, b) = res.to_u8();
-                buffer[idx * 3] = r;
-                buffer[idx * 3 + 1] = g;
-                buffer[idx * 3 + 2] = b;
-            }
-        }
-        let path = &Path::new("result.png");
-        if let Err(_e) = lodepng::encode_file(
-            path,
-            buffer,
-            self.width,
-            self.height,
-            lodepng::ColorType::RGB,
-            8,
-        ) {
-            panic!("encode error! {} ", _e);
+            self.samples[vp.sample].radiance += vp.flux_color.div(to_div);
         }
     }
 
@@ -289,7 +441,7 @@ impl ProgressivePhotonTracer {
         let mut irad = 1e-20;
         for vp_ptr in self.points.iter_mut() {
             let mut vp = vp_ptr.lock();
-            vp.renew();
+            vp.renew(self.settings.alpha);
             if vp.radius2 > irad {
                 irad = vp.radius2;
             }
@@ -297,19 +449,18 @@ impl ProgressivePhotonTracer {
         self.max_radius = irad;
         info!("max radius2 is {}", irad);
     }
+}
 
-    fn insert_photon(&self, photon: &Photon) {
-        let mut coord: [f64; 3] = [0.0, 0.0, 0.0];
-        coord[0] = photon.ray.o.x;
-        coord[1] = photon.ray.o.y;
-        coord[2] = photon.ray.o.z;
-        let result = self
-            .hit_point_map
-            .within(&coord, self.max_radius, &squared_euclidean)
-            .unwrap();
-        for (_, vp_ptr) in result.iter() {
-            let mut vp = vp_ptr.lock();
-            vp.handle(photon);
+impl Integrator for ProgressivePhotonTracer {
+    fn render(&mut self, camera: Arc<Camera>, scene: Arc<Scene>) -> Framebuffer {
+        self.camera = camera;
+        self.scene = scene;
+        self.reset();
+        if self.settings.mode == RenderMode::Sppm {
+            self.run_sppm();
+        } else {
+            self.run_ppm();
         }
+        self.film.to_framebuffer()
     }
 }
@@ -0,0 +1,92 @@
+use crate::util::{Filter, SamplerKind, AABB};
+use std::str::FromStr;
+
+// 渲染算法
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RenderMode {
+    Ppm,  // 渐进式光子映射，视点只在开始时生成一次
+    Sppm, // 随机渐进式光子映射，每轮重新生成视点
+    Pt,   // 路径追踪
+    Rt,   // 光线追踪
+}
+
+impl FromStr for RenderMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ppm" => Ok(RenderMode::Ppm),
+            "sppm" => Ok(RenderMode::Sppm),
+            "pt" => Ok(RenderMode::Pt),
+            "rt" => Ok(RenderMode::Rt),
+            _ => Err(format!("unknown render mode `{}`", s)),
+        }
+    }
+}
+
+// 渲染参数，所有字段都可以直接修改，未指定的字段使用 Default 中的取值
+#[derive(Clone, Debug)]
+pub struct RenderSettings {
+    pub mode: RenderMode,         // 渲染算法
+    pub rounds: usize,            // 光子追踪的轮数
+    pub photons_per_pass: usize,  // 每轮发射的光子数
+    pub threads: usize,           // 渲染使用的线程数
+    pub alpha: f64,               // 每轮新增光子中保留的比例，即半径收缩系数
+    /*
+     * 初始半径的最后退路，单位与场景坐标相同，只在未指定 init_radius、第一轮没有视点、场景中又没有有界物体时使用
+     * 此时没有可以参照的尺度：PPM 没有视点，半径不起作用；SPPM 中它只是之后各轮才出现的视点的初始半径，
+     * 所有视点重合在一点时同样使用它
+     * 半径按 alpha 收缩，任意正的初值都收敛，取值只影响收敛速度
+     * 默认值沿用原先的半径平方上限 2e5，约 450，与 Scene::init 中边长约 1000 的房间同一量级
+     */
+    pub fallback_radius: f64,
+    pub init_radius: Option<f64>, // 初始半径，为None时根据视点分布估计
+    pub max_trace_depth: u32,     // 视线追踪的最大递归深度
+    pub max_photon_depth: u32,    // 光子追踪的最大递归深度
+    pub samples_per_pixel: usize, // 路径追踪和光线追踪中每个像素的采样数
+    pub seed: u64,                // 随机数种子，种子和线程数相同时渲染结果完全相同
+    pub sampler: SamplerKind,     // 像素、光源位置和方向等各维度使用的采样器
+    pub filter: Filter,           // 把样本累加到像素上的重建滤波器
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            mode: RenderMode::Ppm,
+            rounds: 1,
+            photons_per_pass: 10_0000,
+            threads: 4,
+            alpha: 0.7,
+            fallback_radius: 450.0,
+            init_radius: None,
+            max_trace_depth: 20,
+            max_photon_depth: 10,
+            samples_per_pixel: 16,
+            seed: 0,
+            sampler: SamplerKind::Sobol,
+            filter: Filter::default(),
+        }
+    }
+}
+
+impl RenderSettings {
+    /*
+     * 光子映射的初始半径，bounds 为视点的分布范围，没有视点时为场景中有界物体的范围
+     * 大约让半径为两个像素在场景中对应的长度；范围为空或退化为一个点时使用 fallback_radius
+     */
+    pub fn initial_radius(&self, bounds: &AABB, width: usize, height: usize) -> f64 {
+        if let Some(radius) = self.init_radius {
+            return radius;
+        }
+        if bounds.is_empty() {
+            return self.fallback_radius;
+        }
+        let d = bounds.diagonal();
+        let radius = ((d.x + d.y + d.z) / 3.0) / ((width + height) as f64 / 2.0) * 2.0;
+        if radius > 0.0 {
+            radius
+        } else {
+            self.fallback_radius
+        }
+    }
+}
@@ -1,6 +1,4 @@
-#![feature(clamp)]
 #![allow(dead_code)]
-#![feature(vec_resize_default)]
 
 #[macro_use]
 extern crate log;
@@ -1,28 +1,256 @@
-#![allow(unused_imports)]
 #[macro_use]
 extern crate log;
 extern crate env_logger;
 
 use env_logger::Env;
 use ppm::camera::Camera;
-use ppm::core::ProgressivePhotonTracer;
+use ppm::core::{new_integrator, RenderSettings};
 use ppm::scene::Scene;
 use ppm::util::*;
+use std::env;
+use std::path::PathBuf;
+use std::process;
+use std::str::FromStr;
 use std::sync::Arc;
 
-use ppm::scene::primitive::BazierCurve;
+const USAGE: &str = "\
+Usage: ppm [OPTIONS]
+
+Options:
+    -m, --mode <MODE>       rendering algorithm: ppm, sppm, pt or rt (default: ppm)
+    -s, --scene <FILE>      scene description file (default: built-in scene)
+    -o, --output <FILE>     output image path; .png, .pfm, .hdr or .exr (default: result.png)
+        --bit-depth <N>     bits per channel for png output: 8 or 16 (default: 8)
+        --tonemap <OP>      tone mapping for png output: clamp, reinhard or aces (default: clamp)
+        --exposure <F>      exposure compensation in stops before tone mapping (default: 0)
+    -W, --width <N>         image width in pixels (default: from scene, or 512)
+    -H, --height <N>        image height in pixels (default: from scene, or 384)
+    -r, --rounds <N>        number of photon tracing rounds (default: 1)
+    -p, --photons <N>       photons emitted per round (default: 100000)
+    -t, --threads <N>       number of rendering threads (default: 4)
+    -a, --alpha <F>         radius reduction factor in (0, 1] (default: 0.7)
+        --radius <F>        initial gathering radius (default: estimated)
+        --spp <N>           samples per pixel for pt and rt (default: 16)
+        --seed <N>          random seed; the same seed and thread count give identical images (default: 0)
+        --sampler <KIND>    sampler: independent, stratified, halton or sobol (default: sobol)
+        --filter <KIND>     reconstruction filter: box, tent, gaussian or mitchell (default: box)
+        --filter-radius <F> filter radius in pixels (default: 0.5, 1, 1.5 or 2 by filter)
+    -h, --help              print this help message";
+
+struct Options {
+    scene: Option<PathBuf>,
+    output: PathBuf,
+    bit_depth: u32,
+    format: ImageFormat, // 由输出文件的扩展名和 bit_depth 决定
+    tone_mapping: ToneMapping, // 只用于 PNG，浮点格式保存原始的辐射亮度
+    width: Option<usize>,
+    height: Option<usize>,
+    filter_radius: Option<f64>, // 为 None 时使用滤波器默认的半径
+    settings: RenderSettings,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            scene: None,
+            output: PathBuf::from("result.png"),
+            bit_depth: 8,
+            format: ImageFormat::Png { bit_depth: 8 },
+            tone_mapping: ToneMapping::default(),
+            width: None,
+            height: None,
+            filter_radius: None,
+            settings: RenderSettings::default(),
+        }
+    }
+}
+
+fn parse_value<T: FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("missing value for `{}`", flag))?;
+    value
+        .parse::<T>()
+        .map_err(|_| format!("invalid value `{}` for `{}`", value, flag))
+}
+
+// 解析命令行参数，Ok(None) 表示只需要打印帮助信息
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Option<Options>, String> {
+    let mut opts = Options::default();
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-m" | "--mode" => opts.settings.mode = parse_value(&flag, args.next())?,
+            "-s" | "--scene" => opts.scene = Some(parse_value(&flag, args.next())?),
+            "-o" | "--output" => opts.output = parse_value(&flag, args.next())?,
+            "--bit-depth" => opts.bit_depth = parse_value(&flag, args.next())?,
+            "--tonemap" => opts.tone_mapping.operator = parse_value(&flag, args.next())?,
+            "--exposure" => opts.tone_mapping.exposure = parse_value(&flag, args.next())?,
+            "-W" | "--width" => opts.width = Some(parse_value(&flag, args.next())?),
+            "-H" | "--height" => opts.height = Some(parse_value(&flag, args.next())?),
+            "-r" | "--rounds" => opts.settings.rounds = parse_value(&flag, args.next())?,
+            "-p" | "--photons" => opts.settings.photons_per_pass = parse_value(&flag, args.next())?,
+            "-t" | "--threads" => opts.settings.threads = parse_value(&flag, args.next())?,
+            "-a" | "--alpha" => opts.settings.alpha = parse_value(&flag, args.next())?,
+            "--radius" => opts.settings.init_radius = Some(parse_value(&flag, args.next())?),
+            "--spp" => opts.settings.samples_per_pixel = parse_value(&flag, args.next())?,
+            "--seed" => opts.settings.seed = parse_value(&flag, args.next())?,
+            "--sampler" => opts.settings.sampler = parse_value(&flag, args.next())?,
+            "--filter" => opts.settings.filter = Filter::new(parse_value(&flag, args.next())?),
+            "--filter-radius" => opts.filter_radius = Some(parse_value(&flag, args.next())?),
+            _ => return Err(format!("unknown option `{}`", flag)),
+        }
+    }
+    if opts.bit_depth != 8 && opts.bit_depth != 16 {
+        return Err("bit depth must be 8 or 16".to_string());
+    }
+    opts.format = match ImageFormat::from_path(&opts.output) {
+        Some(ImageFormat::Png { .. }) => ImageFormat::Png { bit_depth: opts.bit_depth },
+        Some(format) => format,
+        None => return Err(format!("unsupported output format `{}`", opts.output.display())),
+    };
+    if !opts.tone_mapping.exposure.is_finite() {
+        return Err("exposure must be finite".to_string());
+    }
+    if opts.width == Some(0) || opts.height == Some(0) {
+        return Err("image size must be positive".to_string());
+    }
+    if opts.settings.samples_per_pixel == 0 {
+        return Err("samples per pixel must be positive".to_string());
+    }
+    if opts.settings.threads == 0 {
+        return Err("thread count must be positive".to_string());
+    }
+    if opts.settings.photons_per_pass < opts.settings.threads {
+        return Err("photons per round must be at least the thread count".to_string());
+    }
+    if opts.settings.alpha.is_nan() || opts.settings.alpha <= 0.0 || opts.settings.alpha > 1.0 {
+        return Err("alpha must be in (0, 1]".to_string());
+    }
+    if let Some(radius) = opts.settings.init_radius {
+        if !radius.is_finite() || radius <= 0.0 {
+            return Err("radius must be positive and finite".to_string());
+        }
+    }
+    if let Some(radius) = opts.filter_radius {
+        if !radius.is_finite() || radius <= 0.0 {
+            return Err("filter radius must be positive".to_string());
+        }
+        opts.settings.filter.radius = radius;
+    }
+    Ok(Some(opts))
+}
+
+fn run(opts: Options) -> Result<(), String> {
+    let (scene, camera) = match &opts.scene {
+        Some(path) => {
+            let scene = Scene::from_file(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            let camera = scene
+                .get_camera()
+                .cloned()
+                .ok_or_else(|| format!("{}: no camera declared", path.display()))?;
+            (scene, camera)
+        }
+        None => {
+            let mut scene = Scene::new();
+            scene.init();
+            let mut camera = Camera::new();
+            camera.set_size(512, 384);
+            camera.set_pos(&Vector3::new(6000.0, 5000.0, 400.0));
+            camera.set_dir(Vector3::new(-1.0, 0.0, 0.0));
+            (scene, camera)
+        }
+    };
+    let mut camera = camera;
+    if opts.width.is_some() || opts.height.is_some() {
+        let width = opts.width.unwrap_or(camera.width);
+        let height = opts.height.unwrap_or(camera.height);
+        camera.set_size(width, height);
+    }
+
+    let mut integrator = new_integrator(opts.settings.clone());
+    let mut framebuffer = integrator.render(Arc::new(camera), Arc::new(scene));
+    if let ImageFormat::Png { .. } = opts.format {
+        framebuffer.tone_map(&opts.tone_mapping);
+    }
+    framebuffer
+        .save(&opts.output, opts.format)
+        .map_err(|e| format!("cannot write {}: {}", opts.output.display(), e))?;
+    info!("image written to {}", opts.output.display());
+    Ok(())
+}
 
 fn main() {
     env_logger::from_env(Env::default().default_filter_or("ppm")).init();
 
-    info!("Hello, world!");
+    let opts = match parse_args(env::args().skip(1)) {
+        Ok(Some(opts)) => opts,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(e) = run(opts) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Options>, String> {
+        parse_args(args.iter().map(|s| s.to_string()))
+    }
+
+    fn error(args: &[&str]) -> String {
+        match parse(args) {
+            Err(e) => e,
+            Ok(_) => panic!("{:?} should be rejected", args),
+        }
+    }
+
+    #[test]
+    fn help_stops_parsing() {
+        assert!(parse(&["--help"]).unwrap().is_none());
+        // 帮助之后的参数不再检查
+        assert!(parse(&["-h", "--bogus"]).unwrap().is_none());
+    }
+
+    #[test]
+    fn defaults_and_values_are_parsed() {
+        let opts = parse(&[]).unwrap().unwrap();
+        assert_eq!(opts.format, ImageFormat::Png { bit_depth: 8 });
+        assert_eq!(opts.settings.init_radius, None);
+        let opts = parse(&["-o", "out.png", "--bit-depth", "16", "-t", "3", "-p", "3", "--radius", "2.5"])
+            .unwrap()
+            .unwrap();
+        assert_eq!(opts.format, ImageFormat::Png { bit_depth: 16 });
+        assert_eq!((opts.settings.threads, opts.settings.photons_per_pass), (3, 3));
+        assert_eq!(opts.settings.init_radius, Some(2.5));
+    }
+
+    #[test]
+    fn bad_arguments_are_reported() {
+        assert_eq!(error(&["--bogus"]), "unknown option `--bogus`");
+        assert_eq!(error(&["--threads"]), "missing value for `--threads`");
+        assert_eq!(error(&["-t", "many"]), "invalid value `many` for `-t`");
+        assert_eq!(error(&["-o", "out.jpg"]), "unsupported output format `out.jpg`");
+        assert_eq!(error(&["-o", "out"]), "unsupported output format `out`");
+        assert_eq!(error(&["--bit-depth", "12"]), "bit depth must be 8 or 16");
+        assert_eq!(error(&["-t", "4", "-p", "3"]), "photons per round must be at least the thread count");
+        assert_eq!(error(&["-t", "0"]), "thread count must be positive");
+        assert_eq!(error(&["-a", "1.5"]), "alpha must be in (0, 1]");
+        assert_eq!(error(&["--filter-radius", "inf"]), "filter radius must be positive");
+    }
 
-    let mut scene = Scene::new();
-    scene.init();
-    let mut camera = Camera::new();
-    camera.set_size(512, 384);
-    camera.set_pos(&Vector3::new(6000.0, 5000.0, 400.0));
-    camera.set_dir(Vector3::new(-1.0, 0.0, 0.0));
-    let mut ppm = ProgressivePhotonTracer::new(Arc::new(camera), Arc::new(scene));
-    ppm.run(1, 4);
+    #[test]
+    fn radius_must_be_positive_and_finite() {
+        for radius in ["0", "-1", "NaN", "inf", "-inf"].iter() {
+            assert_eq!(error(&["--radius", radius]), "radius must be positive and finite", "{}", radius);
+        }
+    }
 }
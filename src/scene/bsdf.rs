@@ -0,0 +1,441 @@
+use crate::consts::EPS;
+use crate::util::*;
+use std::f64::consts::PI;
+
+/*
+ * 双向散射分布函数
+ *
+ * 约定 wo、wi 都是从交点指向外的单位向量：wo 指向上一个顶点（视线追踪时为观察者，光子追踪时为光子的来源），
+ * wi 为采样得到的下一个方向。所有计算都在以法向量为 z 轴的局部坐标系中进行，法向量与 wo 位于同一侧。
+ */
+
+// 沿路径传输的量，穿过折射表面时辐射亮度需要按折射率之比缩放，而光子的能量不需要
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Transport {
+    Radiance,   // 从相机出发的视线
+    Importance, // 从光源出发的光子
+}
+
+// 一次散射计算所需的交点信息
+#[derive(Clone, Copy, Debug)]
+pub struct Shading {
+    pub normal: Vector3, // 单位法向量，与 wo 位于同一侧
+    pub color: Color,    // 交点处的表面颜色
+    pub eta: f64,        // 法向量背侧与正侧介质的折射率之比
+    pub mode: Transport,
+}
+
+// 对 BSDF 采样的结果
+#[derive(Clone, Copy, Debug)]
+pub struct BsdfSample {
+    pub dir: Vector3,      // 采样得到的方向 wi
+    pub weight: Color,     // f * |cos| / pdf
+    pub pdf: f64,          // 关于立体角的概率密度，delta 分布时没有意义
+    pub delta: bool,       // 是否来自镜面反射或折射，此时无法通过其他方式采样到
+    pub transmitted: bool, // 是否穿过了表面
+}
+
+pub trait Bxdf: Send + Sync {
+    // f(wo, wi)，不含余弦项；delta 分布返回 0
+    fn eval(&self, sh: &Shading, wo: &Vector3, wi: &Vector3) -> Color;
+    // 由 sample 采样得到 wi 的概率密度（立体角），delta 分布返回 0
+    fn pdf(&self, sh: &Shading, wo: &Vector3, wi: &Vector3) -> f64;
+    // uc 用于在反射与折射之间选择，u 用于采样方向，都在 [0, 1) 中均匀分布
+    fn sample(&self, sh: &Shading, wo: &Vector3, uc: f64, u: (f64, f64)) -> Option<BsdfSample>;
+    fn is_delta(&self) -> bool {
+        false
+    }
+    // 理想镜面散射的全部出射方向，weight 为该方向的期望贡献 f * |cos|，用于在视线追踪中确定性地展开；非 delta 分布返回空
+    fn branches(&self, _sh: &Shading, _wo: &Vector3) -> Vec<BsdfSample> {
+        Vec::new()
+    }
+}
+
+fn reflect(wo: &Vector3, n: &Vector3) -> Vector3 {
+    n.mult(2.0 * wo.dot(n)) - wo
+}
+
+// wo 从法向量 n 一侧折射到另一侧，eta 为另一侧与 n 一侧的折射率之比，发生全反射时返回 None
+fn refract(wo: &Vector3, n: &Vector3, eta: f64) -> Option<Vector3> {
+    let cos_i = wo.dot(n);
+    let sin2_t = (1.0 - cos_i * cos_i).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(wo.mult(-1.0 / eta) + n.mult(cos_i / eta - cos_t))
+}
+
+// 电介质的 Fresnel 反射率，cos_i 为入射方向与法向量夹角的余弦，eta 为透射侧与入射侧的折射率之比
+pub fn fresnel_dielectric(cos_i: f64, eta: f64) -> f64 {
+    let cos_i = cos_i.clamp(-1.0, 1.0).abs();
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0; // 全反射
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let rs = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    let rp = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    (rs * rs + rp * rp) * 0.5
+}
+
+// 金属的 Fresnel 反射率，用 Schlick 近似，正入射时的反射率 f0 即表面颜色
+fn fresnel_schlick(f0: &Color, cos_i: f64) -> Color {
+    let k = (1.0 - cos_i.abs().min(1.0)).powi(5);
+    f0.mult(1.0 - k) + Color::new(k, k, k)
+}
+
+// 穿过表面的辐射亮度按折射率之比的平方缩放
+fn transmission_scale(sh: &Shading, eta: f64) -> f64 {
+    match sh.mode {
+        Transport::Radiance => 1.0 / (eta * eta),
+        Transport::Importance => 1.0,
+    }
+}
+
+// 理想漫反射
+pub struct Lambertian;
+
+impl Bxdf for Lambertian {
+    fn eval(&self, sh: &Shading, wo: &Vector3, wi: &Vector3) -> Color {
+        if wo.dot(&sh.normal) <= 0.0 || wi.dot(&sh.normal) <= 0.0 {
+            return Color::default();
+        }
+        sh.color.mult(1.0 / PI)
+    }
+
+    fn pdf(&self, sh: &Shading, wo: &Vector3, wi: &Vector3) -> f64 {
+        if wo.dot(&sh.normal) <= 0.0 {
+            return 0.0;
+        }
+        sampling::cosine_hemisphere_pdf(wi.dot(&sh.normal))
+    }
+
+    fn sample(&self, sh: &Shading, wo: &Vector3, _uc: f64, u: (f64, f64)) -> Option<BsdfSample> {
+        if wo.dot(&sh.normal) <= 0.0 {
+            return None;
+        }
+        let local = sampling::cosine_hemisphere(u);
+        if local.z < EPS {
+            return None;
+        }
+        Some(BsdfSample {
+            dir: Frame::new(&sh.normal).to_world(&local).normalize(),
+            weight: sh.color,
+            pdf: sampling::cosine_hemisphere_pdf(local.z),
+            delta: false,
+            transmitted: false,
+        })
+    }
+}
+
+// 理想镜面，即光滑的金属表面
+pub struct Mirror;
+
+impl Bxdf for Mirror {
+    fn eval(&self, _sh: &Shading, _wo: &Vector3, _wi: &Vector3) -> Color {
+        Color::default()
+    }
+
+    fn pdf(&self, _sh: &Shading, _wo: &Vector3, _wi: &Vector3) -> f64 {
+        0.0
+    }
+
+    fn sample(&self, sh: &Shading, wo: &Vector3, _uc: f64, _u: (f64, f64)) -> Option<BsdfSample> {
+        self.branches(sh, wo).pop()
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+
+    fn branches(&self, sh: &Shading, wo: &Vector3) -> Vec<BsdfSample> {
+        let cos = wo.dot(&sh.normal);
+        if cos <= 0.0 {
+            return Vec::new();
+        }
+        vec![BsdfSample {
+            dir: reflect(wo, &sh.normal),
+            weight: fresnel_schlick(&sh.color, cos),
+            pdf: 1.0,
+            delta: true,
+            transmitted: false,
+        }]
+    }
+}
+
+// 理想的光滑电介质，按 Fresnel 反射率分配反射与折射，全反射时只有反射
+pub struct Glass;
+
+impl Bxdf for Glass {
+    fn eval(&self, _sh: &Shading, _wo: &Vector3, _wi: &Vector3) -> Color {
+        Color::default()
+    }
+
+    fn pdf(&self, _sh: &Shading, _wo: &Vector3, _wi: &Vector3) -> f64 {
+        0.0
+    }
+
+    // 以 Fresnel 反射率为概率在反射与折射之间选择
+    fn sample(&self, sh: &Shading, wo: &Vector3, uc: f64, _u: (f64, f64)) -> Option<BsdfSample> {
+        let mut branches = self.branches(sh, wo);
+        let pick = if uc < branches.first()?.pdf { 0 } else { branches.len() - 1 };
+        let mut sample = branches.swap_remove(pick);
+        sample.weight = sample.weight.mult(1.0 / sample.pdf);
+        Some(sample)
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+
+    // 第一项为反射，pdf 为 Fresnel 反射率；没有全反射时第二项为折射
+    fn branches(&self, sh: &Shading, wo: &Vector3) -> Vec<BsdfSample> {
+        let n = &sh.normal;
+        let cos = wo.dot(n);
+        if cos <= 0.0 {
+            return Vec::new();
+        }
+        let f = fresnel_dielectric(cos, sh.eta);
+        // 反射不带表面颜色
+        let mut ret = vec![BsdfSample {
+            dir: reflect(wo, n),
+            weight: Color::new(f, f, f),
+            pdf: f,
+            delta: true,
+            transmitted: false,
+        }];
+        if let Some(dir) = refract(wo, n, sh.eta).filter(|_| f < 1.0) {
+            ret.push(BsdfSample {
+                dir,
+                weight: sh.color.mult((1.0 - f) * transmission_scale(sh, sh.eta)),
+                pdf: 1.0 - f,
+                delta: true,
+                transmitted: true,
+            });
+        }
+        ret
+    }
+}
+
+/*
+ * GGX 法线分布，alpha 为粗糙度的平方
+ * 按 D(h) cos(h) 采样微表面法向量
+ */
+#[derive(Clone, Copy)]
+struct Ggx {
+    alpha: f64,
+}
+
+impl Ggx {
+    // h 为局部坐标中的微表面法向量
+    fn d(&self, h: &Vector3) -> f64 {
+        if h.z <= 0.0 {
+            return 0.0;
+        }
+        let a2 = self.alpha * self.alpha;
+        let k = h.z * h.z * (a2 - 1.0) + 1.0;
+        a2 / (PI * k * k)
+    }
+
+    // Smith 遮挡函数的单向部分
+    fn g1(&self, v: &Vector3) -> f64 {
+        let cos2 = v.z * v.z;
+        if cos2 < EPS {
+            return 0.0;
+        }
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+        2.0 / (1.0 + (1.0 + self.alpha * self.alpha * tan2).sqrt())
+    }
+
+    fn g(&self, wo: &Vector3, wi: &Vector3) -> f64 {
+        self.g1(wo) * self.g1(wi)
+    }
+
+    fn pdf(&self, h: &Vector3) -> f64 {
+        self.d(h) * h.z
+    }
+
+    fn sample(&self, u: (f64, f64)) -> Vector3 {
+        let tan2 = self.alpha * self.alpha * u.0 / (1.0 - u.0).max(EPS);
+        let cos = 1.0 / (1.0 + tan2).sqrt();
+        let sin = (1.0 - cos * cos).max(0.0).sqrt();
+        let phi = 2.0 * PI * u.1;
+        Vector3::new(sin * phi.cos(), sin * phi.sin(), cos)
+    }
+}
+
+// 粗糙度换算为 GGX 的参数，过小的粗糙度会导致数值问题
+fn ggx(roughness: f64) -> Ggx {
+    Ggx { alpha: (roughness * roughness).max(1e-3) }
+}
+
+// 粗糙金属，GGX 微表面模型
+pub struct GgxConductor {
+    dist: Ggx,
+}
+
+impl GgxConductor {
+    // roughness 在 (0, 1] 中，越大越粗糙
+    pub fn new(roughness: f64) -> Self {
+        GgxConductor { dist: ggx(roughness) }
+    }
+}
+
+impl Bxdf for GgxConductor {
+    fn eval(&self, sh: &Shading, wo: &Vector3, wi: &Vector3) -> Color {
+        let frame = Frame::new(&sh.normal);
+        let (lo, li) = (frame.to_local(wo), frame.to_local(wi));
+        if lo.z <= 0.0 || li.z <= 0.0 {
+            return Color::default();
+        }
+        let h = (lo + li).normalize();
+        let f = fresnel_schlick(&sh.color, li.dot(&h));
+        f.mult(self.dist.d(&h) * self.dist.g(&lo, &li) / (4.0 * lo.z * li.z))
+    }
+
+    fn pdf(&self, sh: &Shading, wo: &Vector3, wi: &Vector3) -> f64 {
+        let frame = Frame::new(&sh.normal);
+        let (lo, li) = (frame.to_local(wo), frame.to_local(wi));
+        if lo.z <= 0.0 || li.z <= 0.0 {
+            return 0.0;
+        }
+        let h = (lo + li).normalize();
+        self.dist.pdf(&h) / (4.0 * lo.dot(&h))
+    }
+
+    fn sample(&self, sh: &Shading, wo: &Vector3, _uc: f64, u: (f64, f64)) -> Option<BsdfSample> {
+        let frame = Frame::new(&sh.normal);
+        let lo = frame.to_local(wo);
+        if lo.z <= 0.0 {
+            return None;
+        }
+        let h = self.dist.sample(u);
+        let li = reflect(&lo, &h);
+        if li.z <= 0.0 || lo.dot(&h) <= 0.0 {
+            return None;
+        }
+        let wi = frame.to_world(&li);
+        let pdf = self.pdf(sh, wo, &wi);
+        if pdf < EPS {
+            return None;
+        }
+        Some(BsdfSample {
+            dir: wi,
+            weight: self.eval(sh, wo, &wi).mult(li.z / pdf),
+            pdf,
+            delta: false,
+            transmitted: false,
+        })
+    }
+}
+
+/*
+ * 粗糙电介质，GGX 微表面模型(Walter et al. 2007)
+ * 按微表面上的 Fresnel 反射率在反射与折射之间选择，折射部分带表面颜色
+ */
+pub struct RoughDielectric {
+    dist: Ggx,
+}
+
+impl RoughDielectric {
+    // roughness 在 (0, 1] 中，越大越粗糙
+    pub fn new(roughness: f64) -> Self {
+        RoughDielectric { dist: ggx(roughness) }
+    }
+
+    // 折射时的半程向量，朝向 wo 一侧
+    fn half_vector(lo: &Vector3, li: &Vector3, eta: f64) -> Vector3 {
+        let h = (lo + li.mult(eta)).normalize();
+        if h.z < 0.0 {
+            h.mult(-1.0)
+        } else {
+            h
+        }
+    }
+}
+
+impl Bxdf for RoughDielectric {
+    fn eval(&self, sh: &Shading, wo: &Vector3, wi: &Vector3) -> Color {
+        let frame = Frame::new(&sh.normal);
+        let (lo, li) = (frame.to_local(wo), frame.to_local(wi));
+        if lo.z <= 0.0 || li.z.abs() < EPS {
+            return Color::default();
+        }
+        let eta = sh.eta;
+        if li.z > 0.0 {
+            let h = (lo + li).normalize();
+            let f = fresnel_dielectric(lo.dot(&h), eta);
+            let v = f * self.dist.d(&h) * self.dist.g(&lo, &li) / (4.0 * lo.z * li.z);
+            return Color::new(v, v, v);
+        }
+        let h = Self::half_vector(&lo, &li, eta);
+        let (oh, ih) = (lo.dot(&h), li.dot(&h));
+        if oh <= 0.0 || ih >= 0.0 {
+            return Color::default(); // 两个方向必须位于微表面的两侧
+        }
+        let f = fresnel_dielectric(oh, eta);
+        let denom = oh + eta * ih;
+        let v = (1.0 - f) * self.dist.d(&h) * self.dist.g(&lo, &li) * eta * eta * (ih * oh).abs()
+            / (lo.z * li.z * denom * denom).abs();
+        sh.color.mult(v * transmission_scale(sh, eta))
+    }
+
+    fn pdf(&self, sh: &Shading, wo: &Vector3, wi: &Vector3) -> f64 {
+        let frame = Frame::new(&sh.normal);
+        let (lo, li) = (frame.to_local(wo), frame.to_local(wi));
+        if lo.z <= 0.0 || li.z.abs() < EPS {
+            return 0.0;
+        }
+        let eta = sh.eta;
+        if li.z > 0.0 {
+            let h = (lo + li).normalize();
+            let oh = lo.dot(&h);
+            if oh <= 0.0 {
+                return 0.0;
+            }
+            return fresnel_dielectric(oh, eta) * self.dist.pdf(&h) / (4.0 * oh);
+        }
+        let h = Self::half_vector(&lo, &li, eta);
+        let (oh, ih) = (lo.dot(&h), li.dot(&h));
+        if oh <= 0.0 || ih >= 0.0 {
+            return 0.0;
+        }
+        let denom = oh + eta * ih;
+        (1.0 - fresnel_dielectric(oh, eta)) * self.dist.pdf(&h) * eta * eta * ih.abs() / (denom * denom)
+    }
+
+    fn sample(&self, sh: &Shading, wo: &Vector3, uc: f64, u: (f64, f64)) -> Option<BsdfSample> {
+        let frame = Frame::new(&sh.normal);
+        let lo = frame.to_local(wo);
+        if lo.z <= 0.0 {
+            return None;
+        }
+        let h = self.dist.sample(u);
+        let oh = lo.dot(&h);
+        if oh <= 0.0 {
+            return None;
+        }
+        let f = fresnel_dielectric(oh, sh.eta);
+        let (li, transmitted) = if uc < f {
+            (reflect(&lo, &h), false)
+        } else {
+            (refract(&lo, &h, sh.eta)?, true)
+        };
+        if (li.z < 0.0) != transmitted {
+            return None; // 经过微表面散射之后落到了错误的一侧
+        }
+        let wi = frame.to_world(&li);
+        let pdf = self.pdf(sh, wo, &wi);
+        if pdf < EPS {
+            return None;
+        }
+        Some(BsdfSample {
+            dir: wi,
+            weight: self.eval(sh, wo, &wi).mult(li.z.abs() / pdf),
+            pdf,
+            delta: false,
+            transmitted,
+        })
+    }
+}
@@ -0,0 +1,253 @@
+use crate::util::*;
+
+const BINS: usize = 12; // SAH 划分时每个轴上的桶数
+const MAX_LEAF_SIZE: usize = 4; // 叶子节点最多包含的图元数量
+const TRAVERSAL_COST: f64 = 1.0; // 相对于一次图元求交，访问一个内部节点的代价
+
+struct BvhNode {
+    bounds: AABB,
+    // 内部节点：start 为右孩子下标，左孩子紧跟在自身之后；叶子节点：items[start..start + count]
+    start: usize,
+    count: usize,
+}
+
+/*
+ * 层次包围盒，按表面积启发式(SAH)自顶向下建立
+ * 只保存图元的编号，求交时由调用者对编号对应的图元求交
+ */
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    items: Vec<usize>,
+}
+
+struct BuildItem {
+    id: usize,
+    bounds: AABB,
+    centroid: Vector3,
+}
+
+impl Bvh {
+    // items 中每一项为 (图元编号, 图元包围盒)
+    pub fn build(items: Vec<(usize, AABB)>) -> Self {
+        let mut build_items: Vec<BuildItem> = items
+            .into_iter()
+            .map(|(id, bounds)| BuildItem {
+                id,
+                bounds,
+                centroid: bounds.centroid(),
+            })
+            .collect();
+        let mut bvh = Bvh {
+            nodes: Vec::new(),
+            items: Vec::with_capacity(build_items.len()),
+        };
+        if !build_items.is_empty() {
+            bvh.build_node(&mut build_items);
+        }
+        bvh
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn bounds(&self) -> AABB {
+        match self.nodes.first() {
+            Some(node) => node.bounds,
+            None => AABB::empty(),
+        }
+    }
+
+    fn build_node(&mut self, items: &mut [BuildItem]) -> usize {
+        let bounds = items.iter().fold(AABB::empty(), |b, item| b.union(&item.bounds));
+        let idx = self.nodes.len();
+        self.nodes.push(BvhNode { bounds, start: 0, count: 0 });
+
+        let split = if items.len() <= MAX_LEAF_SIZE {
+            None
+        } else {
+            Self::find_split(items, &bounds)
+        };
+        match split {
+            None => {
+                self.nodes[idx].start = self.items.len();
+                self.nodes[idx].count = items.len();
+                self.items.extend(items.iter().map(|item| item.id));
+            }
+            Some(mid) => {
+                let (left, right) = items.split_at_mut(mid);
+                self.build_node(left);
+                let right_idx = self.build_node(right);
+                self.nodes[idx].start = right_idx;
+            }
+        }
+        idx
+    }
+
+    // 按 SAH 寻找划分位置，划分后 items[..mid] 与 items[mid..] 分别成为左右子树；不值得划分时返回 None
+    fn find_split(items: &mut [BuildItem], bounds: &AABB) -> Option<usize> {
+        let centroid_bounds = items.iter().fold(AABB::empty(), |b, item| b.grow(&item.centroid));
+        let axis = centroid_bounds.longest_axis();
+        let lo = centroid_bounds.min[axis];
+        let extent = centroid_bounds.max[axis] - lo;
+        if extent <= 0.0 {
+            // 所有图元的中心重合，无法按位置划分
+            if items.len() > 4 * MAX_LEAF_SIZE {
+                return Some(items.len() / 2);
+            }
+            return None;
+        }
+
+        let bin_of = |c: &Vector3| (((c[axis] - lo) / extent * BINS as f64) as usize).min(BINS - 1);
+        let mut bin_bounds = [AABB::empty(); BINS];
+        let mut bin_count = [0usize; BINS];
+        for item in items.iter() {
+            let b = bin_of(&item.centroid);
+            bin_bounds[b] = bin_bounds[b].union(&item.bounds);
+            bin_count[b] += 1;
+        }
+
+        // 从右向左累计，right_area[i] 为桶 i..BINS 的包围盒面积
+        let mut right_area = [0.0; BINS];
+        let mut right_count = [0usize; BINS];
+        let mut acc = AABB::empty();
+        let mut count = 0;
+        for i in (1..BINS).rev() {
+            acc = acc.union(&bin_bounds[i]);
+            count += bin_count[i];
+            right_area[i] = acc.surface_area();
+            right_count[i] = count;
+        }
+
+        let mut best_cost = f64::INFINITY;
+        let mut best_bin = 0;
+        let mut acc = AABB::empty();
+        let mut count = 0;
+        for i in 0..BINS - 1 {
+            acc = acc.union(&bin_bounds[i]);
+            count += bin_count[i];
+            if count == 0 || right_count[i + 1] == 0 {
+                continue;
+            }
+            let cost = acc.surface_area() * count as f64
+                + right_area[i + 1] * right_count[i + 1] as f64;
+            if cost < best_cost {
+                best_cost = cost;
+                best_bin = i;
+            }
+        }
+
+        let area = bounds.surface_area();
+        let leaf_cost = items.len() as f64;
+        let split_cost = if area > 0.0 {
+            TRAVERSAL_COST + best_cost / area
+        } else {
+            f64::INFINITY
+        };
+        if split_cost >= leaf_cost && items.len() <= 4 * MAX_LEAF_SIZE {
+            return None;
+        }
+        if best_cost == f64::INFINITY {
+            return Some(items.len() / 2);
+        }
+
+        // 将属于左侧桶的图元移到前面
+        let mut mid = 0;
+        for i in 0..items.len() {
+            if bin_of(&items[i].centroid) <= best_bin {
+                items.swap(i, mid);
+                mid += 1;
+            }
+        }
+        Some(mid)
+    }
+
+    /*
+     * 求射线最近的交点，test(id, t_max) 对编号为 id 的图元求交，返回小于 t_max 的交点距离及交点信息
+     * 返回 (图元编号, 距离, 交点信息)
+     */
+    pub fn intersect<H, F>(&self, ray: &Ray, t_max: f64, mut test: F) -> Option<(usize, f64, H)>
+    where
+        F: FnMut(usize, f64) -> Option<(f64, H)>,
+    {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let inv_d = Vector3::new(1.0 / ray.d.x, 1.0 / ray.d.y, 1.0 / ray.d.z);
+        let mut closest = t_max;
+        let mut ret = None;
+        let mut stack = Vec::with_capacity(64);
+        stack.push(0usize);
+        while let Some(idx) = stack.pop() {
+            let node = &self.nodes[idx];
+            match node.bounds.intersect(ray, &inv_d, closest) {
+                Some(_) => {}
+                None => continue,
+            }
+            if node.count > 0 {
+                for &id in &self.items[node.start..node.start + node.count] {
+                    if let Some((t, hit)) = test(id, closest) {
+                        if t < closest {
+                            closest = t;
+                            ret = Some((id, t, hit));
+                        }
+                    }
+                }
+            } else {
+                // 先访问离射线起点更近的孩子
+                let (left, right) = (idx + 1, node.start);
+                let dl = self.nodes[left].bounds.intersect(ray, &inv_d, closest);
+                let dr = self.nodes[right].bounds.intersect(ray, &inv_d, closest);
+                match (dl, dr) {
+                    (Some((tl, _)), Some((tr, _))) => {
+                        if tl <= tr {
+                            stack.push(right);
+                            stack.push(left);
+                        } else {
+                            stack.push(left);
+                            stack.push(right);
+                        }
+                    }
+                    (Some(_), None) => stack.push(left),
+                    (None, Some(_)) => stack.push(right),
+                    (None, None) => {}
+                }
+            }
+        }
+        ret
+    }
+
+    // 判断射线在 t_max 之前是否与任一图元相交，用于阴影射线
+    pub fn any_hit<F>(&self, ray: &Ray, t_max: f64, mut test: F) -> bool
+    where
+        F: FnMut(usize, f64) -> bool,
+    {
+        if self.nodes.is_empty() {
+            return false;
+        }
+        let inv_d = Vector3::new(1.0 / ray.d.x, 1.0 / ray.d.y, 1.0 / ray.d.z);
+        let mut stack = Vec::with_capacity(64);
+        stack.push(0usize);
+        while let Some(idx) = stack.pop() {
+            let node = &self.nodes[idx];
+            if node.bounds.intersect(ray, &inv_d, t_max).is_none() {
+                continue;
+            }
+            if node.count > 0 {
+                for &id in &self.items[node.start..node.start + node.count] {
+                    if test(id, t_max) {
+                        return true;
+                    }
+                }
+            } else {
+                stack.push(node.start);
+                stack.push(idx + 1);
+            }
+        }
+        false
+    }
+}
@@ -1,11 +1,24 @@
 use crate::util::*;
-extern crate rand;
-use rand::Rng;
+use std::f64::consts::PI;
+
+// 从光源上采样得到的一个点，用于直接光照计算
+pub struct LightSample {
+    pub pos : Vector3,      // 光源上的采样点
+    pub dir : Vector3,      // 从着色点指向采样点的单位向量
+    pub dist : f64,         // 着色点到采样点的距离
+    pub radiance : Color,   // 沿dir到达着色点的辐射亮度
+    pub pdf : f64,          // 关于立体角的概率密度，点光源为 1
+    pub delta : bool,       // 是否来自点光源，此时无法通过其他方式采样到
+}
 
 pub trait Light {
-    fn gen_photon(&self) -> Photon;
+    fn gen_photon(&self, sampler : &mut Sampler) -> Photon;
     fn intersect(&self, ray : &Ray) -> Option<f64>;
     fn get_power(&self) -> Color;
+    // 从着色点 pos 对光源采样，着色点看不到光源发光的一面时返回 None
+    fn sample_li(&self, pos : &Vector3, sampler : &mut Sampler) -> Option<LightSample>;
+    // 从 pos 出发由 sample_li 采样到方向 dir 的概率密度（立体角），点光源为 0
+    fn pdf_li(&self, pos : &Vector3, dir : &Vector3) -> f64;
 }
 
 pub struct DotLight {
@@ -14,10 +27,11 @@ pub struct DotLight {
 }
 
 impl Light for DotLight {
-    fn gen_photon(&self) -> Photon {
+    fn gen_photon(&self, sampler : &mut Sampler) -> Photon {
+        // 在整个球面上均匀发射，每个光子携带 4π 倍的发光强度
         Photon { 
-            ray : Ray { o : self.pos, d : Vector3::random(), }, 
-            power : self.color, 
+            ray : Ray { o : self.pos, d : Vector3::random(sampler), }, 
+            power : self.color.mult(4.0 * PI), 
         }
     }
 
@@ -29,6 +43,25 @@ impl Light for DotLight {
     fn get_power(&self) -> Color {
         self.color
     }
+
+    // 点光源的 color 视为发光强度，到达着色点的亮度按距离平方衰减
+    fn sample_li(&self, pos : &Vector3, _ : &mut Sampler) -> Option<LightSample> {
+        let dist2 = self.pos.distance2(pos);
+        if dist2 < 1e-10 { return None; }
+        let dist = dist2.sqrt();
+        Some(LightSample {
+            pos : self.pos,
+            dir : (self.pos - pos).mult(1.0 / dist),
+            dist,
+            radiance : self.color.mult(1.0 / dist2),
+            pdf : 1.0,
+            delta : true,
+        })
+    }
+
+    fn pdf_li(&self, _ : &Vector3, _ : &Vector3) -> f64 {
+        0.0
+    }
 }
 
 impl DotLight {
@@ -48,33 +81,23 @@ pub struct AreaLight {
 }
 
 impl Light for AreaLight {
-    fn gen_photon(&self) -> Photon {
-        let mut rng = rand::thread_rng();
-        let mut x : f64 = 0.0;
-        let mut y : f64 = 0.0;
-        let mut z : f64 = 0.0;
-        loop {
-            if x * x + y * y + z * z < 1.0 && x * x + y * y > 1e-10 {
-                if Vector3::new(x, y, z).dot(&self.dir) > 0.0 {
-                    break;
-                }
-            }
-            x = rng.gen_range(-1.0, 1.0);
-            y = rng.gen_range(-1.0, 1.0);
-            z = rng.gen_range(-1.0, 1.0);
-        }
+    fn gen_photon(&self, sampler : &mut Sampler) -> Photon {
+        // 朗伯发光面按余弦加权在半球内发射，光子携带的通量为 辐射亮度 * cos * 面积 / 概率密度 = 辐射亮度 * 面积 * π
+        let d = Frame::new(&self.dir).to_world(&sampling::cosine_hemisphere(sampler.get_2d()));
         Photon { 
             ray : Ray { 
-                o : self.pos + self.dx.mult(rng.gen_range(0.0,self.width)) + self.dy.mult(rng.gen_range(0.0,self.height)), 
-                d : Vector3::new(x, y, z).normalize(), 
+                o : self.sample_point(sampler.get_2d()), 
+                d : d.normalize(), 
             }, 
-            power : self.color, 
+            power : self.color.mult(self.width * self.height * PI), 
         }
     }
 
+    // 只有发光的一面可见，与 sample_li 和 gen_photon 一致
     fn intersect(&self, ray : &Ray) -> Option<f64> {
         // 计算ray的方向向量在平面法向量的投影
         let projection = ray.d.dot(&self.dir);
+        if projection >= 0.0 { return None } // 从背面射向光源
         // 计算射线原点到矩形位置的向量
         let vec1 = self.pos - ray.o;
         // 查看在矩形法向量方向上，射线与向量vec1是否同向
@@ -93,10 +116,44 @@ impl Light for AreaLight {
     fn get_power(&self) -> Color {
         self.color
     }
+
+    // 在矩形上均匀采样，color 视为发光面的辐射亮度
+    fn sample_li(&self, pos : &Vector3, sampler : &mut Sampler) -> Option<LightSample> {
+        let point = self.sample_point(sampler.get_2d());
+        let to_light = point - *pos;
+        let dist2 = to_light.norm_squared();
+        if dist2 < 1e-10 { return None; }
+        let dist = dist2.sqrt();
+        let dir = to_light.mult(1.0 / dist);
+        let cos_l = -dir.dot(&self.dir);
+        if cos_l < 1e-10 { return None; }   // 位于光源背面
+        Some(LightSample {
+            pos : point,
+            dir,
+            dist,
+            radiance : self.color,
+            pdf : dist2 / (cos_l * self.width * self.height),
+            delta : false,
+        })
+    }
+
+    fn pdf_li(&self, pos : &Vector3, dir : &Vector3) -> f64 {
+        let cos_l = -dir.dot(&self.dir);
+        if cos_l < 1e-10 { return 0.0; }
+        match self.intersect(&Ray::new(*pos, *dir)) {
+            Some(dist) => dist * dist / (cos_l * self.width * self.height),
+            None => 0.0,
+        }
+    }
 }
 
 impl AreaLight {
     pub fn new(pos : Vector3, dx : Vector3, dy : Vector3, dir : Vector3, color : Color, width : f64, height : f64 ) -> Self {
         AreaLight { pos, dx, dy, dir, color, width, height }
     }
+
+    // 将 [0, 1)² 中的均匀分布映射为矩形上的均匀分布
+    fn sample_point(&self, u : (f64, f64)) -> Vector3 {
+        self.pos + self.dx.mult(u.0 * self.width) + self.dy.mult(u.1 * self.height)
+    }
 }
@@ -0,0 +1,566 @@
+use super::light::*;
+use super::material::Material;
+use super::obj::load_obj;
+use super::primitive::*;
+use super::texture::*;
+use super::Scene;
+use crate::camera::Camera;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/*
+ * 场景描述文件格式
+ *
+ * 每行一条指令，`#` 之后为注释。指令由关键字开头，后面跟若干 `键 值...` 对：
+ *
+ *   camera      position X Y Z  direction X Y Z  size W H
+ *   texture     NAME  image  file FILE  [wrap MODE]  [filter MODE]  [scale U V]  [offset U V]
+ *   texture     NAME  checker  even R G B  odd R G B  [scale U V]
+ *   texture     NAME  noise  low R G B  high R G B  [scale F]  [octaves N]  [seed N]
+ *   texture     NAME  marble  low R G B  high R G B  [scale F]  [turbulence F]  [octaves N]  [seed N]
+ *   texture     NAME  gradient  from R G B  to R G B  start X Y Z  end X Y Z
+ *   material    NAME  color R G B  [diffuse F] [specular F] [refraction F] [rindex F] [roughness F]  [texture NAME]
+ *   plane       normal X Y Z  distance F  material NAME
+ *   sphere      center X Y Z  radius F  material NAME
+ *   profile     NAME  R Z  R Z ...
+ *   bezier      position X Y Z  material NAME  [profile NAME]  [axis X Y Z]  [scale F]
+ *   triangle    v0 X Y Z  v1 X Y Z  v2 X Y Z  material NAME
+ *   mesh        file FILE  [material NAME]  [scale F]  [translate X Y Z]
+ *   area_light  position X Y Z  dx X Y Z  dy X Y Z  normal X Y Z  color R G B  width F  height F
+ *   point_light position X Y Z  color R G B
+ *
+ * 纹理、材质和剖面必须先声明再使用，图像和模型的路径相对于场景文件所在目录。
+ * 材质的 diffuse、specular、refraction 分别为漫反射、金属反射和电介质折射分量的权重，roughness 在 [0, 1] 中，
+ * 为 0 时反射和折射都是理想镜面，否则使用 GGX 微表面模型，见 Material。
+ * 材质引用纹理时由纹理代替 color 作为表面颜色；图像纹理的 wrap 可取 repeat、mirror、clamp，
+ * filter 可取 nearest、bilinear，纹理坐标先乘以 scale 再加上 offset。
+ * checker 在纹理坐标上交替两种颜色；noise、marble 与 gradient 是实体纹理，按交点的世界坐标取色，
+ * noise 与 marble 的 scale 是噪声频率，相同 seed 生成相同的图案。
+ * profile 声明旋转体的剖面曲线，控制点为 (半径, 高度)，个数为 3n + 1，表示首尾相接的 n 段三次 Bezier 曲线；
+ * bezier 未指定 profile 时使用默认的花瓶剖面，axis 为旋转轴方向，默认为 z 轴。
+ * mesh 读取 Wavefront OBJ 模型，未指定 material 时使用模型 MTL 文件中的材质。
+ */
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(PathBuf, std::io::Error),
+    Parse { line: usize, msg: String },
+    Model { path: PathBuf, line: usize, msg: String }, // 模型文件中的错误
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Io(path, e) => write!(f, "cannot read {}: {}", path.display(), e),
+            SceneError::Parse { line, msg } => write!(f, "line {}: {}", line, msg),
+            SceneError::Model { path, line, msg } => {
+                write!(f, "{}: line {}: {}", path.display(), line, msg)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+// 一条指令中的各个字段，键 -> 值
+struct Directive<'a> {
+    line: usize,
+    keyword: &'a str,
+    fields: HashMap<&'a str, Vec<&'a str>>,
+}
+
+impl<'a> Directive<'a> {
+    fn error(&self, msg: String) -> SceneError {
+        SceneError::Parse { line: self.line, msg }
+    }
+
+    fn raw(&self, key: &str) -> Result<&[&'a str], SceneError> {
+        match self.fields.get(key) {
+            Some(values) => Ok(values),
+            None => Err(self.error(format!("`{}` is missing `{}`", self.keyword, key))),
+        }
+    }
+
+    fn has(&self, key: &str) -> bool {
+        self.fields.contains_key(key)
+    }
+
+    fn number(&self, key: &str) -> Result<f64, SceneError> {
+        let token = self.raw(key)?[0];
+        token
+            .parse::<f64>()
+            .map_err(|_| self.error(format!("`{}` expects a number, found `{}`", key, token)))
+    }
+
+    fn number_or(&self, key: &str, default: f64) -> Result<f64, SceneError> {
+        if self.has(key) {
+            self.number(key)
+        } else {
+            Ok(default)
+        }
+    }
+
+    fn size(&self, key: &str, idx: usize) -> Result<usize, SceneError> {
+        let token = self.raw(key)?[idx];
+        token.parse::<usize>().map_err(|_| {
+            self.error(format!("`{}` expects a positive integer, found `{}`", key, token))
+        })
+    }
+
+    fn triple(&self, key: &str) -> Result<[f64; 3], SceneError> {
+        let mut ret = [0.0; 3];
+        for (idx, token) in self.raw(key)?.iter().enumerate() {
+            ret[idx] = token.parse::<f64>().map_err(|_| {
+                self.error(format!("`{}` expects three numbers, found `{}`", key, token))
+            })?;
+        }
+        Ok(ret)
+    }
+
+    fn vector(&self, key: &str) -> Result<Vector3, SceneError> {
+        let [x, y, z] = self.triple(key)?;
+        Ok(Vector3::new(x, y, z))
+    }
+
+    fn direction(&self, key: &str) -> Result<Vector3, SceneError> {
+        let v = self.vector(key)?;
+        if v.norm() < EPS {
+            return Err(self.error(format!("`{}` must not be a zero vector", key)));
+        }
+        Ok(v)
+    }
+
+    fn color(&self, key: &str) -> Result<Color, SceneError> {
+        let [r, g, b] = self.triple(key)?;
+        Ok(Color::new(r, g, b))
+    }
+
+    fn text(&self, key: &str) -> Result<&'a str, SceneError> {
+        Ok(self.raw(key)?[0])
+    }
+
+    fn pair(&self, key: &str) -> Result<(f64, f64), SceneError> {
+        let values = self.raw(key)?;
+        let parse = |token: &str| {
+            token.parse::<f64>().map_err(|_| {
+                self.error(format!("`{}` expects two numbers, found `{}`", key, token))
+            })
+        };
+        Ok((parse(values[0])?, parse(values[1])?))
+    }
+
+    // 用 FromStr 解析的取值，如纹理的 wrap、filter
+    fn parsed<T: FromStr<Err = String>>(&self, key: &str) -> Result<T, SceneError> {
+        self.text(key)?.parse::<T>().map_err(|e| self.error(e))
+    }
+}
+
+// 有限的正数，NaN 与无穷大都不是
+fn is_positive(x: f64) -> bool {
+    x.is_finite() && x > 0.0
+}
+
+// 每种指令允许的键以及该键后跟的值的个数
+fn schema(keyword: &str) -> Option<&'static [(&'static str, usize)]> {
+    let keys: &'static [(&'static str, usize)] = match keyword {
+        "camera" => &[("position", 3), ("direction", 3), ("size", 2)],
+        "material" => &[
+            ("color", 3),
+            ("diffuse", 1),
+            ("specular", 1),
+            ("refraction", 1),
+            ("rindex", 1),
+            ("roughness", 1),
+            ("texture", 1),
+        ],
+        "texture" => &[],
+        "plane" => &[("normal", 3), ("distance", 1), ("material", 1)],
+        "sphere" => &[("center", 3), ("radius", 1), ("material", 1)],
+        "profile" => &[],
+        "bezier" => &[
+            ("position", 3),
+            ("material", 1),
+            ("profile", 1),
+            ("axis", 3),
+            ("scale", 1),
+        ],
+        "triangle" => &[("v0", 3), ("v1", 3), ("v2", 3), ("material", 1)],
+        "mesh" => &[("file", 1), ("material", 1), ("scale", 1), ("translate", 3)],
+        "area_light" => &[
+            ("position", 3),
+            ("dx", 3),
+            ("dy", 3),
+            ("normal", 3),
+            ("color", 3),
+            ("width", 1),
+            ("height", 1),
+        ],
+        "point_light" => &[("position", 3), ("color", 3)],
+        _ => return None,
+    };
+    Some(keys)
+}
+
+// 每种纹理允许的键以及该键后跟的值的个数
+fn texture_schema(kind: &str) -> Option<&'static [(&'static str, usize)]> {
+    let keys: &'static [(&'static str, usize)] = match kind {
+        "image" => &[("file", 1), ("wrap", 1), ("filter", 1), ("scale", 2), ("offset", 2)],
+        "checker" => &[("even", 3), ("odd", 3), ("scale", 2)],
+        "noise" => &[("low", 3), ("high", 3), ("scale", 1), ("octaves", 1), ("seed", 1)],
+        "marble" => &[
+            ("low", 3),
+            ("high", 3),
+            ("scale", 1),
+            ("turbulence", 1),
+            ("octaves", 1),
+            ("seed", 1),
+        ],
+        "gradient" => &[("from", 3), ("to", 3), ("start", 3), ("end", 3)],
+        _ => return None,
+    };
+    Some(keys)
+}
+
+fn tokenize(line: usize, text: &str) -> Result<Option<Directive<'_>>, SceneError> {
+    let content = match text.find('#') {
+        Some(pos) => &text[..pos],
+        None => text,
+    };
+    let mut tokens = content.split_whitespace();
+    let keyword = match tokens.next() {
+        Some(k) => k,
+        None => return Ok(None),
+    };
+    let keys = schema(keyword).ok_or_else(|| SceneError::Parse {
+        line,
+        msg: format!("unknown directive `{}`", keyword),
+    })?;
+    let mut fields = HashMap::new();
+    // material、profile 与 texture 指令的第一个参数是名字
+    if keyword == "material" || keyword == "profile" || keyword == "texture" {
+        match tokens.next() {
+            Some(name) => fields.insert("name", vec![name]),
+            None => {
+                return Err(SceneError::Parse { line, msg: format!("`{}` needs a name", keyword) })
+            }
+        };
+    }
+    // texture 指令的第二个参数是纹理种类，不同种类允许的键不同
+    let keys = if keyword == "texture" {
+        let kind = tokens.next().ok_or_else(|| SceneError::Parse {
+            line,
+            msg: "`texture` needs a kind".to_string(),
+        })?;
+        fields.insert("kind", vec![kind]);
+        texture_schema(kind).ok_or_else(|| SceneError::Parse {
+            line,
+            msg: format!("unknown texture kind `{}`", kind),
+        })?
+    } else {
+        keys
+    };
+    // profile 指令之后全部是控制点坐标
+    if keyword == "profile" {
+        fields.insert("points", tokens.collect());
+        return Ok(Some(Directive { line, keyword, fields }));
+    }
+    while let Some(key) = tokens.next() {
+        let arity = match keys.iter().find(|(k, _)| *k == key) {
+            Some((_, n)) => *n,
+            None => {
+                return Err(SceneError::Parse {
+                    line,
+                    msg: format!("unknown key `{}` for `{}`", key, keyword),
+                })
+            }
+        };
+        let values: Vec<&str> = tokens.by_ref().take(arity).collect();
+        if values.len() != arity {
+            return Err(SceneError::Parse {
+                line,
+                msg: format!("`{}` expects {} value(s), found {}", key, arity, values.len()),
+            });
+        }
+        if fields.insert(key, values).is_some() {
+            return Err(SceneError::Parse { line, msg: format!("duplicate key `{}`", key) });
+        }
+    }
+    Ok(Some(Directive { line, keyword, fields }))
+}
+
+struct Loader<'a> {
+    base_dir: &'a Path,
+    scene: Scene,
+    materials: HashMap<String, Arc<Material>>,
+    textures: HashMap<String, Arc<dyn Texture>>,
+    profiles: HashMap<String, Vec<(f64, f64)>>,
+    next_id: usize,
+}
+
+impl<'a> Loader<'a> {
+    fn material(&self, d: &Directive) -> Result<Arc<Material>, SceneError> {
+        let name = d.text("material")?;
+        match self.materials.get(name) {
+            Some(m) => Ok(m.clone()),
+            None => Err(d.error(format!("undefined material `{}`", name))),
+        }
+    }
+
+    // 种类已在 tokenize 中检查
+    fn texture(&self, d: &Directive) -> Result<Arc<dyn Texture>, SceneError> {
+        let texture: Arc<dyn Texture> = match d.text("kind")? {
+            "image" => {
+                let path = self.base_dir.join(d.text("file")?);
+                if !path.is_file() {
+                    return Err(d.error(format!("texture {} does not exist", path.display())));
+                }
+                let mut texture = ImageTexture::open(&path)
+                    .map_err(|e| d.error(format!("cannot read texture {}: {}", path.display(), e)))?;
+                if d.has("wrap") {
+                    texture.set_wrap(d.parsed("wrap")?);
+                }
+                if d.has("filter") {
+                    texture.set_filter(d.parsed("filter")?);
+                }
+                if d.has("scale") {
+                    let (su, sv) = d.pair("scale")?;
+                    texture.set_scale(su, sv);
+                }
+                if d.has("offset") {
+                    let (ou, ov) = d.pair("offset")?;
+                    texture.set_offset(ou, ov);
+                }
+                Arc::new(texture)
+            }
+            "checker" => {
+                let mut texture = CheckerTexture::new(d.color("even")?, d.color("odd")?);
+                if d.has("scale") {
+                    let (su, sv) = d.pair("scale")?;
+                    texture.set_scale(su, sv);
+                }
+                Arc::new(texture)
+            }
+            "noise" => {
+                let seed = if d.has("seed") { d.size("seed", 0)? } else { 0 };
+                let mut texture = NoiseTexture::new(d.color("low")?, d.color("high")?, seed as u64);
+                texture.set_scale(d.number_or("scale", 1.0)?);
+                if d.has("octaves") {
+                    texture.set_octaves(d.size("octaves", 0)?);
+                }
+                Arc::new(texture)
+            }
+            "marble" => {
+                let seed = if d.has("seed") { d.size("seed", 0)? } else { 0 };
+                let mut texture = MarbleTexture::new(d.color("low")?, d.color("high")?, seed as u64);
+                texture.set_scale(d.number_or("scale", 1.0)?);
+                if d.has("turbulence") {
+                    texture.set_turbulence(d.number("turbulence")?);
+                }
+                if d.has("octaves") {
+                    texture.set_octaves(d.size("octaves", 0)?);
+                }
+                Arc::new(texture)
+            }
+            "gradient" => {
+                let (start, end) = (d.vector("start")?, d.vector("end")?);
+                Arc::new(GradientTexture::new(d.color("from")?, d.color("to")?, start, end))
+            }
+            kind => unreachable!("texture kind `{}`", kind),
+        };
+        Ok(texture)
+    }
+
+    fn gen_id(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id - 1
+    }
+
+    fn handle(&mut self, d: Directive) -> Result<(), SceneError> {
+        match d.keyword {
+            "camera" => {
+                if self.scene.camera.is_some() {
+                    return Err(d.error("camera is declared twice".to_string()));
+                }
+                let (width, height) = (d.size("size", 0)?, d.size("size", 1)?);
+                if width == 0 || height == 0 {
+                    return Err(d.error("camera size must be positive".to_string()));
+                }
+                let mut camera = Camera::new();
+                camera.set_size(width, height);
+                camera.set_pos(&d.vector("position")?);
+                camera.set_dir(d.direction("direction")?);
+                self.scene.camera = Some(camera);
+            }
+            "texture" => {
+                let name = d.text("name")?;
+                let texture = self.texture(&d)?;
+                if self.textures.insert(name.to_string(), texture).is_some() {
+                    return Err(d.error(format!("texture `{}` is declared twice", name)));
+                }
+            }
+            "material" => {
+                let name = d.text("name")?;
+                let mut material = Material::new(
+                    d.color("color")?,
+                    d.number_or("diffuse", 1.0)?,
+                    d.number_or("specular", 0.0)?,
+                    d.number_or("refraction", 0.0)?,
+                    d.number_or("rindex", 1.0)?,
+                );
+                let roughness = d.number_or("roughness", 0.0)?;
+                if !(0.0..=1.0).contains(&roughness) {
+                    return Err(d.error("material roughness must be in [0, 1]".to_string()));
+                }
+                material.set_roughness(roughness);
+                if d.has("texture") {
+                    let texture = d.text("texture")?;
+                    match self.textures.get(texture) {
+                        Some(t) => material.set_texture(t.clone()),
+                        None => return Err(d.error(format!("undefined texture `{}`", texture))),
+                    }
+                }
+                if self.materials.insert(name.to_string(), Arc::new(material)).is_some() {
+                    return Err(d.error(format!("material `{}` is declared twice", name)));
+                }
+            }
+            "plane" => {
+                let id = self.gen_id();
+                let plane = Plane::new(
+                    id,
+                    d.direction("normal")?,
+                    d.number("distance")?,
+                    self.material(&d)?,
+                );
+                self.scene.objects.push(Box::new(plane));
+            }
+            "sphere" => {
+                let id = self.gen_id();
+                let radius = d.number("radius")?;
+                if !is_positive(radius) {
+                    return Err(d.error("sphere radius must be positive and finite".to_string()));
+                }
+                let sphere = Sphere::new(id, radius, d.vector("center")?, self.material(&d)?);
+                self.scene.objects.push(Box::new(sphere));
+            }
+            "profile" => {
+                let name = d.text("name")?;
+                let mut coords = Vec::new();
+                for token in d.raw("points")? {
+                    coords.push(token.parse::<f64>().map_err(|_| {
+                        d.error(format!("`profile` expects numbers, found `{}`", token))
+                    })?);
+                }
+                let points: Vec<(f64, f64)> = coords.chunks(2).map(|c| (c[0], c[c.len() - 1])).collect();
+                if coords.len() % 2 != 0 || !BazierCurve::is_valid_profile(&points) {
+                    return Err(d.error(format!(
+                        "`profile` expects 3n + 1 (radius, height) pairs, found {} numbers",
+                        coords.len()
+                    )));
+                }
+                if self.profiles.insert(name.to_string(), points).is_some() {
+                    return Err(d.error(format!("profile `{}` is declared twice", name)));
+                }
+            }
+            "bezier" => {
+                let id = self.gen_id();
+                let mut curve = BazierCurve::new(id, d.vector("position")?, self.material(&d)?);
+                if d.has("profile") {
+                    let name = d.text("profile")?;
+                    match self.profiles.get(name) {
+                        Some(points) => curve.set_profile(points),
+                        None => return Err(d.error(format!("undefined profile `{}`", name))),
+                    }
+                }
+                if d.has("axis") {
+                    curve.set_axis(d.direction("axis")?);
+                }
+                let scale = d.number_or("scale", 1.0)?;
+                if !is_positive(scale) {
+                    return Err(d.error("bezier scale must be positive and finite".to_string()));
+                }
+                curve.set_scale(scale);
+                self.scene.objects.push(Box::new(curve));
+            }
+            "triangle" => {
+                let id = self.gen_id();
+                let triangle = Triangle::new(
+                    id,
+                    d.vector("v0")?,
+                    d.vector("v1")?,
+                    d.vector("v2")?,
+                    self.material(&d)?,
+                );
+                self.scene.objects.push(Box::new(triangle));
+            }
+            "mesh" => {
+                let material = if d.has("material") { Some(self.material(&d)?) } else { None };
+                let scale = d.number_or("scale", 1.0)?;
+                if !scale.is_finite() || scale == 0.0 {
+                    return Err(d.error("mesh scale must be finite and not zero".to_string()));
+                }
+                let offset = if d.has("translate") { d.vector("translate")? } else { Vector3::zeros() };
+                let mut model = load_obj(self.base_dir.join(d.text("file")?))?;
+                model.transform(scale, offset);
+                let meshes = model.into_meshes(self.next_id, material);
+                self.next_id += meshes.len();
+                for mesh in meshes {
+                    self.scene.objects.push(Box::new(mesh));
+                }
+            }
+            "area_light" => {
+                let (width, height) = (d.number("width")?, d.number("height")?);
+                if !is_positive(width) || !is_positive(height) {
+                    return Err(d.error("area light width and height must be positive and finite".to_string()));
+                }
+                let light = AreaLight::new(
+                    d.vector("position")?,
+                    d.direction("dx")?.normalize(),
+                    d.direction("dy")?.normalize(),
+                    d.direction("normal")?.normalize(),
+                    d.color("color")?,
+                    width,
+                    height,
+                );
+                self.scene.illumiants.push(Arc::new(light));
+            }
+            "point_light" => {
+                let light = DotLight::new(d.vector("position")?, d.color("color")?);
+                self.scene.illumiants.push(Arc::new(light));
+            }
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+}
+
+impl Scene {
+    // 从场景描述文件中读取场景
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Scene, SceneError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| SceneError::Io(path.to_path_buf(), e))?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+        Scene::parse(&text, base_dir)
+    }
+
+    // 从字符串中读取场景，base_dir 用于解析纹理等相对路径
+    pub fn parse(text: &str, base_dir: &Path) -> Result<Scene, SceneError> {
+        let mut loader = Loader {
+            base_dir,
+            scene: Scene::new(),
+            materials: HashMap::new(),
+            textures: HashMap::new(),
+            profiles: HashMap::new(),
+            next_id: 0,
+        };
+        for (idx, line) in text.lines().enumerate() {
+            if let Some(directive) = tokenize(idx + 1, line)? {
+                loader.handle(directive)?;
+            }
+        }
+        loader.scene.build_bvh();
+        Ok(loader.scene)
+    }
+}
@@ -1,100 +1,152 @@
 use super::*;
+use super::bsdf::*;
+use super::texture::Texture;
 use crate::consts::EPS;
-extern crate rand;
-use rand::Rng;
-use std::f64::consts::PI;
 
+/*
+ * 材质由若干 BSDF 分量按权重混合而成：
+ *   diffuse    理想漫反射
+ *   specular   金属反射，roughness 为 0 时为理想镜面，否则为 GGX 微表面
+ *   refraction 电介质的反射与折射，roughness 为 0 时为光滑玻璃，否则为粗糙电介质，折射率为 rindex
+ * 各分量的权重之和超过 1 时按比例缩放，不足 1 的部分视为被吸收
+ */
 pub struct Material {
     color: Color,
+    texture: Option<Arc<dyn Texture>>,  // 有纹理时代替 color 作为表面颜色
     pub diffuse : f64,
     pub specular : f64,
     pub refraction : f64,
     pub rindex : f64,
+    roughness : f64,
+    lobes : Vec<(f64, Box<dyn Bxdf>)>,  // 各分量及其被选中的概率
 }
 
 impl Material {
     pub fn new(color: Color, diffuse: f64, specular: f64, refraction: f64, rindex: f64) -> Self {
-        Material { color, diffuse, specular, refraction, rindex }
+        let mut material = Material {
+            color,
+            texture: None,
+            diffuse,
+            specular,
+            refraction,
+            rindex,
+            roughness: 0.0,
+            lobes: Vec::new(),
+        };
+        material.build_lobes();
+        material
+    }
+
+    pub fn set_texture(&mut self, texture: Arc<dyn Texture>) {
+        self.texture = Some(texture);
+    }
+
+    // roughness 在 [0, 1] 中，影响 specular 与 refraction 两个分量
+    pub fn set_roughness(&mut self, roughness: f64) {
+        self.roughness = roughness.clamp(0.0, 1.0);
+        self.build_lobes();
+    }
+
+    pub fn roughness(&self) -> f64 {
+        self.roughness
+    }
+
+    fn build_lobes(&mut self) {
+        let total = (self.diffuse + self.specular + self.refraction).max(1.0);
+        let rough = self.roughness > EPS;
+        self.lobes.clear();
+        if self.diffuse > EPS {
+            self.lobes.push((self.diffuse / total, Box::new(Lambertian)));
+        }
+        if self.specular > EPS {
+            let lobe: Box<dyn Bxdf> = if rough { Box::new(GgxConductor::new(self.roughness)) } else { Box::new(Mirror) };
+            self.lobes.push((self.specular / total, lobe));
+        }
+        if self.refraction > EPS {
+            let lobe: Box<dyn Bxdf> = if rough { Box::new(RoughDielectric::new(self.roughness)) } else { Box::new(Glass) };
+            self.lobes.push((self.refraction / total, lobe));
+        }
+    }
+
+    // 是否只有镜面反射与折射，此时无法在该表面上估计光子密度或对光源采样
+    pub fn is_delta(&self) -> bool {
+        self.lobes.iter().all(|(_, lobe)| lobe.is_delta())
+    }
+
+    // 各分量的 f(wo, wi) 之和，不含余弦项
+    pub fn eval(&self, sh : &Shading, wo : &Vector3, wi : &Vector3) -> Color {
+        self.lobes.iter().filter(|(_, lobe)| !lobe.is_delta()).fold(Color::default(), |sum, (p, lobe)| {
+            sum + lobe.eval(sh, wo, wi).mult(*p)
+        })
+    }
+
+    // 由 sample 采样得到 wi 的概率密度（立体角），不含 delta 分量
+    pub fn pdf(&self, sh : &Shading, wo : &Vector3, wi : &Vector3) -> f64 {
+        self.lobes.iter().filter(|(_, lobe)| !lobe.is_delta()).map(|(p, lobe)| p * lobe.pdf(sh, wo, wi)).sum()
     }
 
     /*
-     * 计算漫反射的单位方向
-     * ray_x : 入射的射线
-     * ray_n : 法向量
+     * 先用 uc 按概率选择一个分量，再对该分量采样，被吸收时返回 None
+     * 采样到非 delta 分量时，权重与概率密度按所有非 delta 分量计算，以便与光源采样做多重重要性采样
      */
-     pub fn cal_diffuse_ray(&self, vec_n : &Vector3) -> Option<Vector3> {
-         if self.diffuse > EPS {
-             let mut rng = rand::thread_rng();
-             let vert = vec_n.get_vertical_vec();
-             let theta = rng.gen_range(0.0, 2.0) * PI;
-             let phi = rng.gen_range(0.0, 2.0) * PI;
-             return Some(vec_n.rotate(&vert, theta).rotate(vec_n, phi).normalize());
-         }
-         None
-     }
-
-     //计算镜面反射的单位方向
-     //ray_x : 入射的射线
-     //ray_n : 法向量
-     pub fn cal_specular_ray(&self, vec_x : &Vector3, vec_n : &Vector3) -> Option<Vector3> {
-         if self.specular > EPS {
-             if vec_x.dot(vec_n) < 0.0 {
-                 return Some(*vec_x - vec_n.mult(2.0 * vec_n.dot(vec_x)));
-             } else {
-                 return Some(*vec_x + vec_n.mult(2.0 * vec_n.dot(vec_x)));
-             }
-         }
-         None
-     }
-
-     //计算折射的单位方向
-     //ray_x : 入射的射线
-     //ray_n : 法向量
-     //refracted : 是否已经折射过奇数次
-    pub fn cal_refractive_ray(&self, vec_x: &Vector3, vec_n: &Vector3, refracted : bool) -> Option<Vector3> {
-        if self.refraction > EPS {
-            let mut n = self.rindex;
-            if !refracted {
-                n = 1.0 / n;
+    pub fn sample(&self, sh : &Shading, wo : &Vector3, uc : f64, u : (f64, f64)) -> Option<BsdfSample> {
+        let mut uc = uc;
+        for (p, lobe) in self.lobes.iter() {
+            if uc >= *p {
+                uc -= p;
+                continue;
+            }
+            let mut sample = lobe.sample(sh, wo, uc / p, u)?;
+            if sample.delta {
+                return Some(sample);
             }
-            let cos_i = -vec_n.dot(vec_x);
-            let cos2_t = 1.0 - ( n * n) * ( 1.0 - cos_i * cos_i);
-            if cos2_t > EPS {
-                return Some(vec_x.mult(n) + vec_n.mult(n * cos_i - cos2_t.sqrt()));
+            let pdf = self.pdf(sh, wo, &sample.dir);
+            if pdf < EPS {
+                return None;
             }
+            let cos = sample.dir.dot(&sh.normal).abs();
+            sample.weight = self.eval(sh, wo, &sample.dir).mult(cos / pdf);
+            sample.pdf = pdf;
+            return Some(sample);
         }
         None
     }
 
+    // 各 delta 分量的全部出射方向，权重已乘上分量被选中的概率
+    pub fn delta_branches(&self, sh : &Shading, wo : &Vector3) -> Vec<BsdfSample> {
+        let mut ret = Vec::new();
+        for (p, lobe) in self.lobes.iter().filter(|(_, lobe)| lobe.is_delta()) {
+            for mut branch in lobe.branches(sh, wo) {
+                branch.weight = branch.weight.mult(*p);
+                ret.push(branch);
+            }
+        }
+        ret
+    }
+
     pub fn is_diffuse(&self) -> bool {
         self.diffuse > EPS
     }
 
+    // 是否有理想镜面反射分量
     pub fn is_specular(&self) -> bool {
-        self.specular > EPS
+        self.specular > EPS && self.roughness <= EPS
     }
 
+    // 是否有光滑的折射分量
     pub fn is_refractive(&self) -> bool {
-        self.refraction > EPS
-    }
-    
-    // TODO 正确计算漫反射分量
-    pub fn brdf(&self, ray_r : &Vector3, vec_n : &Vector3, ray_i : &Vector3) -> f64 {
-        let mut ret = 0.0;
-        let test = ray_r.dot(vec_n);
-        if self.is_diffuse() && test > EPS { // 存在漫反射分量
-            ret += self.diffuse * test;
-        }
-        if let Some(refl) = self.cal_specular_ray(&ray_i.mult(-1.0), vec_n) {   // 存在镜面反射
-            let projection = refl.dot(ray_r);
-            if projection > EPS {
-                ret += self.specular * projection.powi(10);
-            }
-        }
-        ret.clamp(0.0, 1.0)
+        self.refraction > EPS && self.roughness <= EPS
     }
 
     pub fn color(&self) -> Color {
         self.color
     }
+
+    // 交点处的表面颜色，uv 为曲面参数
+    pub fn color_at(&self, uv: (f64, f64), pos: &Vector3) -> Color {
+        match &self.texture {
+            Some(texture) => texture.value(uv, pos),
+            None => self.color,
+        }
+    }
 }
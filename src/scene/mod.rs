@@ -1,17 +1,35 @@
+pub mod bsdf;
+mod bvh;
 mod light;
+mod loader;
 pub mod material;
+mod obj;
 pub mod primitive;
+pub mod texture;
 
+use self::bvh::Bvh;
 use self::light::*;
+pub use self::light::LightSample;
+pub use self::loader::SceneError;
+pub use self::obj::{load_obj, ObjModel};
 use self::material::Material;
 use self::primitive::*;
+use self::texture::ImageTexture;
 pub use super::util::*;
+use crate::camera::Camera;
 use std::boxed::Box;
 use std::sync::Arc;
 
+// 阴影射线两端留出的距离，避免与出发表面及光源所在平面相交
+const SHADOW_EPS: f64 = 1e-3;
+
 pub struct Scene {
     objects: Vec<Box<dyn Primitive + Send + Sync>>, // 代表场景中的各个物体
     illumiants: Vec<Arc<dyn Light + Send + Sync>>,  // 代表场景中的各个光源
+    camera: Option<Camera>,                          // 场景文件中声明的相机
+    bvh: Bvh,                                        // 有包围盒的物体组成的层次包围盒
+    unbounded: Vec<usize>,                           // 没有包围盒的物体，逐个求交
+    indexed: usize,                                  // objects 中已经建立索引的物体数量
 }
 
 impl Scene {
@@ -19,7 +37,50 @@ impl Scene {
         Scene {
             objects: Vec::new(),
             illumiants: Vec::new(),
+            camera: None,
+            bvh: Bvh::build(Vec::new()),
+            unbounded: Vec::new(),
+            indexed: 0,
+        }
+    }
+
+    pub fn add_object(&mut self, object: Box<dyn Primitive + Send + Sync>) {
+        self.objects.push(object);
+    }
+
+    // 为所有物体重新建立层次包围盒，添加物体之后调用；尚未建立索引的物体会被逐个求交
+    pub fn build_bvh(&mut self) {
+        let mut items = Vec::new();
+        self.unbounded.clear();
+        for (i, obj) in self.objects.iter().enumerate() {
+            match obj.bounding_box() {
+                Some(bounds) => items.push((i, bounds)),
+                None => self.unbounded.push(i),
+            }
         }
+        self.bvh = Bvh::build(items);
+        self.indexed = self.objects.len();
+    }
+
+    // 场景中所有有界物体的包围盒，平面等无界物体不计入；没有有界物体时为空包围盒
+    pub fn bounds(&self) -> AABB {
+        self.objects
+            .iter()
+            .filter_map(|obj| obj.bounding_box())
+            .fold(AABB::empty(), |b, obj_bounds| b.union(&obj_bounds))
+    }
+
+    // 场景中是否有无界物体
+    pub fn is_bounded(&self) -> bool {
+        self.objects.iter().all(|obj| obj.bounding_box().is_some())
+    }
+
+    // 不在层次包围盒中的物体
+    fn linear_objects(&self) -> impl Iterator<Item = usize> + '_ {
+        self.unbounded
+            .iter()
+            .cloned()
+            .chain(self.indexed..self.objects.len())
     }
 
     pub fn init(&mut self) {
@@ -35,7 +96,6 @@ impl Scene {
                 0.0,
                 2.0,
             )),
-            None,
         )));
         self.objects.push(Box::new(Plane::new(
             // Right
@@ -49,7 +109,6 @@ impl Scene {
                 0.0,
                 2.0,
             )),
-            None,
         )));
         self.objects.push(Box::new(Plane::new(
             // Top
@@ -63,21 +122,22 @@ impl Scene {
                 0.0,
                 2.0,
             )),
-            None,
         )));
+        // 地板贴图每 600 个单位重复一次
+        let mut floor = Material::new(Color::new(0.75, 0.75, 0.75), 1.0, 0.0, 0.0, 2.0);
+        match ImageTexture::open("floor.png") {
+            Ok(mut texture) => {
+                texture.set_scale(1.0 / 600.0, 1.0 / 600.0);
+                floor.set_texture(Arc::new(texture));
+            }
+            Err(e) => warn!("cannot read floor.png: {}", e),
+        }
         self.objects.push(Box::new(Plane::new(
             // Bottom
             3,
             Vector3::new(0.0, 0.0, 1.0),
             100.0,
-            Arc::new(Material::new(
-                Color::new(0.75, 0.75, 0.75),
-                1.0,
-                0.0,
-                0.0,
-                2.0,
-            )),
-            Some("floor.png"),
+            Arc::new(floor),
         )));
         self.objects.push(Box::new(Plane::new(
             //Back
@@ -91,7 +151,6 @@ impl Scene {
                 0.0,
                 2.0,
             )),
-            None,
         )));
         self.objects.push(Box::new(Plane::new(
             //Front
@@ -105,7 +164,6 @@ impl Scene {
                 0.0,
                 2.0,
             )),
-            None,
         )));
         //self.objects.push(Box::new(Sphere::new(
         //6,
@@ -146,39 +204,40 @@ impl Scene {
             200.0,
             200.0,
         )));
+        self.build_bvh();
     }
 
     // 求给定射线在场景中的碰撞点
     pub fn intersect(&self, ray: &Ray) -> Option<Collider> {
-        let inf: f64 = 1e20;
-        let mut t: f64 = 1e20;
-        let mut id: usize = 0;
-        for i in 0..self.objects.len() {
-            if let Some(d) = self.objects[i].intersect(ray) {
-                if d < t {
-                    t = d;
-                    id = i;
-                }
-            }
-        }
-        if t < inf {
-            let position = ray.o + ray.d.mult(t);
-            let mut norm_vec = self.objects[id].get_normal_vec(&position);
-            if norm_vec.dot(&ray.d) > 0.0 {
-                norm_vec = norm_vec.mult(-1.0);
+        let objects = &self.objects;
+        let test = |i: usize, t_max: f64| {
+            objects[i]
+                .intersect(ray)
+                .filter(|hit| hit.distance < t_max)
+                .map(|hit| (hit.distance, hit))
+        };
+        let mut nearest = self.bvh.intersect(ray, f64::INFINITY, test);
+        for i in self.linear_objects() {
+            let t_max = nearest.as_ref().map_or(f64::INFINITY, |n| n.1);
+            if let Some((t, hit)) = test(i, t_max) {
+                nearest = Some((i, t, hit));
             }
-            return Some(Collider {
-                pos: position,
-                material: self.objects[id].get_material(),
-                norm_vec,
-                distance: t,
-                in_direction: ray.d,
-                hash_value: self.objects[id].get_hash(),
-                color: self.objects[id].get_color(&position),
-            });
-        } else {
-            return None;
         }
+        let (id, t, hit) = nearest?;
+        let front_face = hit.normal.dot(&ray.d) <= 0.0;
+        let side = if front_face { 1.0 } else { -1.0 };
+        Some(Collider {
+            pos: hit.pos,
+            material: self.objects[id].get_material(),
+            norm_vec: hit.normal.mult(side),
+            shading_normal: hit.shading_normal.mult(side),
+            front_face,
+            distance: t,
+            in_direction: ray.d,
+            hash_value: self.objects[id].get_hash(),
+            color: self.objects[id].get_color(&hit),
+            uv: hit.uv,
+        })
     }
 
     pub fn intersect_light(&self, ray: &Ray) -> Option<LightCollider> {
@@ -203,6 +262,45 @@ impl Scene {
         }
     }
 
+    // 判断两点之间是否没有物体遮挡
+    pub fn visible(&self, from: &Vector3, to: &Vector3) -> bool {
+        let dist = from.distance(to);
+        if dist < 2.0 * SHADOW_EPS {
+            return true;
+        }
+        let dir = (*to - *from).mult(1.0 / dist);
+        let ray = Ray::new(*from + dir.mult(SHADOW_EPS), dir);
+        let t_max = dist - 2.0 * SHADOW_EPS;
+        let blocked = |i: usize, t_max: f64| match self.objects[i].intersect(&ray) {
+            Some(hit) => hit.distance < t_max,
+            None => false,
+        };
+        if self.bvh.any_hit(&ray, t_max, blocked) {
+            return false;
+        }
+        !self.linear_objects().any(|i| blocked(i, t_max))
+    }
+
+    // 随机选择一个光源并对其采样，返回的概率密度包含了选择光源的概率
+    pub fn sample_light(&self, pos: &Vector3, sampler: &mut Sampler) -> Option<LightSample> {
+        if self.illumiants.is_empty() {
+            return None;
+        }
+        let idx = sampler.index(self.illumiants.len());
+        let mut sample = self.illumiants[idx].sample_li(pos, sampler)?;
+        sample.pdf /= self.illumiants.len() as f64;
+        Some(sample)
+    }
+
+    // 从 pos 出发的方向 dir 由 sample_light 采样得到的概率密度
+    pub fn light_pdf(&self, pos: &Vector3, dir: &Vector3) -> f64 {
+        if self.illumiants.is_empty() {
+            return 0.0;
+        }
+        let sum: f64 = self.illumiants.iter().map(|l| l.pdf_li(pos, dir)).sum();
+        sum / self.illumiants.len() as f64
+    }
+
     pub fn get_light_num(&self) -> usize {
         self.illumiants.len()
     }
@@ -210,4 +308,8 @@ impl Scene {
     pub fn get_light(&self, idx: usize) -> Arc<dyn Light> {
         self.illumiants[idx].clone()
     }
+
+    pub fn get_camera(&self) -> Option<&Camera> {
+        self.camera.as_ref()
+    }
 }
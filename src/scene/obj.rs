@@ -0,0 +1,353 @@
+use super::loader::SceneError;
+use super::material::Material;
+use super::primitive::{MeshFace, TriangleMesh};
+use super::texture::ImageTexture;
+use crate::consts::EPS;
+use crate::util::*;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+/*
+ * Wavefront OBJ 模型
+ *
+ * 支持 v / vt / vn / f / usemtl / mtllib，多边形按扇形拆成三角形，其余指令被忽略。
+ * MTL 文件中读取 Kd / Ks / Ni / d (或 Tr)，映射到 Material：
+ *   color      = Kd 归一化后的颜色(Kd 为零时使用 Ks 的颜色)
+ *   diffuse    = d * max(Kd)
+ *   specular   = d * max(Ks)
+ *   refraction = 1 - d
+ *   rindex     = Ni
+ * diffuse 与 specular 之和超过 1 时按比例缩小。map_Kd 指定的图像作为材质的纹理。
+ */
+pub struct ObjModel {
+    positions: Vec<Vector3>,
+    normals: Vec<Vector3>,
+    uvs: Vec<(f64, f64)>,
+    groups: Vec<(Option<String>, Vec<MeshFace>)>, // 按材质名分组的三角形
+    materials: HashMap<String, Arc<Material>>,
+}
+
+// 没有指定材质的三角形使用的材质
+fn default_material() -> Material {
+    Material::new(Color::new(0.75, 0.75, 0.75), 1.0, 0.0, 0.0, 1.0)
+}
+
+fn mtl_material(kd: Color, ks: Color, ni: f64, d: f64) -> Material {
+    let max = |c: &Color| c.r.max(c.g).max(c.b);
+    let (kd_max, ks_max) = (max(&kd), max(&ks));
+    let opacity = d.clamp(0.0, 1.0);
+    let color = if kd_max > EPS {
+        kd.div(kd_max)
+    } else if ks_max > EPS {
+        ks.div(ks_max)
+    } else {
+        Color::new(1.0, 1.0, 1.0)
+    };
+    let scale = if kd_max + ks_max > 1.0 { 1.0 / (kd_max + ks_max) } else { 1.0 };
+    Material::new(
+        color,
+        opacity * kd_max * scale,
+        opacity * ks_max * scale,
+        1.0 - opacity,
+        ni,
+    )
+}
+
+struct Parser<'a> {
+    path: &'a Path,
+    line: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, msg: String) -> SceneError {
+        SceneError::Model { path: self.path.to_path_buf(), line: self.line, msg }
+    }
+
+    fn numbers(&self, tokens: &[&str], min: usize, max: usize) -> Result<Vec<f64>, SceneError> {
+        if tokens.len() < min || tokens.len() > max {
+            return Err(self.error(format!("expects {} to {} numbers, found {}", min, max, tokens.len())));
+        }
+        tokens
+            .iter()
+            .map(|t| t.parse::<f64>().map_err(|_| self.error(format!("invalid number `{}`", t))))
+            .collect()
+    }
+
+    // OBJ 中的下标从 1 开始，负数表示从末尾倒数
+    fn index(&self, token: &str, len: usize) -> Result<usize, SceneError> {
+        let idx = token
+            .parse::<i64>()
+            .map_err(|_| self.error(format!("invalid index `{}`", token)))?;
+        let ret = if idx > 0 { idx - 1 } else { len as i64 + idx };
+        if idx == 0 || ret < 0 || ret >= len as i64 {
+            return Err(self.error(format!("index `{}` is out of range", token)));
+        }
+        Ok(ret as usize)
+    }
+}
+
+fn read(path: &Path) -> Result<String, SceneError> {
+    fs::read_to_string(path).map_err(|e| SceneError::Io(path.to_path_buf(), e))
+}
+
+fn strip_comment(text: &str) -> &str {
+    match text.find('#') {
+        Some(pos) => &text[..pos],
+        None => text,
+    }
+}
+
+fn load_mtl(path: &Path, materials: &mut HashMap<String, Arc<Material>>) -> Result<(), SceneError> {
+    struct Entry {
+        name: String,
+        kd: Color,
+        ks: Color,
+        ni: f64,
+        d: f64,
+        map_kd: Option<ImageTexture>,
+    }
+    let mut entries: Vec<Entry> = Vec::new();
+    let text = read(path)?;
+    let mut parser = Parser { path, line: 0 };
+    for (idx, line) in text.lines().enumerate() {
+        parser.line = idx + 1;
+        let tokens: Vec<&str> = strip_comment(line).split_whitespace().collect();
+        if tokens.is_empty() {
+            continue;
+        }
+        if tokens[0] == "newmtl" {
+            if tokens.len() != 2 {
+                return Err(parser.error("`newmtl` needs a name".to_string()));
+            }
+            entries.push(Entry {
+                name: tokens[1].to_string(),
+                kd: Color::new(0.8, 0.8, 0.8),
+                ks: Color::new(0.0, 0.0, 0.0),
+                ni: 1.0,
+                d: 1.0,
+                map_kd: None,
+            });
+            continue;
+        }
+        let entry = match entries.last_mut() {
+            Some(entry) => entry,
+            None => continue, // newmtl 之前的指令
+        };
+        match tokens[0] {
+            "Kd" | "Ks" => {
+                let v = parser.numbers(&tokens[1..], 3, 3)?;
+                let c = Color::new(v[0], v[1], v[2]);
+                if tokens[0] == "Kd" {
+                    entry.kd = c;
+                } else {
+                    entry.ks = c;
+                }
+            }
+            "Ni" => entry.ni = parser.numbers(&tokens[1..], 1, 1)?[0],
+            "d" => entry.d = parser.numbers(&tokens[1..], 1, 1)?[0],
+            "Tr" => entry.d = 1.0 - parser.numbers(&tokens[1..], 1, 1)?[0],
+            "map_Kd" => {
+                if tokens.len() < 2 {
+                    return Err(parser.error("`map_Kd` needs a file name".to_string()));
+                }
+                // 忽略 -o、-s 等选项，只取最后的文件名
+                let file = tokens[tokens.len() - 1];
+                let texture_path = path.parent().unwrap_or_else(|| Path::new("")).join(file);
+                let texture = ImageTexture::open(&texture_path).map_err(|e| {
+                    parser.error(format!("cannot read texture {}: {}", texture_path.display(), e))
+                })?;
+                entry.map_kd = Some(texture);
+            }
+            _ => {}
+        }
+    }
+    for e in entries {
+        let mut material = mtl_material(e.kd, e.ks, e.ni, e.d);
+        if let Some(texture) = e.map_kd {
+            material.set_texture(Arc::new(texture));
+        }
+        materials.insert(e.name, Arc::new(material));
+    }
+    Ok(())
+}
+
+// 读取 OBJ 文件及其引用的 MTL 文件，MTL 路径相对于 OBJ 文件所在目录
+pub fn load_obj<P: AsRef<Path>>(path: P) -> Result<ObjModel, SceneError> {
+    let path = path.as_ref();
+    let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let text = read(path)?;
+    let mut model = ObjModel {
+        positions: Vec::new(),
+        normals: Vec::new(),
+        uvs: Vec::new(),
+        groups: vec![(None, Vec::new())],
+        materials: HashMap::new(),
+    };
+    let mut current = 0; // 当前材质对应的分组
+    let mut parser = Parser { path, line: 0 };
+    for (idx, line) in text.lines().enumerate() {
+        parser.line = idx + 1;
+        let tokens: Vec<&str> = strip_comment(line).split_whitespace().collect();
+        if tokens.is_empty() {
+            continue;
+        }
+        match tokens[0] {
+            "v" => {
+                let v = parser.numbers(&tokens[1..], 3, 4)?;
+                model.positions.push(Vector3::new(v[0], v[1], v[2]));
+            }
+            "vn" => {
+                let v = parser.numbers(&tokens[1..], 3, 3)?;
+                model.normals.push(Vector3::new(v[0], v[1], v[2]));
+            }
+            "vt" => {
+                let v = parser.numbers(&tokens[1..], 1, 3)?;
+                model.uvs.push((v[0], v.get(1).cloned().unwrap_or(0.0)));
+            }
+            "f" => {
+                if tokens.len() < 4 {
+                    return Err(parser.error("a face needs at least three vertices".to_string()));
+                }
+                let mut corners = Vec::with_capacity(tokens.len() - 1);
+                for token in &tokens[1..] {
+                    let mut parts = token.split('/');
+                    let v = parser.index(parts.next().unwrap_or(""), model.positions.len())?;
+                    let vt = match parts.next() {
+                        Some(t) if !t.is_empty() => Some(parser.index(t, model.uvs.len())?),
+                        _ => None,
+                    };
+                    let vn = match parts.next() {
+                        Some(t) if !t.is_empty() => Some(parser.index(t, model.normals.len())?),
+                        _ => None,
+                    };
+                    corners.push((v, vt, vn));
+                }
+                let faces = &mut model.groups[current].1;
+                for i in 1..corners.len() - 1 {
+                    let tri = [corners[0], corners[i], corners[i + 1]];
+                    // 三个顶点都有法向量(纹理坐标)时才使用
+                    let uvs = match (tri[0].1, tri[1].1, tri[2].1) {
+                        (Some(a), Some(b), Some(c)) => Some([a, b, c]),
+                        _ => None,
+                    };
+                    let normals = match (tri[0].2, tri[1].2, tri[2].2) {
+                        (Some(a), Some(b), Some(c)) => Some([a, b, c]),
+                        _ => None,
+                    };
+                    faces.push(MeshFace { vertices: [tri[0].0, tri[1].0, tri[2].0], normals, uvs });
+                }
+            }
+            "usemtl" => {
+                let name = tokens.get(1).map(|s| s.to_string());
+                current = match model.groups.iter().position(|(n, _)| *n == name) {
+                    Some(i) => i,
+                    None => {
+                        model.groups.push((name, Vec::new()));
+                        model.groups.len() - 1
+                    }
+                };
+            }
+            "mtllib" => {
+                for file in &tokens[1..] {
+                    load_mtl(&base_dir.join(file), &mut model.materials)?;
+                }
+            }
+            _ => {} // o, g, s 等指令不影响渲染
+        }
+    }
+    if model.face_count() == 0 {
+        // 没有三角形的网格没有包围盒，错误报告在文件末尾
+        parser.line = text.lines().count().max(1);
+        return Err(parser.error("the model has no faces".to_string()));
+    }
+    Ok(model)
+}
+
+impl ObjModel {
+    pub fn face_count(&self) -> usize {
+        self.groups.iter().map(|(_, faces)| faces.len()).sum()
+    }
+
+    /*
+     * 对所有顶点先缩放再平移
+     * scale 为负时模型被中心反射，顶点法向量取反，并交换每个三角形的两个顶点，
+     * 使由顶点顺序得到的几何法向量同样朝外
+     */
+    pub fn transform(&mut self, scale: f64, offset: Vector3) {
+        for p in self.positions.iter_mut() {
+            *p = p.mult(scale) + offset;
+        }
+        if scale < 0.0 {
+            for n in self.normals.iter_mut() {
+                *n = -*n;
+            }
+            for f in self.groups.iter_mut().flat_map(|(_, faces)| faces.iter_mut()) {
+                f.vertices.swap(1, 2);
+                if let Some(n) = f.normals.as_mut() {
+                    n.swap(1, 2);
+                }
+                if let Some(uv) = f.uvs.as_mut() {
+                    uv.swap(1, 2);
+                }
+            }
+        }
+    }
+
+    /*
+     * 按材质拆分成若干网格，网格编号从 first_id 开始依次递增
+     * material 不为空时忽略 MTL 中的材质，所有三角形组成一个网格
+     */
+    pub fn into_meshes(self, first_id: usize, material: Option<Arc<Material>>) -> Vec<TriangleMesh> {
+        let ObjModel { positions, normals, uvs, groups, materials } = self;
+        if let Some(material) = material {
+            let faces = groups.into_iter().flat_map(|(_, faces)| faces).collect();
+            return vec![TriangleMesh::new(first_id, positions, normals, uvs, faces, material)];
+        }
+
+        let default = Arc::new(default_material());
+        let mut meshes = Vec::new();
+        for (name, faces) in groups.into_iter().filter(|(_, faces)| !faces.is_empty()) {
+            let material = match name.as_ref().and_then(|n| materials.get(n)) {
+                Some(m) => m.clone(),
+                None => {
+                    if let Some(n) = name {
+                        warn!("material `{}` is not defined, using the default material", n);
+                    }
+                    default.clone()
+                }
+            };
+            // 只保留该分组用到的顶点数据
+            let remap = |map: &mut HashMap<usize, usize>, idx: usize| {
+                let len = map.len();
+                *map.entry(idx).or_insert(len)
+            };
+            let (mut pos_map, mut nrm_map, mut uv_map) = (HashMap::new(), HashMap::new(), HashMap::new());
+            let faces: Vec<MeshFace> = faces
+                .into_iter()
+                .map(|f| MeshFace {
+                    vertices: f.vertices.map(|i| remap(&mut pos_map, i)),
+                    normals: f.normals.map(|n| n.map(|i| remap(&mut nrm_map, i))),
+                    uvs: f.uvs.map(|uv| uv.map(|i| remap(&mut uv_map, i))),
+                })
+                .collect();
+            let gather = |map: HashMap<usize, usize>| {
+                let mut items: Vec<(usize, usize)> = map.into_iter().collect();
+                items.sort_by_key(|&(_, local)| local);
+                items.into_iter().map(|(global, _)| global).collect::<Vec<usize>>()
+            };
+            let mesh = TriangleMesh::new(
+                first_id + meshes.len(),
+                gather(pos_map).into_iter().map(|i| positions[i]).collect(),
+                gather(nrm_map).into_iter().map(|i| normals[i]).collect(),
+                gather(uv_map).into_iter().map(|i| uvs[i]).collect(),
+                faces,
+                material,
+            );
+            meshes.push(mesh);
+        }
+        meshes
+    }
+}
+
@@ -1,57 +1,231 @@
 use super::*;
-use nalgebra::base::Matrix3;
-use nalgebra::base::Vector3 as V3;
-use rand::Rng;
-use spin::Mutex;
 
+const MAX_DEPTH: usize = 10; // 剖面曲线细分的最大层数
+const NEWTON_STEPS: usize = 16; // 叶子节点上牛顿迭代的次数
+const T_MIN: f64 = 1e-4; // 交点距离射线起点的最小值
+
+type Hit = (f64, usize, f64);
+
+/*
+ * 以 position 为底部中心、绕 axis 旋转而成的旋转体
+ * 剖面由若干段首尾相接的三次 Bezier 曲线组成，控制点为 (半径, 高度)，整体按 scale 缩放
+ */
 pub struct BazierCurve {
     position: Vector3, // 位置
     hash_value: u64,
-    material: Arc<Material>,    // 材质
-    n_vec: Arc<Mutex<Vector3>>, // 最近一次碰撞处的法向量
-    py: Vec<f64>,
-    pz: Vec<f64>,
-    max_x: f64,
-    min_x: f64,
-    max_r: f64,
-    min_r: f64,
+    material: Arc<Material>, // 材质
+    axis: Vector3,           // 旋转轴，单位向量
+    ex: Vector3,             // 与旋转轴垂直的两个方向，和 axis 组成局部坐标系
+    ey: Vector3,
+    scale: f64,
+    pieces: Vec<Segment>, // 剖面曲线的各段，局部坐标系下未缩放的控制点
+    tolerance: f64,       // 判断牛顿迭代收敛的误差
+    orientation: f64,     // 剖面按顺时针方向给出时为 -1，用来把法向量统一到外侧
+}
+
+// 剖面曲线中第 piece 段的一部分，r 与 z 为控制点，对应该段曲线参数区间 [s0, s1]
+#[derive(Clone, Copy)]
+struct Segment {
+    r: [f64; 4],
+    z: [f64; 4],
+    piece: usize,
+    s0: f64,
+    s1: f64,
+}
+
+// de Casteljau 算法从中点将曲线分成两段
+fn split_half(p: &[f64; 4]) -> ([f64; 4], [f64; 4]) {
+    let p01 = (p[0] + p[1]) * 0.5;
+    let p12 = (p[1] + p[2]) * 0.5;
+    let p23 = (p[2] + p[3]) * 0.5;
+    let p012 = (p01 + p12) * 0.5;
+    let p123 = (p12 + p23) * 0.5;
+    let mid = (p012 + p123) * 0.5;
+    ([p[0], p01, p012, mid], [mid, p123, p23, p[3]])
+}
+
+fn min_max(p: &[f64; 4]) -> (f64, f64) {
+    p.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &x| (lo.min(x), hi.max(x)))
+}
+
+impl Segment {
+    fn split(&self) -> (Segment, Segment) {
+        let (r0, r1) = split_half(&self.r);
+        let (z0, z1) = split_half(&self.z);
+        let mid = (self.s0 + self.s1) * 0.5;
+        (
+            Segment { r: r0, z: z0, piece: self.piece, s0: self.s0, s1: mid },
+            Segment { r: r1, z: z1, piece: self.piece, s0: mid, s1: self.s1 },
+        )
+    }
+}
+
+// 射线在旋转体局部坐标下的表示：到旋转轴距离的平方为 a t^2 + b t + c，高度为 h + t dz
+struct LocalRay {
+    a: f64,
+    b: f64,
+    c: f64,
+    h: f64,
+    dz: f64,
+}
+
+impl LocalRay {
+    // 在 [lo, hi] 中满足 a t^2 + b t + c <= r2 的区间
+    fn within(&self, r2: f64, lo: f64, hi: f64) -> Option<(f64, f64)> {
+        if self.a < EPS {
+            return if self.c <= r2 { Some((lo, hi)) } else { None };
+        }
+        let disc = self.b * self.b - 4.0 * self.a * (self.c - r2);
+        if disc < 0.0 {
+            return None;
+        }
+        let sq = disc.sqrt();
+        let (t0, t1) = ((-self.b - sq) / (2.0 * self.a), (-self.b + sq) / (2.0 * self.a));
+        if t0.max(lo) > t1.min(hi) {
+            return None;
+        }
+        Some((t0.max(lo), t1.min(hi)))
+    }
+
+    /*
+     * 射线在 [lo, hi] 中可能与该段曲线旋转而成的曲面相交的参数区间
+     * 曲线位于控制点的凸包内，因此曲面位于以控制点范围为界的圆环柱壳内，最多得到两个区间
+     */
+    fn candidates(&self, seg: &Segment, lo: f64, hi: f64, tol: f64) -> Vec<(f64, f64)> {
+        let (z_min, z_max) = min_max(&seg.z);
+        let (r_min, r_max) = min_max(&seg.r);
+        let (z_min, z_max) = (z_min - tol, z_max + tol);
+        // 半径绝对值的范围
+        let (inner, outer) = if r_min > 0.0 {
+            (r_min - tol, r_max + tol)
+        } else if r_max < 0.0 {
+            (-r_max - tol, -r_min + tol)
+        } else {
+            (0.0, r_max.max(-r_min) + tol)
+        };
+
+        let (mut lo, mut hi) = (lo, hi);
+        if self.dz.abs() < EPS {
+            if self.h < z_min || self.h > z_max {
+                return Vec::new();
+            }
+        } else {
+            let (t0, t1) = ((z_min - self.h) / self.dz, (z_max - self.h) / self.dz);
+            lo = lo.max(t0.min(t1));
+            hi = hi.min(t0.max(t1));
+        }
+        if lo > hi {
+            return Vec::new();
+        }
+        let (lo, hi) = match self.within(outer * outer, lo, hi) {
+            Some(range) => range,
+            None => return Vec::new(),
+        };
+        if inner <= 0.0 {
+            return vec![(lo, hi)];
+        }
+        // 去掉完全在内侧圆柱里的部分
+        match self.within(inner * inner, lo, hi) {
+            Some((s0, s1)) => {
+                let mut ret = Vec::with_capacity(2);
+                if s0 > lo {
+                    ret.push((lo, s0));
+                }
+                if s1 < hi {
+                    ret.push((s1, hi));
+                }
+                ret
+            }
+            None => vec![(lo, hi)],
+        }
+    }
 }
 
 impl BazierCurve {
-    // TODO
+    // 默认为绕 z 轴旋转的花瓶，可以再用 set_profile、set_axis、set_scale 修改
     pub fn new(id: usize, position: Vector3, material: Arc<Material>) -> Self {
-        BazierCurve {
+        let mut curve = BazierCurve {
             position,
             hash_value: calculate_hash(&id),
             material,
-            n_vec: Arc::new(Mutex::new(Vector3::zero())),
-            py: vec![0.0f64, 100.0f64, 300.0f64, 0.0f64],
-            pz: vec![0.0f64, 100.0f64, 300.0f64, 400.0f64],
-            max_x: 400.0f64,
-            min_x: 0.0f64,
-            max_r: 1e-20,
-            min_r: 1e20,
+            axis: Vector3::zero(),
+            ex: Vector3::zero(),
+            ey: Vector3::zero(),
+            scale: 1.0,
+            pieces: Vec::new(),
+            tolerance: 0.0,
+            orientation: 1.0,
+        };
+        curve.set_axis(Vector3::new(0.0, 0.0, 1.0));
+        curve.set_profile(&[(0.0, 0.0), (100.0, 100.0), (300.0, 300.0), (0.0, 400.0)]);
+        curve
+    }
+
+    // 控制点个数为 3n + 1 时剖面由 n 段三次曲线组成
+    pub fn is_valid_profile(points: &[(f64, f64)]) -> bool {
+        points.len() >= 4 && (points.len() - 1).is_multiple_of(3)
+    }
+
+    /*
+     * 设置剖面曲线的控制点 (半径, 高度)，相邻两段共用端点
+     * 控制点个数不是 3n + 1 时 panic
+     * 剖面与首尾连线围成的区域即为旋转体的截面，控制点按顺时针或逆时针给出都可以，法向量总是指向截面外侧
+     */
+    pub fn set_profile(&mut self, points: &[(f64, f64)]) {
+        assert!(Self::is_valid_profile(points), "a profile needs 3n + 1 control points");
+        self.pieces = points
+            .windows(4)
+            .step_by(3)
+            .enumerate()
+            .map(|(piece, p)| Segment {
+                r: [p[0].0, p[1].0, p[2].0, p[3].0],
+                z: [p[0].1, p[1].1, p[2].1, p[3].1],
+                piece,
+                s0: 0.0,
+                s1: 1.0,
+            })
+            .collect();
+        let extent = points.iter().fold(1.0f64, |m, p| m.max(p.0.abs()).max(p.1.abs()));
+        self.tolerance = extent * 1e-9;
+        self.orientation = if self.profile_area() < 0.0 { -1.0 } else { 1.0 };
+    }
+
+    // 在 (半径, 高度) 平面上采样剖面曲线，按鞋带公式计算与首尾连线围成区域的有向面积，逆时针为正
+    fn profile_area(&self) -> f64 {
+        const SAMPLES: usize = 32;
+        let mut points = Vec::new();
+        for seg in self.pieces.iter() {
+            for i in 0..SAMPLES {
+                let t = i as f64 / SAMPLES as f64;
+                points.push((self.get_p(&seg.r, t).abs(), self.get_p(&seg.z, t)));
+            }
         }
+        let last = self.pieces[self.pieces.len() - 1];
+        points.push((last.r[3].abs(), last.z[3]));
+        let n = points.len();
+        (0..n)
+            .map(|i| {
+                let (p, q) = (points[i], points[(i + 1) % n]);
+                p.0 * q.1 - q.0 * p.1
+            })
+            .sum::<f64>()
+            * 0.5
     }
 
-    pub fn test(&self) {
-        let matrix = Matrix3::new(
-            1.0f64, 1.0f64, 1.0f64, 1.0f64, 1.0f64, 1.0f64, 1.0f64, 1.0f64, 1.0f64,
-        );
-        let v3 = V3::new(0.1f64, 0.2f64, 0.3f64);
-        let v31 = V3::new(0.1f64, 0.2f64, 0.3f64);
-        info!("{:?} {} {} {}", matrix * v3, v3.x, v3.y, v3.z);
-        info!("{} {} {}", v3 + v31, v3 - v31, v3);
-        let p = vec![10.0f64, 2.0f64, 3.0f64, 4.0f64];
-        info!("result : {}", self.getd_p(&p, 0.0));
+    pub fn set_axis(&mut self, axis: Vector3) {
+        self.axis = axis.normalize();
+        self.ex = self.axis.get_vertical_vec();
+        self.ey = self.axis.cross(&self.ex);
+    }
 
-        let vec = Vector3::new(1.0, 0.0, 0.0);
-        let axis = Vector3::new(0.0, 0.0, 1.0);
-        info!("{:?}", vec.rotate(&axis, 0.5 * std::f64::consts::PI));
+    // scale 必须为正数，否则 panic
+    pub fn set_scale(&mut self, scale: f64) {
+        assert!(scale > 0.0, "a bezier scale must be positive");
+        self.scale = scale;
     }
 
     // Bezier曲线给入参数求值。
-    fn get_p(&self, p: &Vec<f64>, t: f64) -> f64 {
+    fn get_p(&self, p: &[f64], t: f64) -> f64 {
         1.0 * p[0] * (1.0 - t) * (1.0 - t) * (1.0 - t)
             + 3.0 * p[1] * t * (1.0 - t) * (1.0 - t)
             + 3.0 * p[2] * t * t * (1.0 - t)
@@ -59,7 +233,7 @@ impl BazierCurve {
     }
 
     // Bezier曲线给入参数求切线导数值。
-    fn getd_p(&self, p: &Vec<f64>, t: f64) -> f64 {
+    fn getd_p(&self, p: &[f64], t: f64) -> f64 {
         -3.0 * p[0] * (1.0 - t) * (1.0 - t)
             + 3.0 * p[1] * (1.0 - t) * (1.0 - t)
             + -6.0 * p[1] * t * (1.0 - t)
@@ -68,125 +242,107 @@ impl BazierCurve {
             + 3.0 * p[3] * t * t
     }
 
-    // 给定t计算射线上的点
-    fn get_c(&self, ray: &Ray, t: f64) -> Vector3 {
-        ray.o + ray.d.mult(t)
-    }
-
-    // 给定u和theta计算旋转体方程的值
-    fn get_s(&self, u: f64, theta: f64) -> Vector3 {
-        // TOFIXME
-        Vector3::new(
-            self.position.x - theta.sin() * self.get_p(&self.py, u),
-            self.position.y + theta.cos() * self.get_p(&self.py, u),
-            self.position.z + self.get_p(&self.pz, u),
-        )
-    }
-
-    fn get_f(&self, t: f64, u: f64, theta: f64, ray: &Ray) -> Vector3 {
-        let c = self.get_c(ray, t);
-        let s = self.get_s(u, theta);
-        c - s
+    // 世界坐标转换到未缩放的局部坐标系，射线参数 t 保持不变
+    fn to_local(&self, v: &Vector3) -> Vector3 {
+        Vector3::new(v.dot(&self.ex), v.dot(&self.ey), v.dot(&self.axis)).mult(1.0 / self.scale)
     }
 
-    fn getd_f(&self, u: f64, theta: f64, ray: &Ray) -> Matrix3<f64> {
-        Matrix3::new(
-            ray.d.x,
-            theta.sin() * self.getd_p(&self.py, u),
-            theta.cos() * self.get_p(&self.py, u),
-            ray.d.y,
-            -theta.cos() * self.getd_p(&self.py, u),
-            theta.sin() * self.get_p(&self.py, u),
-            ray.d.z,
-            -self.getd_p(&self.pz, u),
-            0.0,
-        )
+    fn local_ray(&self, ray: &Ray) -> LocalRay {
+        let o = self.to_local(&(ray.o - self.position));
+        let d = self.to_local(&ray.d);
+        LocalRay {
+            a: d.x * d.x + d.y * d.y,
+            b: 2.0 * (o.x * d.x + o.y * d.y),
+            c: o.x * o.x + o.y * o.y,
+            h: o.z,
+            dz: d.z,
+        }
     }
 
     /*
-     * args : ( t, u, theta) 代表曲面求交方程的三个自变量
+     * 在第 piece 段曲线的参数 u 与射线参数 t 上做牛顿迭代，求解
+     *   a t^2 + b t + c = r(u)^2
+     *   h + t dz = z(u)
+     * 收敛时返回 (t, u)
      */
-    fn init_args(&self, ray: &Ray, args: &mut Vector3) {
-        let mut rng = rand::thread_rng();
-
-        let u = rng.gen_range(0.0, 1.0); // 曲线参数初始化时随机在[0.0,1.0]取值
-        let theta = rng.gen_range(0.0, 2.0 * PI); // 旋转角度也是随机取值，取值范围[0, 2pi]
-
-        let h = rng.gen_range(self.min_x, self.max_x);
-        let t = (self.position + Vector3::new(0.0, 0.0, 1.0).mult(h)).distance(&ray.o);
-        args.x = t;
-        args.y = u;
-        args.z = theta;
+    fn newton(&self, ray: &LocalRay, piece: usize, mut t: f64, mut u: f64) -> Option<(f64, f64)> {
+        let (pr, pz) = (&self.pieces[piece].r, &self.pieces[piece].z);
+        for _ in 0..NEWTON_STEPS {
+            let (r, dr) = (self.get_p(pr, u), self.getd_p(pr, u));
+            let (z, dz) = (self.get_p(pz, u), self.getd_p(pz, u));
+            let f1 = ray.a * t * t + ray.b * t + ray.c - r * r;
+            let f2 = ray.h + t * ray.dz - z;
+            let (j11, j12) = (2.0 * ray.a * t + ray.b, -2.0 * r * dr);
+            let (j21, j22) = (ray.dz, -dz);
+            let det = j11 * j22 - j12 * j21;
+            if det.abs() < EPS {
+                break;
+            }
+            t -= (f1 * j22 - f2 * j12) / det;
+            u -= (j11 * f2 - j21 * f1) / det;
+            if !t.is_finite() || !(-0.5..=1.5).contains(&u) {
+                return None;
+            }
+        }
+        if !(0.0..=1.0).contains(&u) || t < T_MIN {
+            return None;
+        }
+        // 用到旋转轴的距离和高度上的误差判断是否收敛
+        let rho = (ray.a * t * t + ray.b * t + ray.c).max(0.0).sqrt();
+        let err_r = (rho - self.get_p(pr, u).abs()).abs();
+        let err_z = (ray.h + t * ray.dz - self.get_p(pz, u)).abs();
+        if err_r.max(err_z) > self.tolerance * 1e3 {
+            return None;
+        }
+        Some((t, u))
     }
-}
 
-impl Primitive for BazierCurve {
-    fn intersect(&self, r: &Ray) -> Option<f64> {
-        let mut dist = 1e100;
-        let lr: f64 = 0.7; // 衰减系数
-        for _ in 0..35 {
-            // 初始化各项参数
-            let mut args = Vector3::new(0.0, 0.0, 0.0);
-            self.init_args(r, &mut args);
-            let mut flag: bool = false;
-            for _ in 0..20 {
-                // 牛顿迭代过程
-                let t = args.x;
-                let u = args.y;
-                let theta = args.z;
-                let f = self.get_f(t, u, theta, r); // 得到向量
-                let d_f = self.getd_f(u, theta, r); // 得到矩阵
-
-                let max = f.x.abs().max(f.y.abs().max(f.z.abs()));
-                if max < 1e-7 {
-                    flag = true;
-                    break;
-                }
-                // TODO
-                if let Ok(inverse_df) = d_f.pseudo_inverse(1e-20) {
-                    args = args - (inverse_df * f) * lr;
-                } else {
-                    break;
+    // 细分剖面曲线，剔除射线不可能相交的部分，在叶子节点上用牛顿迭代求交
+    // best 为目前最近的交点 (t, 所在段, 段内参数)
+    fn search(&self, ray: &LocalRay, seg: &Segment, lo: f64, hi: f64, depth: usize, best: &mut Option<Hit>) {
+        let hi = best.map_or(hi, |(b, _, _)| hi.min(b));
+        for (t0, t1) in ray.candidates(seg, lo, hi, self.tolerance) {
+            if depth < MAX_DEPTH {
+                let (left, right) = seg.split();
+                self.search(ray, &left, t0, t1, depth + 1, best);
+                self.search(ray, &right, t0, t1, depth + 1, best);
+            } else if let Some((t, u)) = self.newton(ray, seg.piece, (t0 + t1) * 0.5, (seg.s0 + seg.s1) * 0.5) {
+                if best.is_none_or(|(b, _, _)| t < b) {
+                    *best = Some((t, seg.piece, u));
                 }
             }
-            if !flag {
-                continue;
-            }
-            if args.x < 0.0 {
-                continue;
-            }
-            if args.y < 0.0 || args.y > 1.0 {
-                continue;
-            }
-            if args.x > dist {
-                continue;
-            }
-            let u = args.y;
-            let theta = args.z;
-            dist = args.x; // 将t作为距离
-                           // TODO 更新法向量
-            let pspu = Vector3::new(
-                -theta.sin() * self.getd_p(&self.py, u),
-                theta.cos() * self.getd_p(&self.py, u),
-                self.getd_p(&self.pz, u),
-            );
-            let pspt = Vector3::new(
-                theta.cos() * self.get_p(&self.py, u),
-                -theta.sin() * self.get_p(&self.py, u),
-                0.0,
-            );
-
-            let mut vec = self.n_vec.lock();
-            *vec = pspu.cross(&pspt).normalize();
         }
-        if dist < 1e90 {
-            return Some(dist);
-        }
-        None
     }
 
-    fn get_normal_vec(&self, _: &Vector3) -> Vector3 {
-        self.n_vec.as_ref().lock().clone()
+}
+
+impl Primitive for BazierCurve {
+    // 曲面参数为 (剖面曲线参数 u, 旋转角 / 2pi)，u 在 [0, 1] 中按段数均分
+    fn intersect(&self, r: &Ray) -> Option<SurfaceHit> {
+        let ray = self.local_ray(r);
+        let mut best = None;
+        for seg in self.pieces.iter() {
+            self.search(&ray, seg, T_MIN, f64::INFINITY, 0, &mut best);
+        }
+        let (distance, piece, u) = best?;
+
+        // 法向量在过旋转轴的平面内与剖面曲线垂直，(dz, -dr) 在逆时针的剖面上指向外侧
+        let (pr, pz) = (&self.pieces[piece].r, &self.pieces[piece].z);
+        let pos = r.o + r.d.mult(distance);
+        let q = self.to_local(&(pos - self.position));
+        let rho = (q.x * q.x + q.y * q.y).sqrt();
+        let sign = if self.get_p(pr, u) < 0.0 { -1.0 } else { 1.0 };
+        let dr = sign * self.getd_p(pr, u);
+        let dz = self.getd_p(pz, u);
+        let n = if rho < EPS {
+            self.axis
+        } else {
+            (self.ex.mult(q.x) + self.ey.mult(q.y)).mult(dz / rho) - self.axis.mult(dr)
+        };
+        let normal = if n.norm() < EPS { self.axis } else { n.normalize() }.mult(self.orientation);
+        let theta = q.y.atan2(q.x) / (2.0 * PI) + 0.5;
+        let u = (piece as f64 + u) / self.pieces.len() as f64;
+        Some(SurfaceHit { distance, pos, normal, shading_normal: normal, uv: (u, theta) })
     }
 
     fn get_material(&self) -> Arc<Material> {
@@ -197,7 +353,27 @@ impl Primitive for BazierCurve {
         self.hash_value
     }
 
-    fn get_color(&self, _pos: &Vector3) -> Color {
-        self.material.color()
+    fn get_color(&self, hit: &SurfaceHit) -> Color {
+        self.material.color_at(hit.uv, &hit.pos)
+    }
+
+    // Bezier 曲线位于控制点的凸包内，旋转半径不超过控制点半径的最大绝对值
+    fn bounding_box(&self) -> Option<AABB> {
+        let r = self.pieces.iter().flat_map(|p| p.r.iter()).fold(0.0f64, |m, x| m.max(x.abs()));
+        let (z_min, z_max) = self
+            .pieces
+            .iter()
+            .map(|p| min_max(&p.z))
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), (a, b)| (lo.min(a), hi.max(b)));
+        let mut corners = Vec::with_capacity(8);
+        for &x in &[-r, r] {
+            for &y in &[-r, r] {
+                for &z in &[z_min, z_max] {
+                    let local = self.ex.mult(x) + self.ey.mult(y) + self.axis.mult(z);
+                    corners.push(self.position + local.mult(self.scale));
+                }
+            }
+        }
+        Some(AABB::from_points(&corners))
     }
 }
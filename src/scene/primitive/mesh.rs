@@ -0,0 +1,119 @@
+use super::triangle::intersect_triangle;
+use super::*;
+use crate::scene::bvh::Bvh;
+
+// 网格中的一个三角形，保存顶点、法向量和纹理坐标在网格数组中的下标
+#[derive(Clone, Copy, Debug)]
+pub struct MeshFace {
+    pub vertices: [usize; 3],
+    pub normals: Option<[usize; 3]>,
+    pub uvs: Option<[usize; 3]>,
+}
+
+/*
+ * 三角网格，所有三角形共用一个材质
+ * 网格内部建立自己的层次包围盒，在场景的层次包围盒中只占一项
+ */
+pub struct TriangleMesh {
+    positions: Vec<Vector3>,
+    normals: Vec<Vector3>,
+    uvs: Vec<(f64, f64)>,
+    faces: Vec<MeshFace>,
+    bvh: Bvh,
+    material: Arc<Material>,
+    hash_value: u64,
+}
+
+impl TriangleMesh {
+    // 调用者需保证 faces 中的下标都在对应数组的范围内
+    pub fn new(
+        id: usize,
+        positions: Vec<Vector3>,
+        normals: Vec<Vector3>,
+        uvs: Vec<(f64, f64)>,
+        faces: Vec<MeshFace>,
+        material: Arc<Material>,
+    ) -> Self {
+        let items = faces
+            .iter()
+            .enumerate()
+            .map(|(i, f)| {
+                let [a, b, c] = f.vertices;
+                (i, AABB::from_points(&[positions[a], positions[b], positions[c]]))
+            })
+            .collect();
+        let bvh = Bvh::build(items);
+        TriangleMesh {
+            positions,
+            normals,
+            uvs,
+            faces,
+            bvh,
+            material,
+            hash_value: calculate_hash(&id),
+        }
+    }
+
+    pub fn face_count(&self) -> usize {
+        self.faces.len()
+    }
+
+    fn corners(&self, face: &MeshFace) -> (Vector3, Vector3, Vector3) {
+        let [a, b, c] = face.vertices;
+        (self.positions[a], self.positions[b], self.positions[c])
+    }
+}
+
+impl Primitive for TriangleMesh {
+    /*
+     * 法向量总是三角形的几何法向量，用于判断射线从哪一侧击中
+     * 有顶点法向量时着色法向量按重心坐标插值，并翻到几何法向量一侧；没有纹理坐标时以重心坐标作为曲面参数
+     */
+    fn intersect(&self, r: &Ray) -> Option<SurfaceHit> {
+        let (i, distance, (u, v)) = self.bvh.intersect(r, f64::INFINITY, |i, t_max| {
+            let (a, b, c) = self.corners(&self.faces[i]);
+            intersect_triangle(r, &a, &b, &c)
+                .filter(|&(t, _, _)| t < t_max)
+                .map(|(t, u, v)| (t, (u, v)))
+        })?;
+        let face = &self.faces[i];
+        let w = [1.0 - u - v, u, v];
+        let (a, b, c) = self.corners(face);
+        let normal = (b - a).cross(&(c - a)).normalize();
+        let mut shading_normal = normal;
+        if let Some(n) = face.normals {
+            let shading = self.normals[n[0]].mult(w[0])
+                + self.normals[n[1]].mult(w[1])
+                + self.normals[n[2]].mult(w[2]);
+            if shading.norm() > EPS {
+                shading_normal = shading.normalize();
+                if shading_normal.dot(&normal) < 0.0 {
+                    shading_normal = shading_normal.mult(-1.0);
+                }
+            }
+        }
+        let uv = match face.uvs {
+            Some(t) => (0..3).fold((0.0, 0.0), |(s, q), k| {
+                (s + self.uvs[t[k]].0 * w[k], q + self.uvs[t[k]].1 * w[k])
+            }),
+            None => (u, v),
+        };
+        Some(SurfaceHit { distance, pos: r.o + r.d.mult(distance), normal, shading_normal, uv })
+    }
+
+    fn get_color(&self, hit: &SurfaceHit) -> Color {
+        self.material.color_at(hit.uv, &hit.pos)
+    }
+
+    fn get_material(&self) -> Arc<Material> {
+        self.material.clone()
+    }
+
+    fn get_hash(&self) -> u64 {
+        self.hash_value
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        Some(self.bvh.bounds())
+    }
+}
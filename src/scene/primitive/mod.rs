@@ -1,6 +1,8 @@
 mod sphere;
 mod plane;
 mod bazier;
+mod triangle;
+mod mesh;
 
 pub use super::material::*;
 pub use crate::util::*;
@@ -12,12 +14,26 @@ pub use std::f64::consts::PI;
 pub use sphere::Sphere;
 pub use plane::Plane;
 pub use bazier::BazierCurve;
+pub use triangle::Triangle;
+pub use mesh::{MeshFace, TriangleMesh};
 
 
+// 射线与物体交点处的局部信息，由 intersect 直接返回，物体本身不保存任何求交状态
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SurfaceHit {
+    pub distance: f64,      // 交点到射线起点的参数 t
+    pub pos: Vector3,       // 交点位置
+    pub normal: Vector3,    // 单位几何法向量，朝向物体外侧；平面朝向 direction 一侧
+    pub shading_normal: Vector3,    // 着色用的单位法向量，与 normal 位于表面同侧，只用于计算散射
+    pub uv: (f64, f64),     // 交点处的曲面参数，用于纹理等
+}
+
 pub trait Primitive {
-    fn intersect(&self, r : &Ray) -> Option<f64>;
-    fn get_normal_vec(&self, pos : &Vector3) -> Vector3;
-    fn get_color(&self, pos : &Vector3) -> Color;
+    fn intersect(&self, r : &Ray) -> Option<SurfaceHit>;
+    // 交点处的表面颜色，由材质的颜色或纹理决定
+    fn get_color(&self, hit : &SurfaceHit) -> Color;
     fn get_material(&self) -> Arc<Material>;
     fn get_hash(&self) -> u64;
+    // 物体的包围盒，平面等无界物体必须返回 None，这类物体不会进入层次包围盒
+    fn bounding_box(&self) -> Option<AABB>;
 }
@@ -8,7 +8,7 @@ pub struct Sphere {
 }
 
 impl Primitive for Sphere {
-    fn intersect(&self, r: &Ray) -> Option<f64> {
+    fn intersect(&self, r: &Ray) -> Option<SurfaceHit> {
         // 给定一条射线，判断其与本物体是否相交
         let op = self.position - r.o; // 射线源点到球心的向量
         let eps: f64 = 1e-4;
@@ -19,25 +19,23 @@ impl Primitive for Sphere {
         } else {
             det = det.sqrt();
         }
-        if b - det > eps {
-            return Some(b - det);
+        let distance = if b - det > eps {
+            b - det
         } else if b + det > eps {
-            return Some(b + det);
-        }
-        None
-    }
-
-    fn get_normal_vec(&self, pos: &Vector3) -> Vector3 {
-        let ret = *pos - self.position;
-        if !ret.is_zero() {
-            return ret.normalize();
-        }
-        ret
+            b + det
+        } else {
+            return None;
+        };
+        let pos = r.o + r.d.mult(distance);
+        let normal = (pos - self.position).normalize();
+        // 经纬度作为曲面参数
+        let u = normal.y.atan2(normal.x) / (2.0 * PI) + 0.5;
+        let v = normal.z.clamp(-1.0, 1.0).acos() / PI;
+        Some(SurfaceHit { distance, pos, normal, shading_normal: normal, uv: (u, v) })
     }
 
-    fn get_color(&self, _pos: &Vector3) -> Color {
-        // TODO 纹理贴图
-        self.material.color()
+    fn get_color(&self, hit: &SurfaceHit) -> Color {
+        self.material.color_at(hit.uv, &hit.pos)
     }
 
     fn get_material(&self) -> Arc<Material> {
@@ -47,10 +45,15 @@ impl Primitive for Sphere {
     fn get_hash(&self) -> u64 {
         self.hash_value
     }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        let r = Vector3::new(self.radius, self.radius, self.radius);
+        Some(AABB::new(self.position - r, self.position + r))
+    }
 }
 
 impl Sphere {
-    pub fn new(id: u32, radius: f64, position: Vector3, material: Arc<Material>) -> Self {
+    pub fn new(id: usize, radius: f64, position: Vector3, material: Arc<Material>) -> Self {
         Sphere {
             radius,
             position,
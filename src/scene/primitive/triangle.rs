@@ -0,0 +1,76 @@
+use super::*;
+
+pub struct Triangle {
+    vertices: [Vector3; 3],
+    normal: Vector3,
+    material: Arc<Material>,
+    hash_value: u64,
+}
+
+impl Primitive for Triangle {
+    // 重心坐标作为曲面参数
+    fn intersect(&self, r: &Ray) -> Option<SurfaceHit> {
+        let [a, b, c] = &self.vertices;
+        let (distance, u, v) = intersect_triangle(r, a, b, c)?;
+        let pos = r.o + r.d.mult(distance);
+        Some(SurfaceHit { distance, pos, normal: self.normal, shading_normal: self.normal, uv: (u, v) })
+    }
+
+    fn get_color(&self, hit: &SurfaceHit) -> Color {
+        self.material.color_at(hit.uv, &hit.pos)
+    }
+
+    fn get_material(&self) -> Arc<Material> {
+        self.material.clone()
+    }
+
+    fn get_hash(&self) -> u64 {
+        self.hash_value
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        Some(AABB::from_points(&self.vertices))
+    }
+}
+
+impl Triangle {
+    pub fn new(id: usize, a: Vector3, b: Vector3, c: Vector3, material: Arc<Material>) -> Self {
+        let normal = (b - a).cross(&(c - a));
+        Triangle {
+            vertices: [a, b, c],
+            normal: if normal.is_zero() { normal } else { normal.normalize() },
+            material,
+            hash_value: calculate_hash(&id),
+        }
+    }
+}
+
+/*
+ * Möller–Trumbore 算法求射线与三角形 abc 的交点
+ * 返回 (距离, u, v)，交点为 a + u (b - a) + v (c - a)
+ */
+pub(crate) fn intersect_triangle(r: &Ray, a: &Vector3, b: &Vector3, c: &Vector3) -> Option<(f64, f64, f64)> {
+    let e1 = b - a;
+    let e2 = c - a;
+    let p = r.d.cross(&e2);
+    let det = e1.dot(&p);
+    if det.abs() < EPS {
+        return None; // 射线与三角形平行
+    }
+    let inv_det = 1.0 / det;
+    let s = r.o - a;
+    let u = s.dot(&p) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = s.cross(&e1);
+    let v = r.d.dot(&q) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = e2.dot(&q) * inv_det;
+    if t > 1e-4 {
+        return Some((t, u, v));
+    }
+    None
+}
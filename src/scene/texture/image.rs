@@ -0,0 +1,163 @@
+use super::Texture;
+use crate::util::*;
+use crate::util::tone_map::srgb_decode;
+use std::path::Path;
+use std::str::FromStr;
+
+// 纹理坐标超出 [0, 1] 时的处理方式
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WrapMode {
+    Repeat, // 重复
+    Mirror, // 镜像重复
+    Clamp,  // 取边缘的像素
+}
+
+impl FromStr for WrapMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "repeat" => Ok(WrapMode::Repeat),
+            "mirror" => Ok(WrapMode::Mirror),
+            "clamp" => Ok(WrapMode::Clamp),
+            _ => Err(format!("unknown wrap mode `{}`, expected repeat, mirror or clamp", s)),
+        }
+    }
+}
+
+impl WrapMode {
+    // 将像素下标映射到 [0, n) 中
+    fn apply(self, i: i64, n: usize) -> usize {
+        let n = n as i64;
+        let ret = match self {
+            WrapMode::Repeat => i.rem_euclid(n),
+            WrapMode::Mirror => {
+                let m = i.rem_euclid(2 * n);
+                if m >= n {
+                    2 * n - 1 - m
+                } else {
+                    m
+                }
+            }
+            WrapMode::Clamp => i.clamp(0, n - 1),
+        };
+        ret as usize
+    }
+}
+
+// 图像纹理的采样方式
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+    Nearest,  // 取最近的像素
+    Bilinear, // 相邻四个像素双线性插值
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nearest" => Ok(Filter::Nearest),
+            "bilinear" => Ok(Filter::Bilinear),
+            _ => Err(format!("unknown filter `{}`, expected nearest or bilinear", s)),
+        }
+    }
+}
+
+/*
+ * 图像纹理，u 向右、v 向上，(0, 0) 为图像左下角
+ * 采样前先对纹理坐标做变换：uv * scale + offset
+ */
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>, // 按行存储，第一行为图像顶部
+    wrap: WrapMode,
+    filter: Filter,
+    scale: (f64, f64),
+    offset: (f64, f64),
+}
+
+impl ImageTexture {
+    // pixels 按行存储，长度必须为 width * height 且不为零
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert!(width > 0 && height > 0 && pixels.len() == width * height, "invalid texture size");
+        ImageTexture {
+            width,
+            height,
+            pixels,
+            wrap: WrapMode::Repeat,
+            filter: Filter::Bilinear,
+            scale: (1.0, 1.0),
+            offset: (0.0, 0.0),
+        }
+    }
+
+    // 读取 PNG 图像，像素值按 sRGB 传递函数转换到线性空间，与输出时的编码对应
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, lodepng::Error> {
+        let image = lodepng::decode32_file(path)?;
+        let to_linear = |c: u8| srgb_decode(c as f64 / 255.0);
+        let pixels = image
+            .buffer
+            .iter()
+            .map(|p| Color::new(to_linear(p.r), to_linear(p.g), to_linear(p.b)))
+            .collect();
+        Ok(ImageTexture::new(image.width, image.height, pixels))
+    }
+
+    pub fn set_wrap(&mut self, wrap: WrapMode) {
+        self.wrap = wrap;
+    }
+
+    pub fn set_filter(&mut self, filter: Filter) {
+        self.filter = filter;
+    }
+
+    pub fn set_scale(&mut self, su: f64, sv: f64) {
+        self.scale = (su, sv);
+    }
+
+    pub fn set_offset(&mut self, ou: f64, ov: f64) {
+        self.offset = (ou, ov);
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Color {
+        let x = self.wrap.apply(x, self.width);
+        let y = self.wrap.apply(y, self.height);
+        self.pixels[y * self.width + x]
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, uv: (f64, f64), _pos: &Vector3) -> Color {
+        let u = uv.0 * self.scale.0 + self.offset.0;
+        let v = uv.1 * self.scale.1 + self.offset.1;
+        if !u.is_finite() || !v.is_finite() {
+            return self.pixels[0];
+        }
+        // 以像素中心为采样点
+        let x = u * self.width as f64;
+        let y = (1.0 - v) * self.height as f64;
+        match self.filter {
+            Filter::Nearest => self.texel(x.floor() as i64, y.floor() as i64),
+            Filter::Bilinear => {
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                self.texel(x0, y0).mult((1.0 - fx) * (1.0 - fy))
+                    + self.texel(x0 + 1, y0).mult(fx * (1.0 - fy))
+                    + self.texel(x0, y0 + 1).mult((1.0 - fx) * fy)
+                    + self.texel(x0 + 1, y0 + 1).mult(fx * fy)
+            }
+        }
+    }
+}
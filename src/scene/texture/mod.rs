@@ -0,0 +1,19 @@
+mod image;
+mod procedural;
+
+pub use self::image::{Filter, ImageTexture, WrapMode};
+pub use self::procedural::{CheckerTexture, GradientTexture, MarbleTexture, NoiseTexture, Perlin};
+use crate::util::*;
+
+/*
+ * 纹理，根据交点处的曲面参数 uv 和位置 pos 给出颜色
+ * 曲面参数由各个物体在求交时生成，见 Primitive::intersect；程序纹理中的实体纹理直接使用 pos
+ */
+pub trait Texture: Send + Sync {
+    fn value(&self, uv: (f64, f64), pos: &Vector3) -> Color;
+}
+
+// 按 t 在两个颜色之间线性插值
+fn lerp(a: &Color, b: &Color, t: f64) -> Color {
+    a.mult(1.0 - t) + b.mult(t)
+}
@@ -0,0 +1,229 @@
+use super::{lerp, Texture};
+use crate::consts::EPS;
+use crate::util::*;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+/*
+ * Perlin 噪声(improved noise)，排列表由 seed 决定，相同 seed 得到相同的噪声
+ */
+pub struct Perlin {
+    perm: Vec<usize>, // 长度为 512，后一半重复前一半
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+// 由哈希值选择 12 个梯度方向之一，返回其与 (x, y, z) 的点积
+fn grad(hash: usize, x: f64, y: f64, z: f64) -> f64 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 {
+        y
+    } else if h == 12 || h == 14 {
+        x
+    } else {
+        z
+    };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+fn mix(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut perm: Vec<usize> = (0..256).collect();
+        perm.shuffle(&mut StdRng::seed_from_u64(seed));
+        perm.extend_from_within(..);
+        Perlin { perm }
+    }
+
+    // 取值大致在 [-1, 1] 中，整数格点处为 0
+    pub fn noise(&self, p: &Vector3) -> f64 {
+        let (xf, yf, zf) = (p.x.floor(), p.y.floor(), p.z.floor());
+        let (x, y, z) = (p.x - xf, p.y - yf, p.z - zf);
+        let (xi, yi, zi) = (
+            (xf as i64).rem_euclid(256) as usize,
+            (yf as i64).rem_euclid(256) as usize,
+            (zf as i64).rem_euclid(256) as usize,
+        );
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+        let perm = &self.perm;
+        let a = perm[xi] + yi;
+        let (aa, ab) = (perm[a] + zi, perm[a + 1] + zi);
+        let b = perm[xi + 1] + yi;
+        let (ba, bb) = (perm[b] + zi, perm[b + 1] + zi);
+        mix(
+            mix(
+                mix(grad(perm[aa], x, y, z), grad(perm[ba], x - 1.0, y, z), u),
+                mix(grad(perm[ab], x, y - 1.0, z), grad(perm[bb], x - 1.0, y - 1.0, z), u),
+                v,
+            ),
+            mix(
+                mix(grad(perm[aa + 1], x, y, z - 1.0), grad(perm[ba + 1], x - 1.0, y, z - 1.0), u),
+                mix(
+                    grad(perm[ab + 1], x, y - 1.0, z - 1.0),
+                    grad(perm[bb + 1], x - 1.0, y - 1.0, z - 1.0),
+                    u,
+                ),
+                v,
+            ),
+            w,
+        )
+    }
+
+    // 分形布朗运动：频率依次加倍、振幅依次减半的噪声之和，按振幅总和归一化
+    pub fn fbm(&self, p: &Vector3, octaves: usize) -> f64 {
+        let (mut sum, mut amp, mut total, mut freq) = (0.0, 1.0, 0.0, 1.0);
+        for _ in 0..octaves.max(1) {
+            sum += amp * self.noise(&p.mult(freq));
+            total += amp;
+            amp *= 0.5;
+            freq *= 2.0;
+        }
+        sum / total
+    }
+
+    // 湍流：与 fbm 相同，但对每层噪声取绝对值
+    pub fn turbulence(&self, p: &Vector3, octaves: usize) -> f64 {
+        let (mut sum, mut amp, mut total, mut freq) = (0.0, 1.0, 0.0, 1.0);
+        for _ in 0..octaves.max(1) {
+            sum += amp * self.noise(&p.mult(freq)).abs();
+            total += amp;
+            amp *= 0.5;
+            freq *= 2.0;
+        }
+        sum / total
+    }
+}
+
+// 棋盘格纹理，在曲面参数上每 1 / scale 交替一次
+pub struct CheckerTexture {
+    even: Color,
+    odd: Color,
+    scale: (f64, f64),
+}
+
+impl CheckerTexture {
+    pub fn new(even: Color, odd: Color) -> Self {
+        CheckerTexture { even, odd, scale: (1.0, 1.0) }
+    }
+
+    pub fn set_scale(&mut self, su: f64, sv: f64) {
+        self.scale = (su, sv);
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, uv: (f64, f64), _pos: &Vector3) -> Color {
+        let i = (uv.0 * self.scale.0).floor() + (uv.1 * self.scale.1).floor();
+        if !i.is_finite() || (i as i64).rem_euclid(2) == 0 {
+            self.even
+        } else {
+            self.odd
+        }
+    }
+}
+
+// 实体噪声纹理，按 fbm 噪声在两个颜色之间插值，位置先乘以 scale
+pub struct NoiseTexture {
+    low: Color,
+    high: Color,
+    scale: f64,
+    octaves: usize,
+    perlin: Perlin,
+}
+
+impl NoiseTexture {
+    pub fn new(low: Color, high: Color, seed: u64) -> Self {
+        NoiseTexture { low, high, scale: 1.0, octaves: 4, perlin: Perlin::new(seed) }
+    }
+
+    pub fn set_scale(&mut self, scale: f64) {
+        self.scale = scale;
+    }
+
+    pub fn set_octaves(&mut self, octaves: usize) {
+        self.octaves = octaves;
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _uv: (f64, f64), pos: &Vector3) -> Color {
+        let n = self.perlin.fbm(&pos.mult(self.scale), self.octaves);
+        lerp(&self.low, &self.high, (0.5 * (1.0 + n)).clamp(0.0, 1.0))
+    }
+}
+
+// 实体大理石纹理，沿 z 方向的正弦条纹被湍流扰动
+pub struct MarbleTexture {
+    low: Color,
+    high: Color,
+    scale: f64,
+    turbulence: f64, // 扰动的强度
+    octaves: usize,
+    perlin: Perlin,
+}
+
+impl MarbleTexture {
+    pub fn new(low: Color, high: Color, seed: u64) -> Self {
+        MarbleTexture {
+            low,
+            high,
+            scale: 1.0,
+            turbulence: 10.0,
+            octaves: 6,
+            perlin: Perlin::new(seed),
+        }
+    }
+
+    pub fn set_scale(&mut self, scale: f64) {
+        self.scale = scale;
+    }
+
+    pub fn set_turbulence(&mut self, turbulence: f64) {
+        self.turbulence = turbulence;
+    }
+
+    pub fn set_octaves(&mut self, octaves: usize) {
+        self.octaves = octaves;
+    }
+}
+
+impl Texture for MarbleTexture {
+    fn value(&self, _uv: (f64, f64), pos: &Vector3) -> Color {
+        let p = pos.mult(self.scale);
+        let phase = p.z + self.turbulence * self.perlin.turbulence(&p, self.octaves);
+        lerp(&self.low, &self.high, 0.5 * (1.0 + phase.sin()))
+    }
+}
+
+// 线性渐变，从 start 处的 from 过渡到 end 处的 to，两端之外保持端点颜色
+pub struct GradientTexture {
+    from: Color,
+    to: Color,
+    start: Vector3,
+    end: Vector3,
+}
+
+impl GradientTexture {
+    pub fn new(from: Color, to: Color, start: Vector3, end: Vector3) -> Self {
+        GradientTexture { from, to, start, end }
+    }
+}
+
+impl Texture for GradientTexture {
+    fn value(&self, _uv: (f64, f64), pos: &Vector3) -> Color {
+        let d = self.end - self.start;
+        let len2 = d.dot(&d);
+        if len2 < EPS {
+            return self.from;
+        }
+        let t = ((pos - self.start).dot(&d) / len2).clamp(0.0, 1.0);
+        lerp(&self.from, &self.to, t)
+    }
+}
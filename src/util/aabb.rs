@@ -0,0 +1,104 @@
+use super::{Ray, Vector3};
+
+fn min_vec(a: &Vector3, b: &Vector3) -> Vector3 {
+    Vector3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z))
+}
+
+fn max_vec(a: &Vector3, b: &Vector3) -> Vector3 {
+    Vector3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z))
+}
+
+// 轴对齐包围盒
+#[derive(Clone, Copy, Debug)]
+pub struct AABB {
+    pub min: Vector3,
+    pub max: Vector3,
+}
+
+impl AABB {
+    pub fn new(min: Vector3, max: Vector3) -> Self {
+        AABB { min, max }
+    }
+
+    // 不包含任何点的包围盒，与任何包围盒合并都得到后者
+    pub fn empty() -> Self {
+        AABB {
+            min: Vector3::new(1e300, 1e300, 1e300),
+            max: Vector3::new(-1e300, -1e300, -1e300),
+        }
+    }
+
+    pub fn from_points(points: &[Vector3]) -> Self {
+        points.iter().fold(AABB::empty(), |b, p| b.grow(p))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn grow(&self, p: &Vector3) -> AABB {
+        AABB {
+            min: min_vec(&self.min, p),
+            max: max_vec(&self.max, p),
+        }
+    }
+
+    pub fn union(&self, other: &AABB) -> AABB {
+        AABB {
+            min: min_vec(&self.min, &other.min),
+            max: max_vec(&self.max, &other.max),
+        }
+    }
+
+    pub fn centroid(&self) -> Vector3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn diagonal(&self) -> Vector3 {
+        self.max - self.min
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let d = self.diagonal();
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    // 最长的坐标轴，0/1/2 分别对应 x/y/z
+    pub fn longest_axis(&self) -> usize {
+        let d = self.diagonal();
+        if d.x >= d.y && d.x >= d.z {
+            0
+        } else if d.y >= d.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn contains(&self, p: &Vector3) -> bool {
+        (0..3).all(|i| self.min[i] <= p[i] && p[i] <= self.max[i])
+    }
+
+    // 射线与包围盒相交的参数区间，inv_d 为射线方向各分量的倒数
+    pub fn intersect(&self, ray: &Ray, inv_d: &Vector3, t_max: f64) -> Option<(f64, f64)> {
+        let mut t0: f64 = 0.0;
+        let mut t1 = t_max;
+        for i in 0..3 {
+            let mut near = (self.min[i] - ray.o[i]) * inv_d[i];
+            let mut far = (self.max[i] - ray.o[i]) * inv_d[i];
+            if near > far {
+                std::mem::swap(&mut near, &mut far);
+            }
+            // 射线平行于该轴时 near/far 可能为 NaN，max/min 会忽略它们
+            t0 = t0.max(near);
+            t1 = t1.min(far);
+            if t0 > t1 {
+                return None;
+            }
+        }
+        Some((t0, t1))
+    }
+}
@@ -1,28 +1,30 @@
 use super::*;
+use crate::scene::bsdf::{Shading, Transport};
 use crate::scene::material::Material;
 use std::sync::Arc;
 
 pub struct Collider {
     pub pos : Vector3,
     pub material : Arc<Material>,
-    pub norm_vec : Vector3,
+    pub norm_vec : Vector3,    // 单位几何法向量，总是朝向射线来的一侧
+    pub shading_normal : Vector3,    // 着色法向量，与 norm_vec 位于同一侧
+    pub front_face : bool,     // 射线是否从物体外侧击中表面
     pub distance : f64,
     pub in_direction : Vector3,
     pub hash_value : u64,
     pub color : Color,
+    pub uv : (f64, f64),    // 交点处的曲面参数
 }
 
 impl Collider {
-    pub fn get_diffuse_ray(&self) -> Option<Vector3> {
-        self.material.cal_diffuse_ray(&self.norm_vec)
-    }
-
-    pub fn get_specular_ray(&self) -> Option<Vector3> {
-        self.material.cal_specular_ray(&self.in_direction, &self.norm_vec)
-    }
-
-    pub fn get_refractive_ray(&self, refracted : bool) -> Option<Vector3> {
-        self.material.cal_refractive_ray(&self.in_direction, &self.norm_vec, refracted)
+    // 交点处的散射信息，media 为射线当前所在的介质
+    pub fn shading(&self, media : &MediumStack, mode : Transport) -> Shading {
+        Shading {
+            normal : self.shading_normal,
+            color : self.color,
+            eta : media.eta(self),
+            mode,
+        }
     }
 
     pub fn get_hash(&self) -> u64 {
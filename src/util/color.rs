@@ -1,5 +1,6 @@
 use std::ops::{ Add, AddAssign, Mul };
 use crate::consts::*;
+use super::tone_map::srgb_encode;
 
 #[derive(Clone, Default, Debug, Copy)]
 pub struct Color {
@@ -37,42 +38,15 @@ impl Color {
         Color { r: self.r / b, g: self.g / b, b: self.b / b }
     }
 
+    // 截断到 [0, 1] 后按 sRGB 传递函数编码，色调映射应在此之前完成
     pub fn to_u16(&self) -> (u16, u16, u16) {
-        let r = if self.r > EPS { 
-            ( self.r.clamp(0.0, 1.0).powf(1.0 / 2.2) * 65535.0 + 0.5 ) as u16 
-        } else { 
-            0u16 
-        };
-        let g = if self.g > EPS { 
-            ( self.g.clamp(0.0, 1.0).powf(1.0 / 2.2) * 65535.0 + 0.5 ) as u16 
-        } else { 
-            0u16 
-        };
-        let b = if self.b > EPS { 
-            ( self.b.clamp(0.0, 1.0).powf(1.0 / 2.2) * 65535.0 + 0.5 ) as u16 
-        } else { 
-            0u16 
-        };
-        (r, g, b)
+        let q = |v: f64| (srgb_encode(v.clamp(0.0, 1.0)) * 65535.0 + 0.5) as u16;
+        (q(self.r), q(self.g), q(self.b))
     }
 
     pub fn to_u8(&self) -> (u8, u8, u8) {
-        let r = if self.r > EPS { 
-            ( self.r.clamp(0.0, 1.0).powf(1.0 / 2.2) * 255.0 + 0.5 ) as u8 
-        } else { 
-            0u8 
-        };
-        let g = if self.g > EPS { 
-            ( self.g.clamp(0.0, 1.0).powf(1.0 / 2.2) * 255.0 + 0.5 ) as u8 
-        } else { 
-            0u8 
-        };
-        let b = if self.b > EPS { 
-            ( self.b.clamp(0.0, 1.0).powf(1.0 / 2.2) * 255.0 + 0.5 ) as u8 
-        } else { 
-            0u8 
-        };
-        (r, g, b)
+        let q = |v: f64| (srgb_encode(v.clamp(0.0, 1.0)) * 255.0 + 0.5) as u8;
+        (q(self.r), q(self.g), q(self.b))
     }
 
     pub fn power(&self) -> f64 {
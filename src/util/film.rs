@@ -0,0 +1,198 @@
+use super::{Color, Framebuffer};
+use std::str::FromStr;
+
+/*
+ * 胶片：把落在成像平面上任意位置的样本按重建滤波器加权累加到附近的像素上
+ *
+ * 像素 (i, j) 的中心在坐标 (i, j)，覆盖 [i - 0.5, i + 0.5) × [j - 0.5, j + 0.5)。
+ * 每个像素记录加权的颜色之和、权重之和以及落在其中的样本数，最终的颜色为两者之比，
+ * 因此各像素的样本数不同也能得到正确归一化的图像。
+ */
+
+// 重建滤波器的种类
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FilterKind {
+    Box,      // 半径内权重相同
+    Tent,     // 线性衰减
+    Gaussian, // 截断后平移到半径处为 0 的高斯函数
+    Mitchell, // Mitchell-Netravali 三次滤波器，B = C = 1/3
+}
+
+impl FromStr for FilterKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "box" => Ok(FilterKind::Box),
+            "tent" => Ok(FilterKind::Tent),
+            "gaussian" => Ok(FilterKind::Gaussian),
+            "mitchell" => Ok(FilterKind::Mitchell),
+            _ => Err(format!("unknown filter `{}`", s)),
+        }
+    }
+}
+
+// 可分离的滤波器，radius 以像素为单位
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Filter {
+    pub kind: FilterKind,
+    pub radius: f64,
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter::new(FilterKind::Box)
+    }
+}
+
+// 高斯滤波器的衰减系数
+const GAUSSIAN_ALPHA: f64 = 2.0;
+
+// Mitchell-Netravali 滤波器在 [0, 2] 上的一维形式
+fn mitchell_1d(x: f64) -> f64 {
+    let (b, c) = (1.0 / 3.0, 1.0 / 3.0);
+    let x = x.abs();
+    if x < 1.0 {
+        ((12.0 - 9.0 * b - 6.0 * c) * x * x * x + (-18.0 + 12.0 * b + 6.0 * c) * x * x + (6.0 - 2.0 * b)) / 6.0
+    } else if x < 2.0 {
+        ((-b - 6.0 * c) * x * x * x + (6.0 * b + 30.0 * c) * x * x + (-12.0 * b - 48.0 * c) * x + (8.0 * b + 24.0 * c))
+            / 6.0
+    } else {
+        0.0
+    }
+}
+
+impl Filter {
+    // 使用各种滤波器常用的半径
+    pub fn new(kind: FilterKind) -> Self {
+        let radius = match kind {
+            FilterKind::Box => 0.5,
+            FilterKind::Tent => 1.0,
+            FilterKind::Gaussian => 1.5,
+            FilterKind::Mitchell => 2.0,
+        };
+        Filter { kind, radius }
+    }
+
+    fn eval_1d(&self, d: f64) -> f64 {
+        let d = d.abs();
+        if d > self.radius {
+            return 0.0;
+        }
+        match self.kind {
+            FilterKind::Box => 1.0,
+            FilterKind::Tent => 1.0 - d / self.radius,
+            FilterKind::Gaussian => {
+                let r2 = self.radius * self.radius;
+                ((-GAUSSIAN_ALPHA * d * d).exp() - (-GAUSSIAN_ALPHA * r2).exp()).max(0.0)
+            }
+            FilterKind::Mitchell => mitchell_1d(2.0 * d / self.radius),
+        }
+    }
+
+    // 样本相对像素中心的偏移为 (dx, dy) 时的权重，Mitchell 滤波器可能为负
+    pub fn eval(&self, dx: f64, dy: f64) -> f64 {
+        self.eval_1d(dx) * self.eval_1d(dy)
+    }
+}
+
+#[derive(Clone)]
+pub struct Film {
+    pub width: usize,
+    pub height: usize,
+    filter: Filter,
+    sum: Vec<Color>, // 加权的颜色之和
+    weight: Vec<f64>, // 权重之和
+    count: Vec<u32>,  // 落在像素内的样本数
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize, filter: Filter) -> Self {
+        Film {
+            width,
+            height,
+            filter,
+            sum: vec![Color::default(); width * height],
+            weight: vec![0.0; width * height],
+            count: vec![0; width * height],
+        }
+    }
+
+    // 在成像平面的 (x, y) 处加入一个样本，累加到中心与它的距离不超过滤波器半径的像素上
+    pub fn add_sample(&mut self, x: f64, y: f64, color: Color) {
+        self.add_weighted_sample(x, y, color, 1.0);
+    }
+
+    // 滤波器权重再乘以 scale，同一位置的若干子样本可以分摊一个样本的权重
+    pub fn add_weighted_sample(&mut self, x: f64, y: f64, color: Color, scale: f64) {
+        let (px, py) = ((x + 0.5).floor(), (y + 0.5).floor());
+        if px >= 0.0 && py >= 0.0 && (px as usize) < self.width && (py as usize) < self.height {
+            self.count[py as usize * self.width + px as usize] += 1;
+        }
+        let (sum, weight) = (&mut self.sum, &mut self.weight);
+        Self::splat_with(self.filter, self.width, self.height, x, y, |idx, w| {
+            sum[idx] += color.mult(w * scale);
+            weight[idx] += w * scale;
+        });
+    }
+
+    /*
+     * 给 (x, y) 处已经加入的样本补充颜色，只累加颜色而不增加权重，
+     * 用于样本的一部分辐射亮度在加入之后才能算出的情形，如 SPPM 的光子估计
+     */
+    pub fn add_splat(&mut self, x: f64, y: f64, color: Color) {
+        let sum = &mut self.sum;
+        Self::splat_with(self.filter, self.width, self.height, x, y, |idx, w| {
+            sum[idx] += color.mult(w);
+        });
+    }
+
+    // 对中心与 (x, y) 的距离不超过滤波器半径的每个像素调用 f(像素下标, 滤波器权重)
+    fn splat_with(filter: Filter, width: usize, height: usize, x: f64, y: f64, mut f: impl FnMut(usize, f64)) {
+        // 像素 i 接受 [i - r, i + r) 中的样本，使半径为 0.5 的盒式滤波器恰好对应像素本身
+        let r = filter.radius;
+        let x0 = ((x - r).floor() + 1.0).max(0.0) as usize;
+        let y0 = ((y - r).floor() + 1.0).max(0.0) as usize;
+        let x1 = (x + r).floor().min(width as f64 - 1.0);
+        let y1 = (y + r).floor().min(height as f64 - 1.0);
+        if x1 < 0.0 || y1 < 0.0 {
+            return;
+        }
+        for j in y0..=y1 as usize {
+            for i in x0..=x1 as usize {
+                let w = filter.eval(x - i as f64, y - j as f64);
+                if w != 0.0 {
+                    f(j * width + i, w);
+                }
+            }
+        }
+    }
+
+    // 合并另一张同样大小的胶片上的样本
+    pub fn merge(&mut self, other: &Film) {
+        for idx in 0..self.sum.len() {
+            self.sum[idx] += other.sum[idx];
+            self.weight[idx] += other.weight[idx];
+            self.count[idx] += other.count[idx];
+        }
+    }
+
+    pub fn sample_count(&self, x: usize, y: usize) -> u32 {
+        self.count[y * self.width + x]
+    }
+
+    pub fn weight(&self, x: usize, y: usize) -> f64 {
+        self.weight[y * self.width + x]
+    }
+
+    // 按权重之和归一化，没有收到样本(或权重之和几乎为 0)的像素为黑色
+    pub fn to_framebuffer(&self) -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(self.width, self.height);
+        for (idx, pixel) in framebuffer.pixels.iter_mut().enumerate() {
+            if self.weight[idx] > 1e-8 {
+                *pixel = self.sum[idx].mult(1.0 / self.weight[idx]);
+            }
+        }
+        framebuffer
+    }
+}
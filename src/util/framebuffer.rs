@@ -0,0 +1,43 @@
+use super::image_file::{write_image, ImageFormat};
+use super::tone_map::ToneMapping;
+use super::Color;
+use std::io;
+use std::path::Path;
+
+// 渲染结果，按行优先存放每个像素的颜色
+#[derive(Clone)]
+pub struct Framebuffer {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Color>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![Color::default(); width * height],
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: Color) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    // 对每个像素做色调映射，得到可以直接编码显示的线性颜色
+    pub fn tone_map(&mut self, tone_mapping: &ToneMapping) {
+        for c in self.pixels.iter_mut() {
+            *c = tone_mapping.map(*c);
+        }
+    }
+
+    // 按 format 写出图像，PNG 按 sRGB 编码，其余格式保存原始的辐射亮度
+    pub fn save(&self, path: &Path, format: ImageFormat) -> io::Result<()> {
+        write_image(path, format, self.width, self.height, &self.pixels)
+    }
+}
@@ -0,0 +1,199 @@
+use super::Color;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/*
+ * 图像文件的写出
+ *
+ * PNG 截断到 [0, 1] 并按 sRGB 编码，8 位或 16 位，需要时先由 ToneMapping 做色调映射；PFM、Radiance HDR 和 OpenEXR 保存未经处理的浮点辐射亮度
+ */
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImageFormat {
+    Png { bit_depth: u32 }, // 每个通道 8 位或 16 位
+    Pfm,                    // 便携浮点图，每个通道 32 位浮点数
+    Hdr,                    // Radiance RGBE，每个像素 4 字节，按行程编码压缩
+    Exr,                    // OpenEXR，不压缩的 32 位浮点数
+}
+
+impl ImageFormat {
+    // 根据文件扩展名选择格式，PNG 默认为 8 位
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "png" => Some(ImageFormat::Png { bit_depth: 8 }),
+            "pfm" => Some(ImageFormat::Pfm),
+            "hdr" => Some(ImageFormat::Hdr),
+            "exr" => Some(ImageFormat::Exr),
+            _ => None,
+        }
+    }
+}
+
+// 按行优先、从上到下存放的 width * height 个像素
+pub fn write_image(path: &Path, format: ImageFormat, width: usize, height: usize, pixels: &[Color]) -> io::Result<()> {
+    match format {
+        ImageFormat::Png { bit_depth } => write_png(path, bit_depth, width, height, pixels),
+        _ => {
+            let mut out = BufWriter::new(File::create(path)?);
+            match format {
+                ImageFormat::Pfm => write_pfm(&mut out, width, height, pixels)?,
+                ImageFormat::Hdr => write_hdr(&mut out, width, height, pixels)?,
+                _ => write_exr(&mut out, width, height, pixels)?,
+            }
+            out.flush()
+        }
+    }
+}
+
+fn write_png(path: &Path, bit_depth: u32, width: usize, height: usize, pixels: &[Color]) -> io::Result<()> {
+    let mut buffer = Vec::with_capacity(pixels.len() * 6);
+    for color in pixels {
+        if bit_depth == 16 {
+            // 16 位的 PNG 数据按大端序存放
+            let (r, g, b) = color.to_u16();
+            for v in [r, g, b] {
+                buffer.extend_from_slice(&v.to_be_bytes());
+            }
+        } else {
+            let (r, g, b) = color.to_u8();
+            buffer.extend_from_slice(&[r, g, b]);
+        }
+    }
+    let bit_depth = if bit_depth == 16 { 16 } else { 8 };
+    lodepng::encode_file(path, &buffer, width, height, lodepng::ColorType::RGB, bit_depth)
+        .map_err(|e| io::Error::other(e.to_string()))
+}
+
+// 比例因子为负表示小端序，像素从下到上逐行存放
+pub fn write_pfm<W: Write>(out: &mut W, width: usize, height: usize, pixels: &[Color]) -> io::Result<()> {
+    write!(out, "PF\n{} {}\n-1.0\n", width, height)?;
+    for row in pixels.chunks(width).rev() {
+        for c in row {
+            for v in [c.r, c.g, c.b] {
+                out.write_all(&(v as f32).to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// 共用一个指数的 RGBE 编码，见 Graphics Gems II 中 Ward 的 Real Pixels
+fn to_rgbe(c: &Color) -> [u8; 4] {
+    let v = c.r.max(c.g).max(c.b);
+    if v.is_nan() || v < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+    // v = m * 2^e，m 在 [0.5, 1) 中
+    let mut e = v.log2().floor() as i32 + 1;
+    if v / 2f64.powi(e) >= 1.0 {
+        e += 1;
+    }
+    let e = e.clamp(-128, 127);
+    let scale = 256.0 / 2f64.powi(e);
+    let q = |x: f64| (x.max(0.0) * scale).min(255.0) as u8;
+    [q(c.r), q(c.g), q(c.b), (e + 128) as u8]
+}
+
+// 一个分量的一行数据：长度不少于 4 的重复字节写成 (128 + 长度, 字节)，其余写成 (长度, 原样的字节)
+fn write_rle_component<W: Write>(out: &mut W, data: &[u8]) -> io::Result<()> {
+    let mut i = 0;
+    while i < data.len() {
+        let mut run = 1;
+        while i + run < data.len() && run < 127 && data[i + run] == data[i] {
+            run += 1;
+        }
+        if run >= 4 {
+            out.write_all(&[128 + run as u8, data[i]])?;
+            i += run;
+            continue;
+        }
+        // 直到下一段足够长的重复之前的字节都原样写出
+        let start = i;
+        while i < data.len() && i - start < 128 {
+            if i + 3 < data.len() && data[i] == data[i + 1] && data[i] == data[i + 2] && data[i] == data[i + 3] {
+                break;
+            }
+            i += 1;
+        }
+        out.write_all(&[(i - start) as u8])?;
+        out.write_all(&data[start..i])?;
+    }
+    Ok(())
+}
+
+pub fn write_hdr<W: Write>(out: &mut W, width: usize, height: usize, pixels: &[Color]) -> io::Result<()> {
+    write!(out, "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", height, width)?;
+    for row in pixels.chunks(width) {
+        let rgbe: Vec<[u8; 4]> = row.iter().map(to_rgbe).collect();
+        if !(8..0x8000).contains(&width) {
+            // 行程编码只支持这个范围内的宽度
+            for p in rgbe.iter() {
+                out.write_all(p)?;
+            }
+            continue;
+        }
+        out.write_all(&[2, 2, (width >> 8) as u8, (width & 0xff) as u8])?;
+        for k in 0..4 {
+            let component: Vec<u8> = rgbe.iter().map(|p| p[k]).collect();
+            write_rle_component(out, &component)?;
+        }
+    }
+    Ok(())
+}
+
+fn exr_attribute<W: Write>(out: &mut W, name: &str, kind: &str, value: &[u8]) -> io::Result<()> {
+    out.write_all(name.as_bytes())?;
+    out.write_all(&[0])?;
+    out.write_all(kind.as_bytes())?;
+    out.write_all(&[0])?;
+    out.write_all(&(value.len() as i32).to_le_bytes())?;
+    out.write_all(value)
+}
+
+// 单层、按扫描线存放、不压缩的 OpenEXR 文件，通道按名字的字典序排列
+pub fn write_exr<W: Write>(out: &mut W, width: usize, height: usize, pixels: &[Color]) -> io::Result<()> {
+    let le = |v: i32| v.to_le_bytes();
+    let mut header = vec![0x76, 0x2f, 0x31, 0x01];
+    header.extend_from_slice(&le(2)); // 版本 2，单层扫描线图像
+
+    let mut channels = Vec::new();
+    for name in ["B", "G", "R"] {
+        channels.extend_from_slice(name.as_bytes());
+        channels.push(0);
+        channels.extend_from_slice(&le(2)); // FLOAT
+        channels.extend_from_slice(&[0, 0, 0, 0]); // pLinear 与保留字节
+        channels.extend_from_slice(&le(1));
+        channels.extend_from_slice(&le(1));
+    }
+    channels.push(0);
+    let window: Vec<u8> = [0, 0, width as i32 - 1, height as i32 - 1].iter().flat_map(|v| le(*v)).collect();
+    exr_attribute(&mut header, "channels", "chlist", &channels)?;
+    exr_attribute(&mut header, "compression", "compression", &[0])?;
+    exr_attribute(&mut header, "dataWindow", "box2i", &window)?;
+    exr_attribute(&mut header, "displayWindow", "box2i", &window)?;
+    exr_attribute(&mut header, "lineOrder", "lineOrder", &[0])?;
+    exr_attribute(&mut header, "pixelAspectRatio", "float", &1f32.to_le_bytes())?;
+    exr_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8])?;
+    exr_attribute(&mut header, "screenWindowWidth", "float", &1f32.to_le_bytes())?;
+    header.push(0);
+    out.write_all(&header)?;
+
+    // 头部之后是每一行数据块在文件中的偏移量，每块为 y 坐标、数据长度和各通道的一行数据
+    let line_len = width * 3 * 4;
+    let first = header.len() + height * 8;
+    for y in 0..height {
+        out.write_all(&((first + y * (8 + line_len)) as u64).to_le_bytes())?;
+    }
+    for (y, row) in pixels.chunks(width).enumerate() {
+        out.write_all(&le(y as i32))?;
+        out.write_all(&le(line_len as i32))?;
+        for channel in [|c: &Color| c.b, |c: &Color| c.g, |c: &Color| c.r] {
+            for c in row {
+                out.write_all(&(channel(c) as f32).to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use super::collision::Collider;
+
+/*
+ * 射线当前所在的介质：按进入的先后顺序记录包含射线的折射物体及其折射率
+ * 物体相互重叠时以最后进入的物体为准，穿过被覆盖的物体的边界不会发生折射
+ */
+#[derive(Clone, Debug, Default)]
+pub struct MediumStack {
+    media: Vec<(u64, f64)>, // (物体的哈希值, 折射率)
+}
+
+impl MediumStack {
+    // 射线从真空中出发
+    pub fn new() -> Self {
+        MediumStack { media: Vec::new() }
+    }
+
+    // 当前介质的折射率，不在任何物体内部时为 1
+    pub fn ior(&self) -> f64 {
+        self.media.last().map_or(1.0, |m| m.1)
+    }
+
+    pub fn depth(&self) -> usize {
+        self.media.len()
+    }
+
+    // 穿过 collider 处的表面之后所在介质的折射率
+    fn ior_after(&self, collider: &Collider) -> f64 {
+        if collider.front_face {
+            return collider.material.rindex;
+        }
+        let len = self.media.len();
+        match self.media.iter().rposition(|m| m.0 == collider.get_hash()) {
+            Some(i) if i + 1 == len && i > 0 => self.media[i - 1].1,
+            Some(i) if i + 1 == len => 1.0,
+            _ => self.ior(),
+        }
+    }
+
+    // 表面另一侧与射线所在一侧介质的折射率之比
+    pub fn eta(&self, collider: &Collider) -> f64 {
+        self.ior_after(collider) / self.ior()
+    }
+
+    // 射线穿过 collider 处的表面：从正面穿过时进入该物体，从背面穿过时离开该物体
+    pub fn cross(&mut self, collider: &Collider) {
+        let id = collider.get_hash();
+        if collider.front_face {
+            self.media.push((id, collider.material.rindex));
+        } else if let Some(i) = self.media.iter().rposition(|m| m.0 == id) {
+            self.media.remove(i);
+        }
+    }
+}
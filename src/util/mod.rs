@@ -1,12 +1,28 @@
+pub mod aabb;
 pub mod vector3;
 pub mod view_point;
 pub mod color;
 pub mod collision;
+pub mod film;
+pub mod framebuffer;
+pub mod image_file;
+pub mod medium;
+pub mod sampler;
+pub mod sampling;
+pub mod tone_map;
 
+pub use aabb::AABB;
 pub use vector3::*;
 pub use color::Color;
 pub use view_point::{ViewPoint, Photon};
 pub use collision::{ Collider, LightCollider };
+pub use film::{Film, Filter, FilterKind};
+pub use framebuffer::Framebuffer;
+pub use image_file::ImageFormat;
+pub use medium::MediumStack;
+pub use sampler::{Sampler, SamplerKind};
+pub use sampling::Frame;
+pub use tone_map::{ToneMapper, ToneMapping};
 
 use std::hash::{ Hash, Hasher };
 use std::collections::hash_map::DefaultHasher;
@@ -0,0 +1,237 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::str::FromStr;
+
+/*
+ * 渲染中所有随机数的来源
+ *
+ * 每个线程在每一轮中使用各自的随机数流，由用户给定的种子、轮次和线程编号派生，
+ * 因此相同的种子和线程数总是得到完全相同的图像。
+ *
+ * 样本按序列组织：同一像素的各次采样、同一光源发射的各个光子分别构成一个序列，
+ * 由 start_sequence 开始，每个样本由 start_sample 开始，之后依次调用 get_1d / get_2d 取得各维的值。
+ * 低差异序列在同一维上让序列中的样本分布得尽可能均匀，不在序列中时所有采样器都退化为独立的随机数。
+ */
+
+// 采样器的种类
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SamplerKind {
+    Independent, // 独立的均匀随机数
+    Stratified,  // 分层抖动，每一维的分层顺序随机打乱
+    Halton,      // Owen 扰乱的 Halton 序列
+    Sobol,       // Owen 扰乱的二维 Sobol 序列，各维之间打乱样本顺序
+}
+
+impl FromStr for SamplerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "independent" => Ok(SamplerKind::Independent),
+            "stratified" => Ok(SamplerKind::Stratified),
+            "halton" => Ok(SamplerKind::Halton),
+            "sobol" => Ok(SamplerKind::Sobol),
+            _ => Err(format!("unknown sampler `{}`", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Sampler {
+    kind: SamplerKind,
+    rng: StdRng,
+    stream_key: u64, // 由种子和线程编号决定，与轮次无关，使序列可以跨越多轮
+    sequence: Option<(u64, u64)>, // 当前序列的哈希值与样本数
+    index: u64,      // 当前样本在序列中的序号
+    dimension: u64,  // 当前样本下一个要使用的维度
+}
+
+// SplitMix64 的输出函数，将相近的输入打散成互不相关的 64 位整数
+fn mix(z: u64) -> u64 {
+    let mut z = z.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+// 小于 1 的最大浮点数
+const ONE_MINUS_EPSILON: f64 = 1.0 - f64::EPSILON / 2.0;
+
+// Halton 序列各维使用的底数
+const PRIMES: [u64; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97, 101, 103, 107,
+    109, 113, 127, 131,
+];
+
+/*
+ * 由 seed 决定的 [0, n) 上的一个随机排列中的第 i 个元素，n 不超过 2^32
+ * 见 Kensler, Correlated Multi-Jittered Sampling, 2013
+ */
+fn permutation_element(i: u64, n: u64, seed: u64) -> u64 {
+    let p = seed as u32;
+    let l = n as u32;
+    let mut w = l.wrapping_sub(1);
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    let mut i = i as u32;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < l {
+            break;
+        }
+    }
+    ((i as u64) + (p as u64)) % n
+}
+
+// 以 base 为底的根式反演，每一位数字按之前各位决定的随机排列置换，即 Owen 扰乱
+fn scrambled_radical_inverse(base: u64, mut a: u64, seed: u64) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let mut reversed = 0u64;
+    let mut inv_base_m = 1.0;
+    // 直到剩余的位数超出浮点数的精度为止
+    while 1.0 - inv_base_m < 1.0 {
+        let digit = a % base;
+        a /= base;
+        let digit = permutation_element(digit, base, mix(seed ^ reversed));
+        reversed = reversed * base + digit;
+        inv_base_m *= inv_base;
+    }
+    (reversed as f64 * inv_base_m).min(ONE_MINUS_EPSILON)
+}
+
+// 对 32 位定点小数做 Owen 扰乱，见 pbrt-v4 中的 FastOwenScrambler
+fn owen_scramble(v: u32, seed: u32) -> u32 {
+    let mut v = v.reverse_bits();
+    v ^= v.wrapping_mul(0x3d20_adea);
+    v = v.wrapping_add(seed);
+    v = v.wrapping_mul((seed >> 16) | 1);
+    v ^= v.wrapping_mul(0x0552_6c56);
+    v ^= v.wrapping_mul(0x53a2_2864);
+    v.reverse_bits()
+}
+
+// 二维 Sobol 序列的第 i 个点：第一维是 van der Corput 序列，第二维的生成矩阵是模 2 的杨辉三角
+fn sobol_2d(i: u32) -> (u32, u32) {
+    let x = i.reverse_bits();
+    let (mut y, mut v, mut i) = (0u32, 1u32 << 31, i);
+    while i != 0 {
+        if i & 1 != 0 {
+            y ^= v;
+        }
+        v ^= v >> 1;
+        i >>= 1;
+    }
+    (x, y)
+}
+
+fn to_unit(v: u32) -> f64 {
+    (v as f64 / 4_294_967_296.0).min(ONE_MINUS_EPSILON)
+}
+
+impl Sampler {
+    pub fn new(kind: SamplerKind, seed: u64) -> Self {
+        Sampler::derive(kind, seed, 0, 0)
+    }
+
+    // 第 pass 轮中编号为 stream 的线程使用的采样器
+    pub fn derive(kind: SamplerKind, seed: u64, pass: u64, stream: u64) -> Self {
+        Sampler {
+            kind,
+            rng: StdRng::seed_from_u64(mix(mix(mix(seed) ^ pass) ^ stream)),
+            stream_key: mix(mix(seed) ^ mix(stream)),
+            sequence: None,
+            index: 0,
+            dimension: 0,
+        }
+    }
+
+    // 开始一个包含 count 个样本的序列，key 在同一线程中区分不同的序列；count 不超过 2^31
+    pub fn start_sequence(&mut self, key: u64, count: u64) {
+        self.sequence = Some((mix(self.stream_key ^ mix(key)), count.clamp(1, 1 << 31)));
+        self.start_sample(0);
+    }
+
+    // 开始当前序列中的第 index 个样本
+    pub fn start_sample(&mut self, index: u64) {
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    // [0, 1) 中均匀分布的随机数
+    pub fn get_1d(&mut self) -> f64 {
+        let (hash, count) = match self.sequence {
+            Some(sequence) if self.kind != SamplerKind::Independent => sequence,
+            _ => return self.rng.gen(),
+        };
+        let dim = self.dimension;
+        self.dimension += 1;
+        let hash = mix(hash ^ dim);
+        let index = self.index % count;
+        match self.kind {
+            SamplerKind::Stratified => {
+                let stratum = permutation_element(index, count, hash);
+                (stratum as f64 + self.rng.gen::<f64>()) / count as f64
+            }
+            SamplerKind::Halton if (dim as usize) < PRIMES.len() => {
+                scrambled_radical_inverse(PRIMES[dim as usize], self.index, hash)
+            }
+            SamplerKind::Sobol => {
+                let i = permutation_element(index, count, hash);
+                to_unit(owen_scramble(sobol_2d(i as u32).0, mix(hash) as u32))
+            }
+            _ => self.rng.gen(),
+        }
+    }
+
+    pub fn get_2d(&mut self) -> (f64, f64) {
+        let (hash, count) = match self.sequence {
+            Some(sequence) if self.kind == SamplerKind::Stratified || self.kind == SamplerKind::Sobol => sequence,
+            _ => return (self.get_1d(), self.get_1d()),
+        };
+        let dim = self.dimension;
+        self.dimension += 2;
+        let hash = mix(hash ^ dim);
+        let index = self.index % count;
+        match self.kind {
+            SamplerKind::Stratified => {
+                // 分成 nx * ny 个格子，样本数不能整除时有少数格子空着
+                let nx = (count as f64).sqrt().ceil() as u64;
+                let ny = count.div_ceil(nx);
+                let cell = permutation_element(index, nx * ny, hash);
+                let (jx, jy): (f64, f64) = (self.rng.gen(), self.rng.gen());
+                (((cell % nx) as f64 + jx) / nx as f64, ((cell / nx) as f64 + jy) / ny as f64)
+            }
+            _ => {
+                let i = permutation_element(index, count, hash);
+                let (x, y) = sobol_2d(i as u32);
+                let scramble = mix(hash);
+                (to_unit(owen_scramble(x, scramble as u32)), to_unit(owen_scramble(y, (scramble >> 32) as u32)))
+            }
+        }
+    }
+
+    // [0, n) 中均匀分布的整数，n 不能为 0
+    pub fn index(&mut self, n: usize) -> usize {
+        ((self.get_1d() * n as f64) as usize).min(n - 1)
+    }
+}
@@ -0,0 +1,68 @@
+use super::vector3::*;
+use std::f64::consts::PI;
+
+/*
+ * 方向采样
+ *
+ * 所有采样函数都把 [0, 1)² 中均匀分布的 u 映射为单位向量，半球采样返回以 z 轴为极轴的局部坐标，
+ * 需要时用 Frame 变换到以法向量为 z 轴的世界坐标。相应的 *_pdf 为关于立体角的概率密度。
+ */
+
+// 单位球面上的均匀分布：z 在 [-1, 1] 中均匀，方位角在 [0, 2π) 中均匀
+pub fn uniform_sphere(u: (f64, f64)) -> Vector3 {
+    let z = 1.0 - 2.0 * u.0;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.1;
+    Vector3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+pub fn uniform_sphere_pdf() -> f64 {
+    1.0 / (4.0 * PI)
+}
+
+// z >= 0 的半球面上的均匀分布
+pub fn uniform_hemisphere(u: (f64, f64)) -> Vector3 {
+    let z = 1.0 - u.0;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.1;
+    Vector3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+pub fn uniform_hemisphere_pdf() -> f64 {
+    1.0 / (2.0 * PI)
+}
+
+// 余弦加权的半球分布(Malley 方法)：在单位圆盘上均匀采样后投影到半球
+pub fn cosine_hemisphere(u: (f64, f64)) -> Vector3 {
+    let r = u.0.sqrt();
+    let phi = 2.0 * PI * u.1;
+    Vector3::new(r * phi.cos(), r * phi.sin(), (1.0 - u.0).max(0.0).sqrt())
+}
+
+// cos 为方向与 z 轴夹角的余弦
+pub fn cosine_hemisphere_pdf(cos: f64) -> f64 {
+    cos.max(0.0) / PI
+}
+
+// 以法向量为 z 轴的正交坐标系
+pub struct Frame {
+    s: Vector3,
+    t: Vector3,
+    n: Vector3,
+}
+
+impl Frame {
+    pub fn new(n: &Vector3) -> Self {
+        let s = n.get_vertical_vec();
+        let t = n.cross(&s);
+        Frame { s, t, n: *n }
+    }
+
+    pub fn to_local(&self, v: &Vector3) -> Vector3 {
+        Vector3::new(v.dot(&self.s), v.dot(&self.t), v.dot(&self.n))
+    }
+
+    pub fn to_world(&self, v: &Vector3) -> Vector3 {
+        self.s.mult(v.x) + self.t.mult(v.y) + self.n.mult(v.z)
+    }
+}
@@ -0,0 +1,121 @@
+use super::Color;
+use std::str::FromStr;
+
+/*
+ * 色调映射
+ *
+ * 把场景中的辐射亮度映射到显示器能表示的 [0, 1] 范围：先乘以曝光系数 2^exposure，再经过映射算子。
+ * 结果仍是线性值，写出 PNG 时由 Color::to_u8 / to_u16 截断并按 sRGB 传递函数编码。
+ */
+
+// 色调映射算子
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToneMapper {
+    Clamp,    // 不做映射，超出 [0, 1] 的部分直接截断
+    Reinhard, // 对亮度做 L / (1 + L)，保持色相
+    Aces,     // ACES 电影曲线，使用 Hill 对 RRT 与 ODT 的拟合
+}
+
+impl FromStr for ToneMapper {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "clamp" => Ok(ToneMapper::Clamp),
+            "reinhard" => Ok(ToneMapper::Reinhard),
+            "aces" => Ok(ToneMapper::Aces),
+            _ => Err(format!("unknown tone mapper `{}`", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ToneMapping {
+    pub exposure: f64,        // 曝光补偿，单位为档，每增加 1 亮度加倍
+    pub operator: ToneMapper, // 映射算子
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        ToneMapping { exposure: 0.0, operator: ToneMapper::Clamp }
+    }
+}
+
+// 线性 sRGB 到 ACES 工作空间，包含 RRT 的饱和度调整
+const ACES_INPUT: [[f64; 3]; 3] = [
+    [0.59719, 0.35458, 0.04823],
+    [0.07600, 0.90834, 0.01566],
+    [0.02840, 0.13383, 0.83777],
+];
+
+// ODT 之后回到线性 sRGB
+const ACES_OUTPUT: [[f64; 3]; 3] = [
+    [1.60475, -0.53108, -0.07367],
+    [-0.10208, 1.10813, -0.00605],
+    [-0.00327, -0.07276, 1.07602],
+];
+
+fn apply_matrix(m: &[[f64; 3]; 3], c: Color) -> Color {
+    let row = |k: usize| m[k][0] * c.r + m[k][1] * c.g + m[k][2] * c.b;
+    Color::new(row(0), row(1), row(2))
+}
+
+fn rrt_and_odt_fit(v: f64) -> f64 {
+    let a = v * (v + 0.0245786) - 0.000090537;
+    let b = v * (0.983729 * v + 0.4329510) + 0.238081;
+    a / b
+}
+
+// Rec. 709 原色下的相对亮度
+pub fn luminance(c: &Color) -> f64 {
+    0.2126 * c.r + 0.7152 * c.g + 0.0722 * c.b
+}
+
+// sRGB 传递函数：接近 0 时为线性段，其余为指数 2.4 的幂函数
+pub fn srgb_encode(v: f64) -> f64 {
+    if v <= 0.0031308 {
+        12.92 * v
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// srgb_encode 的逆，用于读入的纹理
+pub fn srgb_decode(v: f64) -> f64 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+impl ToneMapping {
+    pub fn new(exposure: f64, operator: ToneMapper) -> Self {
+        ToneMapping { exposure, operator }
+    }
+
+    // 返回线性值，Clamp 之外的算子保证各分量不超过 1
+    pub fn map(&self, c: Color) -> Color {
+        let c = c.mult(2f64.powf(self.exposure));
+        // 负值和 NaN 都视为 0
+        let c = Color::new(c.r.max(0.0), c.g.max(0.0), c.b.max(0.0));
+        match self.operator {
+            ToneMapper::Clamp => c,
+            ToneMapper::Reinhard => {
+                let l = luminance(&c);
+                if l <= 0.0 {
+                    return Color::default();
+                }
+                let c = c.mult(1.0 / (1.0 + l));
+                // 饱和的颜色在亮度映射后仍可能有分量超过 1
+                c.mult(1.0 / c.r.max(c.g).max(c.b).max(1.0))
+            }
+            ToneMapper::Aces => {
+                let v = apply_matrix(&ACES_INPUT, c);
+                let v = Color::new(rrt_and_odt_fit(v.r), rrt_and_odt_fit(v.g), rrt_and_odt_fit(v.b));
+                let v = apply_matrix(&ACES_OUTPUT, v);
+                Color::new(v.r.clamp(0.0, 1.0), v.g.clamp(0.0, 1.0), v.b.clamp(0.0, 1.0))
+            }
+        }
+    }
+}
@@ -1,4 +1,3 @@
-use rand::Rng;
 use nalgebra::Vector3 as V3;
 pub use num_traits::identities::Zero;
 use nalgebra::Rotation3;
@@ -7,7 +6,7 @@ use nalgebra::Unit;
 pub type Vector3 = V3<f64>;
 
 pub trait MyVector3 {
-    fn random() -> Self;
+    fn random(sampler : &mut super::Sampler) -> Self;
     fn mult(&self, b: f64) -> Vector3;
     fn distance(&self, other : &Vector3) -> f64;
     fn distance2(&self, other : &Vector3) -> f64;
@@ -16,12 +15,8 @@ pub trait MyVector3 {
 }
 
 impl MyVector3 for Vector3 {
-    fn random() -> Self {   // generate a vec randomly
-        Vector3::new(
-            rand::thread_rng().gen_range(-10.0, 10.0),
-            rand::thread_rng().gen_range(-10.0, 10.0),
-            rand::thread_rng().gen_range(-10.0, 10.0)
-        ).normalize()
+    fn random(sampler : &mut super::Sampler) -> Self {   // 单位球面上均匀分布的随机方向
+        super::sampling::uniform_sphere(sampler.get_2d())
     }
 
     fn mult(&self, b: f64) -> Vector3 {    // multi a number on this vec
@@ -1,6 +1,6 @@
-use super::{Ray, Vector3, MyVector3, color::Color, collision::Collider};
+use super::{Ray, Vector3, MyVector3, color::Color, collision::Collider, medium::MediumStack};
+use crate::scene::bsdf::{Shading, Transport};
 use crate::scene::material::Material;
-use crate::consts::*;
 use std::sync::Arc;
 
 #[derive(Clone)]
@@ -12,10 +12,12 @@ pub struct Photon {
 #[derive(Clone)]
 pub struct ViewPoint {
     pub pos: Vector3,   // 位置
-    pub norm: Vector3,  // 该处的法向量
+    pub shading: Shading, // 该处的法向量、表面颜色等散射信息
     pub dire: Vector3,  // 击中该处的视线射线方向
     pub px_pos : usize, // 在图片中对应的像素位置
-    pub color: Color, // 本身颜色值
+    pub index : usize,  // 在本轮所有视点中的序号
+    pub sample : usize, // 产生该视点的视线样本的序号
+    pub weight: Color, // 视线从相机到达该处时累积的权重
     pub radius2: f64,
     pub count: f64, // 已经被统计到该视点名下的光子数量
     pub delta: f64, // 当前这轮被统计到该视点名下的光子数量
@@ -24,14 +26,17 @@ pub struct ViewPoint {
 }
 
 impl ViewPoint {
-    pub fn new(collider : &Collider, px_pos: usize,  wgt : f64) -> Self {
+    // media : 视线当前所在的介质
+    pub fn new(collider : &Collider, media : &MediumStack, px_pos: usize, weight : Color, radius2 : f64) -> Self {
         ViewPoint { 
             pos : collider.pos, 
-            norm : collider.norm_vec, 
+            shading : collider.shading(media, Transport::Radiance), 
             dire : collider.in_direction.mult(-1.0), 
             px_pos,
-            color : collider.color.mult(wgt), 
-            radius2: MAX_PH_RADIUS2, 
+            index : 0,
+            sample : 0,
+            weight, 
+            radius2, 
             count : 0.0, 
             delta : 0.0,
             flux_color: Color::default(), 
@@ -44,21 +49,30 @@ impl ViewPoint {
     }
 
     pub fn handle(&mut self, photon : &Photon) {
-        let dist = self.pos.distance2(&photon.ray.o);
-        if dist < self.radius2 {
+        if let Some(flux) = self.contribution(photon) {
             self.delta += 1.0;
-            self.flux_color = self.flux_color + self.color * photon.power
-                .mult(self.material.brdf(&photon.ray.d, &self.norm, &self.dire))
-                .mult(1.0 - dist / self.radius2);
+            self.flux_color += flux;
+        }
+    }
+
+    // 光子落在半径之内时对该视点的通量贡献，不修改视点
+    pub fn contribution(&self, photon : &Photon) -> Option<Color> {
+        let dist = self.pos.distance2(&photon.ray.o);
+        if dist >= self.radius2 {
+            return None;
         }
+        let f = self.material.eval(&self.shading, &self.dire, &photon.ray.d);
+        // 核函数 2 (1 - d² / r²) 在圆内的平均值为 1
+        Some(self.weight * f * photon.power.mult(2.0 * (1.0 - dist / self.radius2)))
     }
 
-    pub fn renew(&mut self) {
+    // alpha : 每轮新增光子中保留的比例
+    pub fn renew(&mut self, alpha : f64) {
         if self.delta > 1e-8 {
-            let k = ( self.count as f64 + self.delta * 0.7) / ( self.count as f64 + self.delta);
+            let k = ( self.count as f64 + self.delta * alpha) / ( self.count as f64 + self.delta);
             self.radius2 *= k;
             self.flux_color = self.flux_color.mult(k);
-            self.count += self.delta * 0.7;
+            self.count += self.delta * alpha;
             self.delta = 0.0;
         }
     }
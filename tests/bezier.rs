@@ -0,0 +1,290 @@
+extern crate ppm;
+extern crate rand;
+
+use ppm::scene::material::Material;
+use ppm::scene::primitive::{BazierCurve, Primitive};
+use ppm::util::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::Arc;
+
+// 与 BazierCurve::new 中相同的剖面控制点
+const PY: [f64; 4] = [0.0, 100.0, 300.0, 0.0];
+const PZ: [f64; 4] = [0.0, 100.0, 300.0, 400.0];
+const STEP: f64 = 0.1; // 沿射线采样的步长
+
+fn bezier(p: &[f64; 4], t: f64) -> f64 {
+    let s = 1.0 - t;
+    p[0] * s * s * s + 3.0 * p[1] * t * s * s + 3.0 * p[2] * t * t * s + p[3] * t * t * t
+}
+
+fn curve(position: Vector3) -> BazierCurve {
+    let material = Arc::new(Material::new(Color::new(1.0, 1.0, 1.0), 1.0, 0.0, 0.0, 1.0));
+    BazierCurve::new(0, position, material)
+}
+
+// 剖面在高度 h 处的半径，高度方向的控制点单调，可以二分求曲线参数
+fn radius_at(h: f64) -> Option<f64> {
+    if !(0.0..=400.0).contains(&h) {
+        return None;
+    }
+    let (mut lo, mut hi) = (0.0, 1.0);
+    for _ in 0..60 {
+        let mid = (lo + hi) * 0.5;
+        if bezier(&PZ, mid) < h {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    Some(bezier(&PY, lo).abs())
+}
+
+// 点在旋转体内部时为正，在外部时为负
+fn signed_depth(position: &Vector3, p: &Vector3) -> f64 {
+    let rho = ((p.x - position.x).powi(2) + (p.y - position.y).powi(2)).sqrt();
+    match radius_at(p.z - position.z) {
+        Some(r) => r - rho,
+        None => -1.0,
+    }
+}
+
+// 沿射线等距采样，找到第一次穿过曲面的位置再二分细化，作为参考答案
+fn sampled_hit(position: &Vector3, ray: &Ray, t_max: f64) -> Option<f64> {
+    let inside = |t: f64| signed_depth(position, &(ray.o + ray.d * t)) > 0.0;
+    let start = inside(1e-4);
+    let mut t = 1e-4;
+    while t < t_max {
+        let next = t + STEP;
+        if inside(next) != start {
+            let (mut lo, mut hi) = (t, next);
+            for _ in 0..60 {
+                let mid = (lo + hi) * 0.5;
+                if inside(mid) == start {
+                    lo = mid;
+                } else {
+                    hi = mid;
+                }
+            }
+            return Some((lo + hi) * 0.5);
+        }
+        t = next;
+    }
+    None
+}
+
+fn random_dir(rng: &mut StdRng) -> Vector3 {
+    loop {
+        let v = Vector3::new(rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0));
+        let n = v.norm();
+        if n > 1e-3 && n <= 1.0 {
+            return v / n;
+        }
+    }
+}
+
+fn check_against_sampling(position: Vector3, rays: Vec<Ray>) {
+    let vase = curve(position);
+    for ray in rays {
+        let expect = sampled_hit(&position, &ray, 3000.0);
+        let got = vase.intersect(&ray).map(|hit| hit.distance);
+        match (expect, got) {
+            (Some(e), Some(g)) => {
+                assert!((e - g).abs() < 1e-3, "ray {:?} -> {:?}: expected {}, got {}", ray.o, ray.d, e, g)
+            }
+            (Some(e), None) => panic!("ray {:?} -> {:?}: expected a hit at {}", ray.o, ray.d, e),
+            (None, Some(g)) => {
+                // 采样步长之内的擦边交点，至少要落在曲面上
+                let p = ray.o + ray.d * g;
+                assert!(signed_depth(&position, &p).abs() < 1e-3, "ray {:?} -> {:?}: spurious hit {}", ray.o, ray.d, g);
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+#[test]
+fn rays_from_outside_match_sampling() {
+    let position = Vector3::new(5000.0, 5000.0, 200.0);
+    let mut rng = StdRng::seed_from_u64(1);
+    let rays = (0..200)
+        .map(|_| {
+            let o = position + Vector3::new(0.0, 0.0, 200.0) + random_dir(&mut rng) * 1000.0;
+            let target = position
+                + Vector3::new(rng.gen_range(-320.0, 320.0), rng.gen_range(-320.0, 320.0), rng.gen_range(-20.0, 420.0));
+            Ray::new(o, (target - o).normalize())
+        })
+        .collect();
+    check_against_sampling(position, rays);
+}
+
+#[test]
+fn rays_from_inside_match_sampling() {
+    let position = Vector3::new(0.0, 0.0, 0.0);
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut rays = Vec::new();
+    while rays.len() < 100 {
+        let o = Vector3::new(rng.gen_range(-250.0, 250.0), rng.gen_range(-250.0, 250.0), rng.gen_range(0.0, 400.0));
+        if signed_depth(&position, &o) > 1.0 {
+            rays.push(Ray::new(o, random_dir(&mut rng)));
+        }
+    }
+    check_against_sampling(position, rays);
+}
+
+#[test]
+fn axis_and_horizontal_rays() {
+    let position = Vector3::new(0.0, 0.0, 0.0);
+    let vase = curve(position);
+    // 平行于旋转轴向下
+    let ray = Ray::new(Vector3::new(50.0, 0.0, 1000.0), Vector3::new(0.0, 0.0, -1.0));
+    let t = vase.intersect(&ray).unwrap().distance;
+    assert!((t - sampled_hit(&position, &ray, 2000.0).unwrap()).abs() < 1e-6);
+    // 水平射线打到最宽处附近
+    let h = 200.0;
+    let r = radius_at(h).unwrap();
+    let t = vase.intersect(&Ray::new(Vector3::new(-1000.0, 0.0, h), Vector3::new(1.0, 0.0, 0.0)));
+    assert!((t.unwrap().distance - (1000.0 - r)).abs() < 1e-6);
+    // 从旁边经过
+    let miss = vase.intersect(&Ray::new(Vector3::new(-1000.0, 400.0, h), Vector3::new(1.0, 0.0, 0.0)));
+    assert!(miss.is_none());
+}
+
+#[test]
+fn intersection_is_deterministic() {
+    let position = Vector3::new(5000.0, 5000.0, 200.0);
+    let vase = curve(position);
+    let ray = Ray::new(Vector3::new(6000.0, 5000.0, 400.0), Vector3::new(-1.0, 0.01, -0.05).normalize());
+    let first = vase.intersect(&ray);
+    assert!(first.is_some());
+    for _ in 0..100 {
+        assert_eq!(vase.intersect(&ray), first);
+    }
+}
+
+#[test]
+fn hit_normals_are_perpendicular_to_surface() {
+    let position = Vector3::new(0.0, 0.0, 0.0);
+    let vase = curve(position);
+    let mut rng = StdRng::seed_from_u64(3);
+    let mut checked = 0;
+    while checked < 100 {
+        let o = Vector3::new(0.0, 0.0, 200.0) + random_dir(&mut rng) * 1000.0;
+        let ray = Ray::new(o, (Vector3::new(0.0, 0.0, 200.0) - o).normalize());
+        let hit = match vase.intersect(&ray) {
+            Some(hit) => hit,
+            None => continue,
+        };
+        let p = ray.o + ray.d * hit.distance;
+        // 在内外分界函数上做中心差分得到梯度
+        let e = 1e-3;
+        let grad = Vector3::new(
+            signed_depth(&position, &(p + Vector3::new(e, 0.0, 0.0))) - signed_depth(&position, &(p - Vector3::new(e, 0.0, 0.0))),
+            signed_depth(&position, &(p + Vector3::new(0.0, e, 0.0))) - signed_depth(&position, &(p - Vector3::new(0.0, e, 0.0))),
+            signed_depth(&position, &(p + Vector3::new(0.0, 0.0, e))) - signed_depth(&position, &(p - Vector3::new(0.0, 0.0, e))),
+        );
+        if grad.norm() < 1e-9 || p.z < 1.0 || p.z > 399.0 {
+            continue; // 两端的尖点处法向量没有定义
+        }
+        let n = hit.normal;
+        assert!((n.norm() - 1.0).abs() < 1e-9);
+        assert!(n.dot(&grad.normalize()).abs() > 0.999, "normal {:?} at {:?}", n, p);
+        checked += 1;
+    }
+}
+
+// de Casteljau 从中点拆分，拆成两段之后曲面不变
+fn split_profile() -> Vec<(f64, f64)> {
+    let half = |p: &[f64; 4]| {
+        let p01 = (p[0] + p[1]) * 0.5;
+        let p12 = (p[1] + p[2]) * 0.5;
+        let p23 = (p[2] + p[3]) * 0.5;
+        let p012 = (p01 + p12) * 0.5;
+        let p123 = (p12 + p23) * 0.5;
+        [p[0], p01, p012, (p012 + p123) * 0.5, p123, p23, p[3]]
+    };
+    let (r, z) = (half(&PY), half(&PZ));
+    r.iter().cloned().zip(z.iter().cloned()).collect()
+}
+
+#[test]
+fn piecewise_profile_matches_single_segment() {
+    let position = Vector3::new(0.0, 0.0, 0.0);
+    let vase = curve(position);
+    let mut split = curve(position);
+    split.set_profile(&split_profile());
+    let mut rng = StdRng::seed_from_u64(4);
+    for _ in 0..200 {
+        let o = Vector3::new(0.0, 0.0, 200.0) + random_dir(&mut rng) * 1000.0;
+        let target = Vector3::new(rng.gen_range(-320.0, 320.0), rng.gen_range(-320.0, 320.0), rng.gen_range(-20.0, 420.0));
+        let ray = Ray::new(o, (target - o).normalize());
+        match (vase.intersect(&ray), split.intersect(&ray)) {
+            (Some(a), Some(b)) => {
+                assert!((a.distance - b.distance).abs() < 1e-6);
+                assert!(a.normal.dot(&b.normal) > 1.0 - 1e-9);
+            }
+            (None, None) => {}
+            (a, b) => panic!("ray {:?} -> {:?}: {:?} vs {:?}", ray.o, ray.d, a, b),
+        }
+    }
+}
+
+#[test]
+fn axis_and_scale_transform_the_surface() {
+    // 绕 x 轴放倒并放大两倍，等价于对射线做相反的变换
+    let position = Vector3::new(100.0, -50.0, 30.0);
+    let mut lying = curve(position);
+    lying.set_axis(Vector3::new(1.0, 0.0, 0.0));
+    lying.set_scale(2.0);
+    let upright = curve(Vector3::new(0.0, 0.0, 0.0));
+    let to_upright = |v: Vector3| Vector3::new(v.y, v.z, v.x);
+    let mut rng = StdRng::seed_from_u64(5);
+    let mut hits = 0;
+    for _ in 0..200 {
+        let center = position + Vector3::new(400.0, 0.0, 0.0);
+        let o = center + random_dir(&mut rng) * 2000.0;
+        let target = center + random_dir(&mut rng) * 500.0;
+        let ray = Ray::new(o, (target - o).normalize());
+        let local = Ray::new(to_upright(ray.o - position) / 2.0, to_upright(ray.d) / 2.0);
+        match (lying.intersect(&ray), upright.intersect(&local)) {
+            (Some(a), Some(b)) => {
+                assert!((a.distance - b.distance).abs() < 1e-6);
+                assert!(to_upright(a.normal).dot(&b.normal).abs() > 1.0 - 1e-9);
+                hits += 1;
+            }
+            (None, None) => {}
+            (a, b) => panic!("ray {:?} -> {:?}: {:?} vs {:?}", ray.o, ray.d, a, b),
+        }
+    }
+    assert!(hits > 20);
+}
+
+#[test]
+#[should_panic(expected = "scale must be positive")]
+fn negative_scale_is_rejected() {
+    curve(Vector3::new(0.0, 0.0, 0.0)).set_scale(-1.0);
+}
+
+#[test]
+fn normals_point_away_from_the_axis() {
+    // 默认的逆时针剖面、反向给出的顺时针剖面、半径取负的镜像剖面，法向量都指向外侧
+    let default: Vec<(f64, f64)> = PY.iter().cloned().zip(PZ.iter().cloned()).collect();
+    let reversed: Vec<(f64, f64)> = default.iter().rev().cloned().collect();
+    let mirrored: Vec<(f64, f64)> = default.iter().map(|p| (-p.0, p.1)).collect();
+    for profile in [default, reversed, mirrored] {
+        let mut vase = curve(Vector3::new(0.0, 0.0, 0.0));
+        vase.set_profile(&profile);
+        for k in 0..8 {
+            let angle = k as f64 * std::f64::consts::PI / 4.0;
+            let radial = Vector3::new(angle.cos(), angle.sin(), 0.0);
+            let center = Vector3::new(0.0, 0.0, 200.0);
+            // 从外面射向旋转轴，以及从轴上向外射出，两者都击中 z = 200 处的侧面
+            for ray in [Ray::new(center + radial * 1000.0, -radial), Ray::new(center, radial)] {
+                let hit = vase.intersect(&ray).expect("the side at z = 200 is hit");
+                let r = radius_at(200.0).unwrap();
+                assert!((hit.pos - (center + radial * r)).norm() < 1e-6, "{:?}: {:?}", profile, hit.pos);
+                assert!(hit.normal.dot(&radial) > 0.5, "{:?}: normal {:?} at {:?}", profile, hit.normal, hit.pos);
+            }
+        }
+    }
+}
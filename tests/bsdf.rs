@@ -0,0 +1,138 @@
+extern crate ppm;
+extern crate rand;
+
+use ppm::scene::bsdf::*;
+use ppm::util::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::f64::consts::PI;
+
+const N: usize = 20_000;
+
+fn shading(eta: f64, mode: Transport) -> Shading {
+    Shading {
+        normal: Vector3::new(0.0, 0.0, 1.0),
+        color: Color::new(1.0, 1.0, 1.0),
+        eta,
+        mode,
+    }
+}
+
+// 与法向量夹角为 theta 的出射方向
+fn dir(theta: f64) -> Vector3 {
+    Vector3::new(theta.sin(), 0.0, theta.cos())
+}
+
+// 所有非 delta 的分量，以及测试时使用的折射率之比
+fn rough_lobes() -> Vec<(&'static str, Box<dyn Bxdf>, f64)> {
+    vec![
+        ("lambertian", Box::new(Lambertian), 1.0),
+        ("ggx 0.3", Box::new(GgxConductor::new(0.3)), 1.0),
+        ("ggx 0.8", Box::new(GgxConductor::new(0.8)), 1.0),
+        ("rough glass 0.3", Box::new(RoughDielectric::new(0.3)), 1.5),
+        ("rough glass 0.6 inside", Box::new(RoughDielectric::new(0.6)), 1.0 / 1.5),
+    ]
+}
+
+fn close(a: f64, b: f64, tol: f64) -> bool {
+    (a - b).abs() <= tol * (1.0 + a.abs().max(b.abs()))
+}
+
+#[test]
+fn sampled_weight_matches_eval_and_pdf() {
+    let mut rng = StdRng::seed_from_u64(1);
+    for (name, lobe, eta) in rough_lobes() {
+        for mode in [Transport::Radiance, Transport::Importance] {
+            let sh = shading(eta, mode);
+            for theta in [0.1, 0.7, 1.3] {
+                let wo = dir(theta);
+                for _ in 0..2000 {
+                    let sample = match lobe.sample(&sh, &wo, rng.gen(), (rng.gen(), rng.gen())) {
+                        Some(sample) => sample,
+                        None => continue,
+                    };
+                    assert!(!sample.delta);
+                    assert_eq!(sample.transmitted, sample.dir.z < 0.0, "{}", name);
+                    let pdf = lobe.pdf(&sh, &wo, &sample.dir);
+                    assert!(close(sample.pdf, pdf, 1e-6), "{}: pdf {} vs {}", name, sample.pdf, pdf);
+                    let f = lobe.eval(&sh, &wo, &sample.dir);
+                    let expect = f.mult(sample.dir.z.abs() / pdf);
+                    assert!(close(sample.weight.r, expect.r, 1e-6), "{}: weight {:?} vs {:?}", name, sample.weight, expect);
+                }
+            }
+        }
+    }
+}
+
+// 在球面上按 (cos θ, φ) 的网格积分，每个格子的立体角相同
+fn integrate_sphere(f: impl Fn(&Vector3) -> f64) -> f64 {
+    let (nz, nphi) = (800, 800);
+    let mut sum = 0.0;
+    for i in 0..nz {
+        let z = 1.0 - 2.0 * (i as f64 + 0.5) / nz as f64;
+        let r = (1.0 - z * z).sqrt();
+        for j in 0..nphi {
+            let phi = 2.0 * PI * (j as f64 + 0.5) / nphi as f64;
+            sum += f(&Vector3::new(r * phi.cos(), r * phi.sin(), z));
+        }
+    }
+    sum * 4.0 * PI / (nz * nphi) as f64
+}
+
+// 微表面采样得到的方向可能落到错误的一侧而被丢弃，pdf 的积分等于 sample 成功的概率，不超过 1
+#[test]
+fn pdf_integrates_to_sampling_probability() {
+    let mut rng = StdRng::seed_from_u64(4);
+    for (name, lobe, eta) in rough_lobes() {
+        let sh = shading(eta, Transport::Radiance);
+        let wo = dir(0.5);
+        let total = integrate_sphere(|wi| lobe.pdf(&sh, &wo, wi));
+        let hits = (0..N).filter(|_| lobe.sample(&sh, &wo, rng.gen(), (rng.gen(), rng.gen())).is_some()).count();
+        let rate = hits as f64 / N as f64;
+        assert!(total < 1.01, "{}: pdf integrates to {}", name, total);
+        assert!((total - rate).abs() < 0.02, "{}: pdf integrates to {} but {} of the samples succeed", name, total, rate);
+    }
+    let lambertian = integrate_sphere(|wi| Lambertian.pdf(&shading(1.0, Transport::Radiance), &dir(0.5), wi));
+    assert!((lambertian - 1.0).abs() < 1e-3);
+}
+
+#[test]
+fn reflection_is_reciprocal() {
+    let mut rng = StdRng::seed_from_u64(2);
+    for (name, lobe, eta) in rough_lobes() {
+        let sh = shading(eta, Transport::Radiance);
+        for _ in 0..1000 {
+            let a = sampling::uniform_hemisphere((rng.gen(), rng.gen()));
+            let b = sampling::uniform_hemisphere((rng.gen(), rng.gen()));
+            let (ab, ba) = (lobe.eval(&sh, &a, &b), lobe.eval(&sh, &b, &a));
+            assert!(close(ab.r, ba.r, 1e-9), "{}: f(a, b) = {:?}, f(b, a) = {:?}", name, ab, ba);
+        }
+    }
+}
+
+// 白色表面在各个方向上的反照率不超过 1；光子模式下折射不缩放，按能量计算
+#[test]
+fn white_furnace() {
+    let mut lobes = rough_lobes();
+    lobes.push(("mirror", Box::new(Mirror), 1.0));
+    lobes.push(("glass", Box::new(Glass), 1.5));
+    lobes.push(("glass inside", Box::new(Glass), 1.0 / 1.5));
+    let mut rng = StdRng::seed_from_u64(3);
+    for (name, lobe, eta) in lobes {
+        let sh = shading(eta, Transport::Importance);
+        for theta in [0.0, 0.6, 1.2, 1.5] {
+            let wo = dir(theta);
+            let mut albedo = 0.0;
+            for _ in 0..N {
+                if let Some(sample) = lobe.sample(&sh, &wo, rng.gen(), (rng.gen(), rng.gen())) {
+                    albedo += sample.weight.r;
+                }
+            }
+            albedo /= N as f64;
+            assert!(albedo <= 1.02, "{} at {}: albedo {}", name, theta, albedo);
+            if name == "lambertian" || name.starts_with("glass") || name == "mirror" {
+                assert!(albedo > 0.97, "{} at {}: albedo {}", name, theta, albedo);
+            }
+        }
+    }
+}
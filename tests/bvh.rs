@@ -0,0 +1,146 @@
+extern crate ppm;
+extern crate rand;
+
+use ppm::scene::material::Material;
+use ppm::scene::primitive::*;
+use ppm::scene::Scene;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+// 与 Scene::visible 相同的阴影射线留白
+const SHADOW_EPS: f64 = 1e-3;
+
+fn point(rng: &mut StdRng, size: f64) -> Vector3 {
+    Vector3::new(rng.gen::<f64>() * size, rng.gen::<f64>() * size, rng.gen::<f64>() * size)
+}
+
+fn direction(rng: &mut StdRng) -> Vector3 {
+    sampling::uniform_sphere((rng.gen(), rng.gen()))
+}
+
+// 随机的三角形与球组成的场景，同一个种子得到相同的物体
+fn soup(seed: u64) -> Vec<Box<dyn Primitive + Send + Sync>> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let material = Arc::new(Material::new(Color::new(0.5, 0.5, 0.5), 1.0, 0.0, 0.0, 1.0));
+    let mut objects: Vec<Box<dyn Primitive + Send + Sync>> = Vec::new();
+    for id in 0..400 {
+        let a = point(&mut rng, 100.0);
+        let size = if rng.gen::<f64>() < 0.1 { 40.0 } else { 8.0 };
+        let b = a + direction(&mut rng).mult(size * rng.gen::<f64>());
+        let c = a + direction(&mut rng).mult(size * rng.gen::<f64>());
+        objects.push(Box::new(Triangle::new(id, a, b, c, material.clone())));
+    }
+    for id in 400..500 {
+        let radius = 0.5 + 4.5 * rng.gen::<f64>();
+        objects.push(Box::new(Sphere::new(id, radius, point(&mut rng, 100.0), material.clone())));
+    }
+    objects
+}
+
+fn scene(seed: u64) -> Scene {
+    let mut scene = Scene::new();
+    for obj in soup(seed) {
+        scene.add_object(obj);
+    }
+    scene.build_bvh();
+    scene
+}
+
+// 逐个求交得到的最近交点 (物体 hash, 距离)
+fn linear_nearest(objects: &[Box<dyn Primitive + Send + Sync>], ray: &Ray) -> Option<(u64, f64)> {
+    let mut nearest: Option<(u64, f64)> = None;
+    for obj in objects {
+        if let Some(hit) = obj.intersect(ray) {
+            if nearest.is_none_or(|(_, t)| hit.distance < t) {
+                nearest = Some((obj.get_hash(), hit.distance));
+            }
+        }
+    }
+    nearest
+}
+
+fn random_ray(rng: &mut StdRng) -> Ray {
+    // 起点既有场景内部的，也有场景外部的
+    let o = point(rng, 160.0) - Vector3::new(30.0, 30.0, 30.0);
+    Ray::new(o, direction(rng))
+}
+
+#[test]
+fn nearest_hit_matches_linear_search() {
+    for seed in 0..3 {
+        let scene = scene(seed);
+        let objects = soup(seed);
+        let mut rng = StdRng::seed_from_u64(100 + seed);
+        let mut hits = 0;
+        for _ in 0..5000 {
+            let ray = random_ray(&mut rng);
+            let expect = linear_nearest(&objects, &ray);
+            let got = scene.intersect(&ray).map(|c| (c.hash_value, c.distance));
+            assert_eq!(got, expect, "ray {:?} {:?}", ray.o, ray.d);
+            hits += expect.is_some() as usize;
+        }
+        // 射线应当有相当一部分击中物体，否则测试没有意义
+        assert!(hits > 400, "only {} rays hit the soup", hits);
+    }
+}
+
+#[test]
+fn visibility_matches_linear_search() {
+    for seed in 0..3 {
+        let scene = scene(seed);
+        let objects = soup(seed);
+        let mut rng = StdRng::seed_from_u64(200 + seed);
+        let (mut blocked, mut clear) = (0, 0);
+        for _ in 0..5000 {
+            let from = point(&mut rng, 100.0);
+            let to = point(&mut rng, 100.0);
+            let dist = from.distance(&to);
+            let dir = (to - from).mult(1.0 / dist);
+            let ray = Ray::new(from + dir.mult(SHADOW_EPS), dir);
+            let expect = objects
+                .iter()
+                .all(|obj| obj.intersect(&ray).is_none_or(|hit| hit.distance >= dist - 2.0 * SHADOW_EPS));
+            assert_eq!(scene.visible(&from, &to), expect, "{:?} -> {:?}", from, to);
+            if expect {
+                clear += 1;
+            } else {
+                blocked += 1;
+            }
+        }
+        assert!(blocked > 500 && clear > 500, "{} blocked, {} clear", blocked, clear);
+    }
+}
+
+fn same_box(a: &AABB, b: &AABB) -> bool {
+    (a.min - b.min).norm() < 1e-12 && (a.max - b.max).norm() < 1e-12
+}
+
+#[test]
+fn bounds_union_bounded_objects_and_skip_planes() {
+    let material = Arc::new(Material::new(Color::new(0.5, 0.5, 0.5), 1.0, 0.0, 0.0, 1.0));
+    let mut mixed = Scene::new();
+    assert!(mixed.bounds().is_empty() && mixed.is_bounded());
+
+    mixed.add_object(Box::new(Sphere::new(0, 2.0, Vector3::new(10.0, 0.0, 0.0), material.clone())));
+    let (a, b, c) = (Vector3::new(-5.0, 1.0, 1.0), Vector3::new(0.0, -3.0, 2.0), Vector3::new(1.0, 1.0, 7.0));
+    mixed.add_object(Box::new(Triangle::new(1, a, b, c, material.clone())));
+    let expect = AABB::new(Vector3::new(-5.0, -3.0, -2.0), Vector3::new(12.0, 2.0, 7.0));
+    assert!(same_box(&mixed.bounds(), &expect), "{:?}", mixed.bounds());
+    assert!(mixed.is_bounded());
+
+    // 无限大的平面使场景无界，但不影响有界物体的范围
+    mixed.add_object(Box::new(Plane::new(2, Vector3::new(0.0, 0.0, 1.0), -100.0, material.clone())));
+    mixed.build_bvh();
+    assert!(!mixed.is_bounded());
+    assert!(same_box(&mixed.bounds(), &expect), "{:?}", mixed.bounds());
+
+    let mut planes = Scene::new();
+    planes.add_object(Box::new(Plane::new(0, Vector3::new(1.0, 0.0, 0.0), 3.0, material)));
+    assert!(!planes.is_bounded() && planes.bounds().is_empty());
+
+    // 随机场景的范围等于各物体包围盒的并
+    let expect = soup(6).iter().fold(AABB::empty(), |b, obj| b.union(&obj.bounding_box().unwrap()));
+    let soup_scene = scene(6);
+    assert!(soup_scene.is_bounded());
+    assert!(same_box(&soup_scene.bounds(), &expect));
+}
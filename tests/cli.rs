@@ -0,0 +1,43 @@
+use std::process::{Command, Output};
+
+fn ppm(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_ppm")).args(args).output().unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn help_exits_successfully() {
+    let output = ppm(&["--help"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("Usage: ppm"));
+}
+
+#[test]
+fn bad_arguments_exit_with_2() {
+    // 参数错误时打印错误信息和用法
+    let cases = [
+        (vec!["--bogus"], "error: unknown option `--bogus`"),
+        (vec!["-o", "result.jpg"], "error: unsupported output format `result.jpg`"),
+        (vec!["-p", "2", "-t", "4"], "error: photons per round must be at least the thread count"),
+        (vec!["--radius", "inf"], "error: radius must be positive and finite"),
+    ];
+    for (args, message) in cases.iter() {
+        let output = ppm(args);
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        let err = stderr(&output);
+        assert!(err.starts_with(message) && err.contains("Usage: ppm"), "{:?}: {}", args, err);
+    }
+}
+
+#[test]
+fn run_failures_exit_with_1() {
+    let path = std::env::temp_dir().join(format!("ppm-cli-missing-{}.txt", std::process::id()));
+    let output = ppm(&["--scene", path.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    let err = stderr(&output);
+    assert!(err.starts_with(&format!("error: {}", path.display())), "{}", err);
+    assert!(!err.contains("Usage"), "{}", err);
+}
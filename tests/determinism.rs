@@ -0,0 +1,64 @@
+extern crate ppm;
+
+use ppm::core::{new_integrator, RenderMode, RenderSettings};
+use ppm::scene::Scene;
+use std::path::Path;
+use std::sync::Arc;
+
+// 包含漫反射、玻璃和粗糙金属的小场景，覆盖所有需要随机数的地方
+const SCENE: &str = "
+camera position 6000 5000 400  direction -1 0 0  size 40 30
+
+material wall   color 0.75 0.75 0.75  diffuse 1.0
+material red    color 0.75 0.25 0.25  diffuse 1.0
+material glass  color 0.95 0.95 0.95  diffuse 0  refraction 1.0  rindex 1.5
+material metal  color 0.9 0.8 0.5     diffuse 0  specular 1.0  roughness 0.3
+
+plane normal 0 1 0  distance 5500  material red
+plane normal 0 1 0  distance 4500  material wall
+plane normal 0 0 1  distance 800   material wall
+plane normal 0 0 1  distance 100   material wall
+plane normal 1 0 0  distance 4500  material wall
+plane normal 1 0 0  distance 6100  material wall
+
+sphere center 5100 4800 260  radius 160  material glass
+sphere center 5000 5250 200  radius 100  material metal
+
+area_light position 4900 4900 800  dx 1 0 0  dy 0 1 0  normal 0 0 -1  color 25 25 25  width 200  height 200
+";
+
+fn render(mode: RenderMode, seed: u64, threads: usize) -> Vec<(f64, f64, f64)> {
+    let scene = Scene::parse(SCENE, Path::new(".")).unwrap();
+    let camera = scene.get_camera().cloned().unwrap();
+    let settings = RenderSettings {
+        mode,
+        rounds: 2,
+        photons_per_pass: 3000,
+        threads,
+        samples_per_pixel: 2,
+        seed,
+        ..Default::default()
+    };
+    let framebuffer = new_integrator(settings).render(Arc::new(camera), Arc::new(scene));
+    framebuffer.pixels.iter().map(|c| (c.r, c.g, c.b)).collect()
+}
+
+#[test]
+fn same_seed_gives_identical_images() {
+    for mode in [RenderMode::Ppm, RenderMode::Sppm, RenderMode::Pt, RenderMode::Rt] {
+        let first = render(mode, 7, 3);
+        assert!(first.iter().any(|p| p.0 > 0.0), "{:?} rendered a black image", mode);
+        // 比较位模式而不是数值，NaN 也必须一致
+        let bits = |img: &[(f64, f64, f64)]| -> Vec<u64> {
+            img.iter().flat_map(|p| [p.0.to_bits(), p.1.to_bits(), p.2.to_bits()]).collect()
+        };
+        assert_eq!(bits(&first), bits(&render(mode, 7, 3)), "{:?} is not deterministic", mode);
+    }
+}
+
+#[test]
+fn different_seeds_give_different_images() {
+    for mode in [RenderMode::Sppm, RenderMode::Pt] {
+        assert_ne!(render(mode, 1, 2), render(mode, 2, 2), "{:?} ignores the seed", mode);
+    }
+}
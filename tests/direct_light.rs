@@ -0,0 +1,77 @@
+extern crate ppm;
+extern crate rand;
+
+use ppm::core::{new_integrator, RenderMode, RenderSettings};
+use ppm::scene::bsdf::Transport;
+use ppm::scene::Scene;
+use ppm::util::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::path::Path;
+use std::sync::Arc;
+
+const N: usize = 40_000;
+
+// 漫反射地面上方的矩形光源，发光面的法向量由 normal 给出；相机朝下，所有视线都落在地面上
+fn scene(normal: &str) -> Scene {
+    let text = format!(
+        "
+camera position 150 0 40  direction -0.5 0 -1  size 1 1
+material floor  color 0.8 0.8 0.8  diffuse 1.0
+plane normal 0 0 1  distance 0  material floor
+area_light position -100 -100 60  dx 1 0 0  dy 0 1 0  normal {}  color 5 5 5  width 200  height 200
+",
+        normal
+    );
+    Scene::parse(&text, Path::new(".")).unwrap()
+}
+
+// 对 1 × 1 的图像做 N 次采样的路径追踪，直接光照由光源采样与 BSDF 采样经 MIS 合并
+fn path_traced(scene: Scene) -> Color {
+    let camera = scene.get_camera().cloned().unwrap();
+    let settings = RenderSettings {
+        mode: RenderMode::Pt,
+        threads: 1,
+        samples_per_pixel: N,
+        ..Default::default()
+    };
+    new_integrator(settings).render(Arc::new(camera), Arc::new(scene)).get(0, 0)
+}
+
+// 只对 BSDF 采样：从地面出发的方向击中光源时计入光源的辐射亮度
+fn bsdf_only(scene: &Scene) -> Color {
+    let camera = scene.get_camera().unwrap();
+    let mut rng = StdRng::seed_from_u64(3);
+    let mut sum = Color::default();
+    for _ in 0..N {
+        let ray = camera.super_emitting(0, 0, rng.gen::<f64>() - 0.5, rng.gen::<f64>() - 0.5);
+        let collider = match scene.intersect(&ray) {
+            Some(collider) => collider,
+            None => continue,
+        };
+        let shading = collider.shading(&MediumStack::new(), Transport::Radiance);
+        let wo = ray.d.mult(-1.0);
+        if let Some(sample) = collider.material.sample(&shading, &wo, rng.gen(), (rng.gen(), rng.gen())) {
+            let next = Ray::new(collider.pos + sample.dir.mult(1e-4), sample.dir);
+            if let Some(lgt) = scene.intersect_light(&next) {
+                sum += sample.weight * lgt.power;
+            }
+        }
+    }
+    sum.mult(1.0 / N as f64)
+}
+
+#[test]
+fn mis_agrees_with_bsdf_sampling() {
+    let expect = bsdf_only(&scene("0 0 -1"));
+    let got = path_traced(scene("0 0 -1"));
+    assert!(expect.r > 0.1, "the floor is not lit: {:?}", expect);
+    assert!((got.r - expect.r).abs() < 0.03 * expect.r, "{:?} vs {:?}", got, expect);
+}
+
+// 光源朝上时地面只能看到它的背面，所有采样策略都不应得到光照
+#[test]
+fn back_of_area_light_emits_nothing() {
+    assert!(bsdf_only(&scene("0 0 1")).is_zero_vec());
+    assert!(path_traced(scene("0 0 1")).is_zero_vec());
+}
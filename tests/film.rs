@@ -0,0 +1,201 @@
+extern crate ppm;
+
+use ppm::core::{new_integrator, RenderMode, RenderSettings};
+use ppm::scene::Scene;
+use ppm::util::*;
+use std::path::Path;
+use std::sync::Arc;
+
+const FILTERS: [FilterKind; 4] = [FilterKind::Box, FilterKind::Tent, FilterKind::Gaussian, FilterKind::Mitchell];
+
+// 每个像素内 n * n 个分层的样本
+fn stratified(width: usize, height: usize, n: usize) -> Vec<(f64, f64)> {
+    let mut points = Vec::new();
+    for j in 0..height {
+        for i in 0..width {
+            for k in 0..n * n {
+                let u = ((k % n) as f64 + 0.5) / n as f64;
+                let v = ((k / n) as f64 + 0.5) / n as f64;
+                points.push((i as f64 + u - 0.5, j as f64 + v - 0.5));
+            }
+        }
+    }
+    points
+}
+
+#[test]
+fn box_filter_averages_samples_inside_each_pixel() {
+    let mut film = Film::new(3, 2, Filter::default());
+    // 落在像素边界上的样本属于右侧和下方的像素
+    film.add_sample(0.0, 0.0, Color::new(1.0, 0.0, 0.0));
+    film.add_sample(0.49, -0.5, Color::new(3.0, 0.0, 0.0));
+    film.add_sample(1.5, 0.2, Color::new(0.0, 2.0, 0.0));
+    film.add_sample(1.9, 1.2, Color::new(0.0, 0.0, 4.0));
+    assert_eq!(film.sample_count(0, 0), 2);
+    assert_eq!(film.sample_count(1, 0), 0);
+    assert_eq!(film.sample_count(2, 0), 1);
+    assert_eq!(film.weight(0, 0), 2.0);
+    let fb = film.to_framebuffer();
+    assert_eq!(fb.get(0, 0).r, 2.0);
+    assert_eq!(fb.get(2, 0).g, 2.0);
+    assert_eq!(fb.get(2, 1).b, 4.0);
+    assert!(fb.get(1, 0).is_zero_vec() && fb.get(0, 1).is_zero_vec());
+}
+
+#[test]
+fn filters_vanish_at_their_radius() {
+    for kind in FILTERS {
+        let filter = Filter::new(kind);
+        assert!(filter.eval(0.0, 0.0) > 0.0, "{:?}", kind);
+        assert_eq!(filter.eval(filter.radius + 1e-9, 0.0), 0.0, "{:?}", kind);
+        if kind != FilterKind::Box {
+            assert!(filter.eval(filter.radius, 0.0).abs() < 1e-9, "{:?}", kind);
+            assert!(filter.eval(0.3, 0.0) < filter.eval(0.0, 0.0), "{:?}", kind);
+        }
+        assert_eq!(filter.eval(0.4, -0.2), filter.eval(-0.4, 0.2));
+    }
+    // Mitchell 滤波器在半径的 3/4 附近有负的旁瓣
+    let mitchell = Filter::new(FilterKind::Mitchell);
+    assert!(mitchell.eval(1.5, 0.0) < 0.0);
+}
+
+#[test]
+fn constant_radiance_stays_constant_for_every_filter() {
+    let (width, height) = (7, 5);
+    for kind in FILTERS {
+        let mut film = Film::new(width, height, Filter::new(kind));
+        for (x, y) in stratified(width, height, 4) {
+            film.add_sample(x, y, Color::new(0.5, 1.0, 2.0));
+        }
+        for (idx, c) in film.to_framebuffer().pixels.iter().enumerate() {
+            let ok = (c.r - 0.5).abs() < 1e-9 && (c.g - 1.0).abs() < 1e-9 && (c.b - 2.0).abs() < 1e-9;
+            assert!(ok, "{:?} gives {:?} at pixel {}", kind, c, idx);
+        }
+    }
+}
+
+#[test]
+fn merging_films_matches_a_single_film() {
+    let filter = Filter::new(FilterKind::Gaussian);
+    let mut whole = Film::new(6, 4, filter);
+    let mut parts = [Film::new(6, 4, filter), Film::new(6, 4, filter)];
+    for (k, (x, y)) in stratified(6, 4, 2).into_iter().enumerate() {
+        let c = Color::new(x, y, k as f64);
+        whole.add_sample(x, y, c);
+        parts[k % 2].add_sample(x, y, c);
+    }
+    let mut merged = parts[0].clone();
+    merged.merge(&parts[1]);
+    for j in 0..4 {
+        for i in 0..6 {
+            assert_eq!(merged.sample_count(i, j), 4);
+            assert!((merged.weight(i, j) - whole.weight(i, j)).abs() < 1e-12);
+        }
+    }
+    for (a, b) in merged.to_framebuffer().pixels.iter().zip(whole.to_framebuffer().pixels.iter()) {
+        assert!((a.r - b.r).abs() < 1e-9 && (a.g - b.g).abs() < 1e-9 && (a.b - b.b).abs() < 1e-9);
+    }
+}
+
+// 只有一面自发光的墙：相机看到的每个像素都是光源的辐射亮度
+const LIGHT_WALL: &str = "
+camera position 6000 5000 400  direction -1 0 0  size 16 12
+area_light position 5000 4000 -600  dx 0 1 0  dy 0 0 1  normal 1 0 0  color 0.6 0.4 0.2  width 2000 height 2000
+";
+
+#[test]
+fn every_integrator_produces_a_normalised_image() {
+    let scene = Scene::parse(LIGHT_WALL, Path::new(".")).unwrap();
+    let camera = Arc::new(scene.get_camera().cloned().unwrap());
+    let scene = Arc::new(scene);
+    for mode in [RenderMode::Ppm, RenderMode::Sppm, RenderMode::Pt] {
+        for kind in FILTERS {
+            let settings = RenderSettings {
+                mode,
+                rounds: 3,
+                photons_per_pass: 100,
+                threads: 3,
+                samples_per_pixel: 4,
+                filter: Filter::new(kind),
+                ..Default::default()
+            };
+            let framebuffer = new_integrator(settings).render(camera.clone(), scene.clone());
+            for c in framebuffer.pixels.iter() {
+                let ok = (c.r - 0.6).abs() < 1e-9 && (c.g - 0.4).abs() < 1e-9 && (c.b - 0.2).abs() < 1e-9;
+                assert!(ok, "{:?} with {:?} gives {:?}", mode, kind, c);
+            }
+        }
+    }
+}
+
+#[test]
+fn splats_add_color_to_existing_samples() {
+    let filter = Filter::new(FilterKind::Mitchell);
+    let (mut split, mut whole) = (Film::new(5, 4, filter), Film::new(5, 4, filter));
+    for (k, (x, y)) in stratified(5, 4, 2).into_iter().enumerate() {
+        let (direct, later) = (Color::new(k as f64, 1.0, 0.0), Color::new(0.0, x, y));
+        split.add_sample(x, y, direct);
+        whole.add_sample(x, y, direct + later);
+        split.add_splat(x, y, later);
+    }
+    for (a, b) in split.to_framebuffer().pixels.iter().zip(whole.to_framebuffer().pixels.iter()) {
+        assert!((a.r - b.r).abs() < 1e-9 && (a.g - b.g).abs() < 1e-9 && (a.b - b.b).abs() < 1e-9);
+    }
+    for j in 0..4 {
+        for i in 0..5 {
+            assert_eq!(split.sample_count(i, j), whole.sample_count(i, j));
+            assert!((split.weight(i, j) - whole.weight(i, j)).abs() < 1e-12);
+        }
+    }
+}
+
+#[test]
+fn weighted_sub_samples_count_as_one_sample() {
+    let filter = Filter::new(FilterKind::Tent);
+    let mut film = Film::new(3, 1, filter);
+    // 像素 0 有 10 个子样本，像素 1 只有一个样本
+    for k in 0..10 {
+        film.add_weighted_sample(-0.45 + 0.1 * k as f64, 0.0, Color::new(1.0, 0.0, 0.0), 0.1);
+    }
+    film.add_sample(1.0, 0.0, Color::new(0.0, 1.0, 0.0));
+    assert_eq!(film.sample_count(0, 0), 10);
+    let expect: f64 = (0..10).map(|k| 0.1 * filter.eval(-0.45 + 0.1 * k as f64, 0.0)).sum();
+    assert!((film.weight(0, 0) - expect).abs() < 1e-9);
+    // 相邻的像素 1 上，这 10 个子样本的权重之和只相当于一个样本
+    let near: f64 = (0..10).map(|k| 0.1 * filter.eval(-0.45 + 0.1 * k as f64 - 1.0, 0.0)).sum();
+    let c = film.to_framebuffer().get(1, 0);
+    assert!((c.r - near / (near + 1.0)).abs() < 1e-9 && (c.g - 1.0 / (near + 1.0)).abs() < 1e-9, "{:?}", c);
+}
+
+// 只被光子照亮的漫反射三角形遮住一部分画面，点光源本身不可见
+const HALF_LIT: &str = "
+camera position 0 0 0  direction 1 0 0  size 12 12
+material white  color 1 1 1  diffuse 1
+triangle  v0 10 0 -100  v1 10 0 100  v2 10 100 0  material white
+point_light position 5 2 0  color 2000 2000 2000
+";
+
+fn sppm_image(filter: Filter) -> Framebuffer {
+    let scene = Scene::parse(HALF_LIT, Path::new(".")).unwrap();
+    let camera = Arc::new(scene.get_camera().cloned().unwrap());
+    let settings = RenderSettings {
+        mode: RenderMode::Sppm,
+        rounds: 4,
+        photons_per_pass: 20000,
+        threads: 2,
+        filter,
+        ..Default::default()
+    };
+    new_integrator(settings).render(camera, Arc::new(scene))
+}
+
+#[test]
+fn sppm_photon_estimate_is_filtered() {
+    let lit = |fb: &Framebuffer| fb.pixels.iter().filter(|c| !c.is_zero_vec()).count();
+    let sharp = sppm_image(Filter::default());
+    let wide = sppm_image(Filter::new(FilterKind::Gaussian));
+    let total = sharp.pixels.len();
+    assert!(lit(&sharp) > total / 4 && lit(&sharp) < total * 3 / 4, "{} of {} pixels are lit", lit(&sharp), total);
+    // 光子估计经过滤波后会扩散到三角形之外相邻的像素上
+    assert!(lit(&wide) > lit(&sharp), "{} vs {} lit pixels", lit(&wide), lit(&sharp));
+}
@@ -0,0 +1,74 @@
+extern crate ppm;
+
+use ppm::scene::bsdf::*;
+use ppm::util::*;
+
+// 法向量为 z 轴的白色玻璃表面，eta 为法向量背侧与正侧的折射率之比
+fn shading(eta: f64) -> Shading {
+    Shading {
+        normal: Vector3::new(0.0, 0.0, 1.0),
+        color: Color::new(1.0, 1.0, 1.0),
+        eta,
+        mode: Transport::Importance,
+    }
+}
+
+fn dir(theta: f64) -> Vector3 {
+    Vector3::new(theta.sin(), 0.0, theta.cos())
+}
+
+#[test]
+fn reflectance_at_normal_incidence() {
+    // ((1.5 - 1) / (1.5 + 1))² = 0.04
+    assert!((fresnel_dielectric(1.0, 1.5) - 0.04).abs() < 1e-12);
+    assert!((fresnel_dielectric(1.0, 1.0 / 1.5) - 0.04).abs() < 1e-12);
+    let branches = Glass.branches(&shading(1.5), &dir(0.0));
+    assert_eq!(branches.len(), 2);
+    assert!((branches[0].weight.r - 0.04).abs() < 1e-12);
+    assert!(!branches[0].transmitted && branches[1].transmitted);
+    // 掠射时几乎全部反射
+    assert!(fresnel_dielectric(1e-4, 1.5) > 0.99);
+}
+
+#[test]
+fn reflection_and_refraction_weights_sum_to_one() {
+    for eta in [1.5, 1.0 / 1.5, 1.33, 2.4] {
+        for k in 0..16 {
+            let theta = k as f64 * 0.1;
+            let branches = Glass.branches(&shading(eta), &dir(theta));
+            let weight: f64 = branches.iter().map(|b| b.weight.r).sum();
+            let pdf: f64 = branches.iter().map(|b| b.pdf).sum();
+            assert!((weight - 1.0).abs() < 1e-9, "eta {} theta {}: R + T = {}", eta, theta, weight);
+            assert!((pdf - 1.0).abs() < 1e-9);
+            // 反射方向关于法向量对称，折射方向满足 Snell 定律
+            let r = branches[0].dir;
+            assert!((r.x + theta.sin()).abs() < 1e-9 && (r.z - theta.cos()).abs() < 1e-9);
+            if let Some(t) = branches.get(1) {
+                assert!(t.dir.z < 0.0);
+                assert!((t.dir.x.abs() * eta - theta.sin()).abs() < 1e-9);
+            }
+        }
+    }
+}
+
+#[test]
+fn total_internal_reflection_beyond_critical_angle() {
+    // 从玻璃内部射向空气，临界角为 asin(1 / 1.5)
+    let eta: f64 = 1.0 / 1.5;
+    let critical = eta.asin();
+    let sh = shading(eta);
+    let below = Glass.branches(&sh, &dir(critical - 0.01));
+    assert_eq!(below.len(), 2);
+    assert!(below[1].transmitted);
+    for theta in [critical + 0.01, 1.0, 1.4] {
+        let branches = Glass.branches(&sh, &dir(theta));
+        assert_eq!(branches.len(), 1, "refraction beyond the critical angle at {}", theta);
+        assert!(!branches[0].transmitted);
+        assert_eq!(branches[0].weight.r, 1.0);
+        assert_eq!(fresnel_dielectric(theta.cos(), eta), 1.0);
+        let sample = Glass.sample(&sh, &dir(theta), 0.999, (0.5, 0.5)).unwrap();
+        assert!(!sample.transmitted && sample.weight.r == 1.0);
+    }
+    // 从外部射入时不会发生全反射
+    assert_eq!(Glass.branches(&shading(1.5), &dir(1.4)).len(), 2);
+}
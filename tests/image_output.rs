@@ -0,0 +1,208 @@
+extern crate lodepng;
+extern crate ppm;
+
+use ppm::util::image_file::{write_exr, write_hdr, write_pfm};
+use ppm::util::*;
+use std::convert::TryInto;
+
+// 含有大于 1 的亮度、纯黑像素和大片相同颜色的测试图像
+fn test_image(width: usize, height: usize) -> Framebuffer {
+    let mut fb = Framebuffer::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            let c = if x < width / 3 {
+                Color::new(0.25, 0.5, 0.75)
+            } else if (x + y) % 7 == 0 {
+                Color::default()
+            } else {
+                Color::new(x as f64 * 0.37, y as f64 * 1.9 + 0.01, (x * y) as f64 * 0.013 + 1e-3)
+            };
+            fb.set(x, y, c);
+        }
+    }
+    fb
+}
+
+fn f32_at(data: &[u8], pos: usize) -> f32 {
+    f32::from_le_bytes(data[pos..pos + 4].try_into().unwrap())
+}
+
+fn i32_at(data: &[u8], pos: usize) -> i32 {
+    i32::from_le_bytes(data[pos..pos + 4].try_into().unwrap())
+}
+
+#[test]
+fn pfm_stores_rows_bottom_to_top() {
+    let fb = test_image(13, 5);
+    let mut data = Vec::new();
+    write_pfm(&mut data, fb.width, fb.height, &fb.pixels).unwrap();
+    let header = b"PF\n13 5\n-1.0\n";
+    assert_eq!(&data[..header.len()], header);
+    assert_eq!(data.len(), header.len() + 13 * 5 * 12);
+    for y in 0..5 {
+        for x in 0..13 {
+            let pos = header.len() + ((4 - y) * 13 + x) * 12;
+            let c = fb.get(x, y);
+            assert_eq!(f32_at(&data, pos), c.r as f32);
+            assert_eq!(f32_at(&data, pos + 4), c.g as f32);
+            assert_eq!(f32_at(&data, pos + 8), c.b as f32);
+        }
+    }
+}
+
+// 按 Radiance 的规则解码一行，支持行程编码与不压缩两种形式
+fn decode_hdr_line(data: &[u8], pos: &mut usize, width: usize) -> Vec<[u8; 4]> {
+    let mut line = vec![[0u8; 4]; width];
+    if data[*pos] == 2 && data[*pos + 1] == 2 && data[*pos + 2] & 0x80 == 0 {
+        assert_eq!(((data[*pos + 2] as usize) << 8) | data[*pos + 3] as usize, width);
+        *pos += 4;
+        for k in 0..4 {
+            let mut x = 0;
+            while x < width {
+                let n = data[*pos] as usize;
+                *pos += 1;
+                if n > 128 {
+                    for p in line.iter_mut().skip(x).take(n - 128) {
+                        p[k] = data[*pos];
+                    }
+                    *pos += 1;
+                    x += n - 128;
+                } else {
+                    assert!(n > 0);
+                    for p in line.iter_mut().skip(x).take(n) {
+                        p[k] = data[*pos];
+                        *pos += 1;
+                    }
+                    x += n;
+                }
+            }
+            assert_eq!(x, width);
+        }
+    } else {
+        for p in line.iter_mut() {
+            p.copy_from_slice(&data[*pos..*pos + 4]);
+            *pos += 4;
+        }
+    }
+    line
+}
+
+fn check_hdr(width: usize, height: usize) {
+    let fb = test_image(width, height);
+    let mut data = Vec::new();
+    write_hdr(&mut data, fb.width, fb.height, &fb.pixels).unwrap();
+    let header = format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", height, width);
+    assert_eq!(&data[..header.len()], header.as_bytes());
+    let mut pos = header.len();
+    for y in 0..height {
+        for (x, p) in decode_hdr_line(&data, &mut pos, width).iter().enumerate() {
+            let c = fb.get(x, y);
+            let max = c.r.max(c.g).max(c.b);
+            if p[3] == 0 {
+                assert!(max < 1e-30);
+                continue;
+            }
+            let f = 2f64.powi(p[3] as i32 - 136);
+            for (v, expect) in [(p[0], c.r), (p[1], c.g), (p[2], c.b)] {
+                // 尾数只有 8 位，误差相对于三个分量中的最大值
+                assert!(((v as f64 + 0.5) * f - expect).abs() <= max / 128.0, "{:?} vs {:?}", p, c);
+            }
+        }
+    }
+    assert_eq!(pos, data.len());
+}
+
+#[test]
+fn hdr_round_trips_with_and_without_rle() {
+    check_hdr(300, 7);
+    check_hdr(5, 4);
+}
+
+#[test]
+fn exr_is_an_uncompressed_float_scanline_image() {
+    let (width, height) = (21, 6);
+    let fb = test_image(width, height);
+    let mut data = Vec::new();
+    write_exr(&mut data, width, height, &fb.pixels).unwrap();
+    assert_eq!(&data[..4], &[0x76, 0x2f, 0x31, 0x01]);
+    assert_eq!(i32_at(&data, 4), 2);
+
+    // 读出所有属性
+    let mut pos = 8;
+    let mut attrs = Vec::new();
+    let read_str = |pos: &mut usize| {
+        let end = *pos + data[*pos..].iter().position(|&b| b == 0).unwrap();
+        let s = String::from_utf8(data[*pos..end].to_vec()).unwrap();
+        *pos = end + 1;
+        s
+    };
+    loop {
+        let name = read_str(&mut pos);
+        if name.is_empty() {
+            break;
+        }
+        let kind = read_str(&mut pos);
+        let size = i32_at(&data, pos) as usize;
+        attrs.push((name, kind, data[pos + 4..pos + 4 + size].to_vec()));
+        pos += 4 + size;
+    }
+    let attr = |name: &str| attrs.iter().find(|a| a.0 == name).unwrap_or_else(|| panic!("missing {}", name));
+    for required in ["channels", "compression", "dataWindow", "displayWindow", "lineOrder", "pixelAspectRatio", "screenWindowCenter", "screenWindowWidth"] {
+        attr(required);
+    }
+    assert_eq!(attr("compression").2, vec![0]);
+    let window = &attr("dataWindow").2;
+    assert_eq!((i32_at(window, 0), i32_at(window, 4), i32_at(window, 8), i32_at(window, 12)), (0, 0, 20, 5));
+    // 通道：名字、类型 FLOAT(2)、pLinear 与保留字节、采样间隔 1 1
+    let channels = &attr("channels").2;
+    assert_eq!(channels.len(), 3 * 18 + 1);
+    for (k, name) in b"BGR".iter().enumerate() {
+        let c = &channels[k * 18..(k + 1) * 18];
+        assert_eq!((c[0], c[1], i32_at(c, 2), i32_at(c, 10), i32_at(c, 14)), (*name, 0, 2, 1, 1));
+    }
+
+    // 偏移量表与各行的数据
+    for y in 0..height {
+        let offset = u64::from_le_bytes(data[pos + y * 8..pos + y * 8 + 8].try_into().unwrap()) as usize;
+        assert_eq!(i32_at(&data, offset), y as i32);
+        assert_eq!(i32_at(&data, offset + 4) as usize, width * 12);
+        for x in 0..width {
+            let c = fb.get(x, y);
+            let base = offset + 8 + x * 4;
+            assert_eq!(f32_at(&data, base), c.b as f32);
+            assert_eq!(f32_at(&data, base + width * 4), c.g as f32);
+            assert_eq!(f32_at(&data, base + width * 8), c.r as f32);
+        }
+    }
+    let last = u64::from_le_bytes(data[pos + (height - 1) * 8..pos + height * 8].try_into().unwrap()) as usize;
+    assert_eq!(last + 8 + width * 12, data.len());
+}
+
+#[test]
+fn png_supports_16_bit_channels() {
+    let fb = test_image(17, 9);
+    let path = std::env::temp_dir().join(format!("ppm-test-{}.png", std::process::id()));
+    fb.save(&path, ImageFormat::Png { bit_depth: 16 }).unwrap();
+    let image = lodepng::decode_file(&path, lodepng::ColorType::RGB, 16).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let bitmap = match image {
+        lodepng::Image::RGB16(bitmap) => bitmap,
+        _ => panic!("not a 16-bit RGB image"),
+    };
+    assert_eq!((bitmap.width, bitmap.height), (17, 9));
+    // lodepng 解码得到的 16 位数据保持文件中的大端序
+    for (p, c) in bitmap.buffer.iter().zip(fb.pixels.iter()) {
+        assert_eq!((u16::from_be(p.r), u16::from_be(p.g), u16::from_be(p.b)), c.to_u16());
+    }
+}
+
+#[test]
+fn format_is_chosen_by_extension() {
+    use std::path::Path;
+    assert_eq!(ImageFormat::from_path(Path::new("a/b.PNG")), Some(ImageFormat::Png { bit_depth: 8 }));
+    assert_eq!(ImageFormat::from_path(Path::new("out.pfm")), Some(ImageFormat::Pfm));
+    assert_eq!(ImageFormat::from_path(Path::new("out.hdr")), Some(ImageFormat::Hdr));
+    assert_eq!(ImageFormat::from_path(Path::new("out.exr")), Some(ImageFormat::Exr));
+    assert_eq!(ImageFormat::from_path(Path::new("out.jpg")), None);
+    assert_eq!(ImageFormat::from_path(Path::new("out")), None);
+}
@@ -0,0 +1,102 @@
+extern crate ppm;
+
+use ppm::scene::material::Material;
+use ppm::util::*;
+use std::sync::Arc;
+
+// 物体 id 的表面上的一个交点，front_face 表示射线从外侧击中
+fn hit(id: u64, rindex: f64, front_face: bool) -> Collider {
+    let white = Color::new(1.0, 1.0, 1.0);
+    Collider {
+        pos: Vector3::new(0.0, 0.0, 0.0),
+        material: Arc::new(Material::new(white, 0.0, 0.0, 1.0, rindex)),
+        norm_vec: Vector3::new(0.0, 0.0, 1.0),
+        shading_normal: Vector3::new(0.0, 0.0, 1.0),
+        front_face,
+        distance: 1.0,
+        in_direction: Vector3::new(0.0, 0.0, -1.0),
+        hash_value: id,
+        color: white,
+        uv: (0.0, 0.0),
+    }
+}
+
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-12
+}
+
+// 依次穿过各个表面，检查每个表面处的折射率之比以及穿过之后所在的介质
+fn walk(media: &mut MediumStack, path: &[(Collider, f64, f64)]) {
+    for (k, (collider, eta, ior)) in path.iter().enumerate() {
+        assert!(close(media.eta(collider), *eta), "surface {}: eta {} vs {}", k, media.eta(collider), eta);
+        media.cross(collider);
+        assert!(close(media.ior(), *ior), "surface {}: ior {} vs {}", k, media.ior(), ior);
+    }
+}
+
+#[test]
+fn nested_media() {
+    // 水中的冰块：进入水、进入冰、离开冰、离开水
+    let (water, ice) = (1.33, 1.31);
+    let mut media = MediumStack::new();
+    walk(
+        &mut media,
+        &[
+            (hit(1, water, true), water, water),
+            (hit(2, ice, true), ice / water, ice),
+            (hit(2, ice, false), water / ice, water),
+            (hit(1, water, false), 1.0 / water, 1.0),
+        ],
+    );
+    assert_eq!(media.depth(), 0);
+}
+
+#[test]
+fn overlapping_media_exit_in_any_order() {
+    // 玻璃杯 A 中的液体 B 与杯壁重叠：先离开 A 时仍在 B 中，不发生折射
+    let (glass, liquid) = (1.5, 1.4);
+    let mut media = MediumStack::new();
+    walk(
+        &mut media,
+        &[
+            (hit(1, glass, true), glass, glass),
+            (hit(2, liquid, true), liquid / glass, liquid),
+            (hit(1, glass, false), 1.0, liquid),
+            (hit(2, liquid, false), 1.0 / liquid, 1.0),
+        ],
+    );
+    assert_eq!(media.depth(), 0);
+
+    // 再进入一次同一个物体：离开时只移除最近的一层
+    let mut media = MediumStack::new();
+    walk(
+        &mut media,
+        &[
+            (hit(1, glass, true), glass, glass),
+            (hit(2, liquid, true), liquid / glass, liquid),
+            (hit(1, glass, true), glass / liquid, glass),
+            (hit(1, glass, false), liquid / glass, liquid),
+            (hit(2, liquid, false), glass / liquid, glass),
+        ],
+    );
+    assert_eq!(media.depth(), 1);
+}
+
+#[test]
+fn non_refractive_surface_seen_from_inside() {
+    // 射线在玻璃中击中一个并未进入过的物体的背面，例如嵌在玻璃中的漫反射物体
+    let glass = 1.5;
+    let mut media = MediumStack::new();
+    media.cross(&hit(1, glass, true));
+    let stranger = hit(7, 1.0, false);
+    assert!(close(media.eta(&stranger), 1.0));
+    media.cross(&stranger);
+    assert!(close(media.ior(), glass));
+    assert_eq!(media.depth(), 1);
+
+    // 从真空中击中一个物体的背面同样不改变介质
+    let mut media = MediumStack::new();
+    assert!(close(media.eta(&hit(3, glass, false)), 1.0));
+    media.cross(&hit(3, glass, false));
+    assert_eq!(media.depth(), 0);
+}
@@ -0,0 +1,253 @@
+extern crate ppm;
+
+use ppm::scene::primitive::*;
+use ppm::scene::{load_obj, ObjModel, Scene, SceneError};
+use std::fs;
+use std::path::PathBuf;
+
+// 在临时目录中写入模型文件，返回 OBJ 文件的路径
+fn write_model(name: &str, obj: &str, mtl: Option<&str>) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ppm-obj-{}-{}", std::process::id(), name));
+    fs::create_dir_all(&dir).unwrap();
+    if let Some(mtl) = mtl {
+        fs::write(dir.join("model.mtl"), mtl).unwrap();
+    }
+    let path = dir.join("model.obj");
+    fs::write(&path, obj).unwrap();
+    path
+}
+
+fn load(name: &str, obj: &str, mtl: Option<&str>) -> Result<ObjModel, SceneError> {
+    let path = write_model(name, obj, mtl);
+    let model = load_obj(&path);
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    model
+}
+
+fn single_mesh(model: ObjModel) -> TriangleMesh {
+    let mut meshes = model.into_meshes(0, None);
+    assert_eq!(meshes.len(), 1);
+    meshes.pop().unwrap()
+}
+
+// 从 z = 10 处竖直向下的射线
+fn shoot(mesh: &TriangleMesh, x: f64, y: f64) -> Option<SurfaceHit> {
+    mesh.intersect(&Ray::new(Vector3::new(x, y, 10.0), Vector3::new(0.0, 0.0, -1.0)))
+}
+
+fn close(a: &Vector3, b: &Vector3) -> bool {
+    (a - b).norm() < 1e-9
+}
+
+#[test]
+fn polygons_are_split_into_fans() {
+    let obj = "
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+f 1 2 3 4
+v 3 0 0
+v 4 0 0
+v 4.5 1 0
+v 3.5 2 0
+v 2.5 1 0
+f 5 6 7 8 9
+";
+    let model = load("fan", obj, None).unwrap();
+    assert_eq!(model.face_count(), 2 + 3);
+    let mesh = single_mesh(model);
+    // 四边形和五边形内部的每一处都被某个三角形覆盖，外部没有三角形
+    for &(x, y) in &[(0.2, 0.8), (0.8, 0.2), (0.5, 0.5), (3.5, 0.2), (4.3, 0.9), (3.5, 1.8), (2.8, 1.0)] {
+        let hit = shoot(&mesh, x, y).unwrap_or_else(|| panic!("missed ({}, {})", x, y));
+        assert!((hit.distance - 10.0).abs() < 1e-9);
+        assert!(close(&hit.normal, &Vector3::new(0.0, 0.0, 1.0)));
+    }
+    for &(x, y) in &[(1.5, 0.5), (2.6, 0.2), (4.4, 1.8), (-0.1, 0.5)] {
+        assert!(shoot(&mesh, x, y).is_none(), "hit ({}, {})", x, y);
+    }
+}
+
+#[test]
+fn every_index_form_is_accepted() {
+    // 顶点法向量都偏向 x 轴，可以与几何法向量区分
+    let header = "
+v 0 0 0
+v 1 0 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 0 1
+vn 1 0 1
+vn 1 0 1
+vn 1 0 1
+";
+    let tilted = Vector3::new(1.0, 0.0, 1.0).normalize();
+    let up = Vector3::new(0.0, 0.0, 1.0);
+    let cases = [
+        ("f 1 2 3", up, false),
+        ("f 1/1 2/2 3/3", up, true),
+        ("f 1//1 2//2 3//3", tilted, false),
+        ("f 1/1/1 2/2/2 3/3/3", tilted, true),
+        ("f -3/-3/-3 -2/-2/-2 -1/-1/-1", tilted, true),
+        ("f -3 -2 -1", up, false),
+        // 只有部分顶点带有法向量时不使用法向量
+        ("f 1//1 2 3", up, false),
+    ];
+    for (k, &(face, normal, has_uv)) in cases.iter().enumerate() {
+        let model = load(&format!("index-{}", k), &format!("{}{}\n", header, face), None).unwrap();
+        let hit = shoot(&single_mesh(model), 0.2, 0.5).unwrap();
+        assert!(close(&hit.normal, &up), "`{}`: normal {:?}", face, hit.normal);
+        assert!(close(&hit.shading_normal, &normal), "`{}`: shading normal {:?}", face, hit.shading_normal);
+        if has_uv {
+            // 纹理坐标与顶点位置相同
+            assert!((hit.uv.0 - 0.2).abs() < 1e-9 && (hit.uv.1 - 0.5).abs() < 1e-9, "`{}`: uv {:?}", face, hit.uv);
+        }
+    }
+}
+
+#[test]
+fn usemtl_groups_faces_and_remaps_vertices() {
+    let mtl = "
+newmtl red
+Kd 0.8 0 0
+newmtl blue
+Kd 0 0 0.4
+Ks 0.2 0.2 0.2
+";
+    // 第一个三角形没有材质；red 出现两次，两段三角形属于同一个网格
+    let obj = "
+mtllib model.mtl
+v 0 0 0
+v 1 0 0
+v 0 1 0
+v 2 0 0
+v 3 0 0
+v 2 1 0
+v 4 0 0
+v 5 0 0
+v 4 1 0
+vn 0 0 1
+f 1 2 3
+usemtl red
+f 4//1 5//1 6//1
+usemtl blue
+f 7 8 9
+usemtl red
+f 7 9 3
+";
+    let model = load("usemtl", obj, Some(mtl)).unwrap();
+    assert_eq!(model.face_count(), 4);
+    let meshes = model.into_meshes(10, None);
+    assert_eq!(meshes.len(), 3);
+    let (plain, red, blue) = (&meshes[0], &meshes[1], &meshes[2]);
+    assert_eq!([plain.face_count(), red.face_count(), blue.face_count()], [1, 2, 1]);
+    let rgb = |mesh: &TriangleMesh| {
+        let c = mesh.get_material().color();
+        [c.r, c.g, c.b]
+    };
+    assert_eq!(rgb(plain), [0.75, 0.75, 0.75]);
+    assert_eq!(rgb(red), [1.0, 0.0, 0.0]);
+    assert_eq!(rgb(blue), [0.0, 0.0, 1.0]);
+    assert!((blue.get_material().diffuse - 0.4).abs() < 1e-9 && (blue.get_material().specular - 0.2).abs() < 1e-9);
+    let hashes = [plain.get_hash(), red.get_hash(), blue.get_hash()];
+    assert!(hashes[0] != hashes[1] && hashes[1] != hashes[2] && hashes[0] != hashes[2]);
+
+    // 每个网格只含有自己的三角形，重新编号后的顶点仍在原来的位置
+    assert!(shoot(plain, 0.2, 0.2).is_some() && shoot(plain, 2.2, 0.2).is_none());
+    assert!(shoot(red, 2.2, 0.2).is_some() && shoot(red, 0.2, 0.2).is_none());
+    assert!(shoot(red, 3.0, 0.9).is_some() && shoot(red, 4.5, 0.2).is_none());
+    assert!(shoot(blue, 4.5, 0.2).is_some() && shoot(blue, 2.2, 0.2).is_none());
+    assert_eq!(red.bounding_box().unwrap().min, Vector3::new(0.0, 0.0, 0.0));
+    assert_eq!(blue.bounding_box().unwrap().min, Vector3::new(4.0, 0.0, 0.0));
+    assert_eq!(blue.bounding_box().unwrap().max, Vector3::new(5.0, 1.0, 0.0));
+
+    // 指定材质时所有三角形组成一个网格
+    let model = load("usemtl-override", obj, Some(mtl)).unwrap();
+    let meshes = model.into_meshes(0, Some(plain.get_material()));
+    assert_eq!(meshes.len(), 1);
+    assert_eq!(meshes[0].face_count(), 4);
+}
+
+fn error_line(result: Result<ObjModel, SceneError>) -> (String, usize) {
+    match result {
+        Err(SceneError::Model { path, line, .. }) => (path.file_name().unwrap().to_string_lossy().into_owned(), line),
+        Err(e) => panic!("unexpected error {}", e),
+        Ok(_) => panic!("the model should be rejected"),
+    }
+}
+
+#[test]
+fn errors_report_the_file_and_line() {
+    let cases = [
+        ("v 0 0 0\nv 1 0 0\n# comment\nv 0 x 0\n", 4),
+        ("v 0 0 0\n\nv 1 0\n", 3),
+        ("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n", 4),
+        ("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 0\n", 4),
+        ("v 0 0 0\nv 1 0 0\nv 0 1 0\nf -4 2 3\n", 4),
+        ("v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\n\nf 1/2 2/1 3/1\n", 6),
+        ("v 0 0 0\nv 1 0 0\nf 1 2\n", 3),
+    ];
+    for (k, &(obj, line)) in cases.iter().enumerate() {
+        assert_eq!(error_line(load(&format!("error-{}", k), obj, None)), ("model.obj".to_string(), line), "{}", obj);
+    }
+    // 没有面的模型报告在最后一行，即使指定了材质也不会生成空网格
+    assert_eq!(error_line(load("no-faces", "v 0 0 0\nv 1 0 0\nv 0 1 0\n", None)), ("model.obj".to_string(), 3));
+    assert_eq!(error_line(load("empty", "", None)), ("model.obj".to_string(), 1));
+    match load("no-faces-message", "mtllib model.mtl\nv 0 0 0\n", Some("newmtl a\nKd 1 1 1\n")) {
+        Err(e) => assert!(e.to_string().ends_with("line 2: the model has no faces"), "{}", e),
+        Ok(_) => panic!("a model without faces should be rejected"),
+    }
+    // MTL 中的错误报告 MTL 文件的行号
+    let mtl = "newmtl a\nKd 1 1 1\n\nKs 1 1\n";
+    let result = load("error-mtl", "mtllib model.mtl\n", Some(mtl));
+    assert_eq!(error_line(result), ("model.mtl".to_string(), 4));
+}
+
+#[test]
+fn negative_scale_keeps_normals_outward() {
+    // z = 1 处法向量朝 +z 的三角形，一份由顶点顺序决定法向量，一份带有顶点法向量
+    let geometric = "v 0 0 1\nv 1 0 1\nv 0 1 1\nvt 0 0\nvt 1 0\nvt 0 1\nf 1/1 2/2 3/3\n";
+    let shaded = "v 0 0 1\nv 1 0 1\nv 0 1 1\nvn 0 0 1\nf 1//1 2//1 3//1\n";
+    for (k, obj) in [geometric, shaded].iter().enumerate() {
+        let mut model = load(&format!("mirror-{}", k), obj, None).unwrap();
+        model.transform(-1.0, Vector3::new(0.0, 0.0, 0.0));
+        let mesh = single_mesh(model);
+        // 反射后三角形位于 z = -1，法向量应当朝 -z，即背离原点
+        let ray = Ray::new(Vector3::new(-0.2, -0.5, -10.0), Vector3::new(0.0, 0.0, 1.0));
+        let hit = mesh.intersect(&ray).unwrap();
+        assert!((hit.distance - 9.0).abs() < 1e-9);
+        assert!(close(&hit.normal, &Vector3::new(0.0, 0.0, -1.0)), "{}: normal {:?}", k, hit.normal);
+        assert!(close(&hit.shading_normal, &Vector3::new(0.0, 0.0, -1.0)), "{}: shading normal {:?}", k, hit.shading_normal);
+        if k == 0 {
+            // 纹理坐标跟随顶点一起交换，交点处的纹理坐标不变
+            assert!((hit.uv.0 - 0.2).abs() < 1e-9 && (hit.uv.1 - 0.5).abs() < 1e-9, "uv {:?}", hit.uv);
+        }
+    }
+}
+
+#[test]
+fn front_face_follows_the_geometric_normal() {
+    // 顶点法向量大幅偏向 +x，掠射的射线与着色法向量同向，但仍从几何法向量一侧击中
+    // 第二个三角形法向量朝 -z，顶点法向量却朝 +z 一侧
+    let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 5 0 0\nv 6 0 0\nv 5 1 0\nvn 1 0 0.2\nf 1//1 2//1 3//1\nf 4//1 6//1 5//1\n";
+    let mut scene = Scene::new();
+    for mesh in load("silhouette", obj, None).unwrap().into_meshes(0, None) {
+        scene.add_object(Box::new(mesh));
+    }
+    scene.build_bvh();
+    let ray = Ray::new(Vector3::new(-0.8, 0.2, 0.1), Vector3::new(1.0, 0.0, -0.1).normalize());
+    let collider = scene.intersect(&ray).unwrap();
+    assert!(collider.front_face);
+    assert!(close(&collider.norm_vec, &Vector3::new(0.0, 0.0, 1.0)), "normal {:?}", collider.norm_vec);
+    // 着色法向量与几何法向量在同一侧
+    let tilted = Vector3::new(1.0, 0.0, 0.2).normalize();
+    assert!(close(&collider.shading_normal, &tilted), "shading normal {:?}", collider.shading_normal);
+
+    // 插值得到的顶点法向量与几何法向量相反时被翻到几何法向量一侧
+    let from_below = Ray::new(Vector3::new(5.2, 0.2, -5.0), Vector3::new(0.0, 0.0, 1.0));
+    let collider = scene.intersect(&from_below).unwrap();
+    assert!(collider.front_face);
+    assert!(close(&collider.norm_vec, &Vector3::new(0.0, 0.0, -1.0)));
+    assert!(close(&collider.shading_normal, &tilted.mult(-1.0)), "shading normal {:?}", collider.shading_normal);
+}
@@ -0,0 +1,139 @@
+extern crate ppm;
+
+use ppm::core::{new_integrator, Integrator, PixelStat, ProgressivePhotonTracer, RenderMode, RenderSettings};
+use ppm::scene::Scene;
+use ppm::util::*;
+use std::path::Path;
+use std::sync::Arc;
+
+// 点光源照亮正对相机的漫反射三角形，三角形占满整个画面
+const WALL: &str = "
+camera position 0 0 0  direction 1 0 0  size 8 8
+material white  color 1 1 1  diffuse 1
+triangle  v0 10 -100 -100  v1 10 100 -100  v2 10 0 200  material white
+point_light position 5 0 0  color 2000 2000 2000
+";
+
+fn render(text: &str, settings: RenderSettings) -> Framebuffer {
+    let scene = Scene::parse(text, Path::new(".")).unwrap();
+    let camera = Arc::new(scene.get_camera().cloned().unwrap());
+    new_integrator(settings).render(camera, Arc::new(scene))
+}
+
+fn brightness(fb: &Framebuffer) -> f64 {
+    fb.pixels.iter().map(|c| c.r + c.g + c.b).sum()
+}
+
+#[test]
+fn photon_count_does_not_depend_on_thread_count() {
+    // 每轮 5 个光子分给 4 个线程时也要全部发射，否则图像按未发射的光子数归一化而偏暗
+    // 所有光子构成同一个样本序列，线程数只改变分段方式，结果只差求和顺序带来的舍入误差
+    let settings = |mode, threads| RenderSettings {
+        mode,
+        rounds: 200,
+        photons_per_pass: 5,
+        threads,
+        init_radius: Some(1.0),
+        ..Default::default()
+    };
+    for mode in [RenderMode::Ppm, RenderMode::Sppm] {
+        let even = brightness(&render(WALL, settings(mode, 5)));
+        let uneven = brightness(&render(WALL, settings(mode, 4)));
+        assert!(even > 0.0);
+        assert!((uneven / even - 1.0).abs() < 1e-9, "{:?}: {} vs {}", mode, uneven, even);
+    }
+}
+
+#[test]
+fn unbounded_scene_without_view_points_uses_the_fallback_radius() {
+    // 只有平面的场景没有有界物体，第一轮又没有视点时，范围为空
+    let text = "
+material white  color 1 1 1  diffuse 1
+plane  normal 1 0 0  distance -10  material white
+plane  normal 0 0 1  distance 0  material white
+";
+    let scene = Scene::parse(text, Path::new(".")).unwrap();
+    assert!(!scene.is_bounded());
+    let settings = RenderSettings { fallback_radius: 3.0, ..Default::default() };
+    assert_eq!(settings.initial_radius(&scene.bounds(), 40, 30), 3.0);
+    // 所有视点重合时范围退化为一个点，同样不能得到 0
+    let point = AABB::empty().grow(&Vector3::new(1.0, 2.0, 3.0));
+    assert_eq!(settings.initial_radius(&point, 40, 30), 3.0);
+    // 有范围时按两个像素的尺度估计，指定 init_radius 时总是使用它
+    let room = AABB::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(70.0, 70.0, 70.0));
+    assert!((settings.initial_radius(&room, 40, 30) - 4.0).abs() < 1e-12);
+    let fixed = RenderSettings { init_radius: Some(0.5), ..settings };
+    assert_eq!(fixed.initial_radius(&scene.bounds(), 40, 30), 0.5);
+}
+
+#[test]
+fn pixel_radius_shrinks_by_the_alpha_rule() {
+    let mut stat = PixelStat { radius2: 4.0, ..Default::default() };
+    let flux = Color::new(1.0, 1.0, 1.0);
+    // 第一次收集到光子时恰好收缩为 alpha 倍
+    stat.renew(10.0, flux, 0.7);
+    assert!((stat.radius2 - 2.8).abs() < 1e-12 && (stat.count - 7.0).abs() < 1e-12);
+    assert!((stat.flux_color.r - 0.7).abs() < 1e-12);
+    // 没有光子的一轮保持不变
+    stat.renew(0.0, flux, 0.7);
+    assert_eq!((stat.radius2, stat.count), (2.8, 7.0));
+    // k = (7 + 5 * 0.7) / (7 + 5)
+    stat.renew(5.0, flux, 0.7);
+    assert!((stat.radius2 - 2.8 * 0.875).abs() < 1e-12 && (stat.count - 10.5).abs() < 1e-12);
+    assert!((stat.flux_color.r - 1.7 * 0.875).abs() < 1e-12);
+    // 无论每轮的光子数多少，半径都不会增大
+    for round in 0..1000 {
+        let before = stat.radius2;
+        stat.renew((round * 7919 % 13) as f64, flux, 0.7);
+        assert!(stat.radius2 <= before && stat.radius2 > 0.0, "round {}: {} -> {}", round, before, stat.radius2);
+    }
+}
+
+// 在 WALL 上用 SPPM 渲染 rounds 轮，返回图像和各像素最终的统计量
+fn render_sppm(rounds: usize, threads: usize) -> (Framebuffer, Vec<PixelStat>) {
+    let scene = Scene::parse(WALL, Path::new(".")).unwrap();
+    let camera = Arc::new(scene.get_camera().cloned().unwrap());
+    let mut tracer = ProgressivePhotonTracer::new(RenderSettings {
+        mode: RenderMode::Sppm,
+        rounds,
+        photons_per_pass: 500,
+        threads,
+        init_radius: Some(2.0),
+        seed: 3,
+        ..Default::default()
+    });
+    let fb = tracer.render(camera, Arc::new(scene));
+    (fb, tracer.pixel_stats().to_vec())
+}
+
+#[test]
+fn sppm_pixel_radius_never_grows() {
+    let (r0, alpha) = (4.0, RenderSettings::default().alpha);
+    // 只有一轮时，收集到光子的像素半径平方恰好为初值的 alpha 倍，其余保持初值
+    let (_, stats) = render_sppm(1, 2);
+    assert_eq!(stats.len(), 64);
+    assert!(stats.iter().any(|s| s.count > 0.0));
+    for s in stats.iter() {
+        let expect = if s.count > 0.0 { r0 * alpha } else { r0 };
+        assert!((s.radius2 - expect).abs() < 1e-12, "{:?}", s);
+    }
+    // 之后各轮只会继续收缩
+    let (_, stats) = render_sppm(6, 2);
+    assert!(stats.iter().all(|s| s.count > 0.0));
+    for s in stats.iter() {
+        assert!(s.radius2 > 0.0 && s.radius2 <= r0 * alpha + 1e-12, "{:?}", s);
+    }
+    assert!(stats.iter().any(|s| s.radius2 < r0 * alpha - 1e-6));
+}
+
+#[test]
+fn sppm_is_reproducible_with_the_same_seed_and_threads() {
+    let bits = |(fb, stats): &(Framebuffer, Vec<PixelStat>)| -> Vec<u64> {
+        let pixels = fb.pixels.iter().flat_map(|c| vec![c.r, c.g, c.b]);
+        let stats = stats.iter().flat_map(|s| vec![s.radius2, s.count, s.flux_color.r, s.flux_color.g, s.flux_color.b]);
+        pixels.chain(stats).map(f64::to_bits).collect()
+    };
+    let first = render_sppm(4, 3);
+    assert!(brightness(&first.0) > 0.0);
+    assert_eq!(bits(&first), bits(&render_sppm(4, 3)));
+}
@@ -0,0 +1,272 @@
+extern crate ppm;
+extern crate rand;
+
+use ppm::util::sampling::*;
+use ppm::util::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::f64::consts::PI;
+
+const N: usize = 200_000;
+
+fn samples(seed: u64, f: fn((f64, f64)) -> Vector3) -> Vec<Vector3> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..N).map(|_| f((rng.gen(), rng.gen()))).collect()
+}
+
+// Pearson 卡方统计量，各个桶的期望概率相等
+fn chi_square(counts: &[usize]) -> f64 {
+    let total: usize = counts.iter().sum();
+    let expect = total as f64 / counts.len() as f64;
+    counts.iter().map(|&c| (c as f64 - expect).powi(2) / expect).sum()
+}
+
+// 按 (t, 方位角) 分成 bins * bins 个概率相等的桶，t 在 [0, 1) 中应当均匀分布
+fn check_uniform(dirs: &[Vector3], t: impl Fn(&Vector3) -> f64) {
+    let bins = 8;
+    let mut counts = vec![0; bins * bins];
+    for d in dirs {
+        let i = ((t(d) * bins as f64) as usize).min(bins - 1);
+        let phi = d.y.atan2(d.x).rem_euclid(2.0 * PI);
+        let j = ((phi / (2.0 * PI) * bins as f64) as usize).min(bins - 1);
+        counts[i * bins + j] += 1;
+    }
+    // 63 个自由度，显著性水平约 1e-4 时的临界值
+    let chi2 = chi_square(&counts);
+    assert!(chi2 < 110.0, "chi-square {} too large: {:?}", chi2, counts);
+}
+
+// 方向落在以 axis 为中心、cos 大于 c 的球冠中的比例
+fn cap_fraction(dirs: &[Vector3], axis: &Vector3, c: f64) -> f64 {
+    dirs.iter().filter(|d| d.dot(axis) > c).count() as f64 / dirs.len() as f64
+}
+
+fn assert_close(got: f64, expect: f64, tol: f64) {
+    assert!((got - expect).abs() < tol, "expected {}, got {}", expect, got);
+}
+
+#[test]
+fn samples_are_unit_vectors() {
+    for f in [uniform_sphere, uniform_hemisphere, cosine_hemisphere] {
+        for d in samples(1, f).iter().take(10_000) {
+            assert!((d.norm() - 1.0).abs() < 1e-12);
+        }
+    }
+    for d in [uniform_sphere((0.0, 0.0)), uniform_hemisphere((0.0, 0.0)), cosine_hemisphere((0.0, 0.0))] {
+        assert!((d.norm() - 1.0).abs() < 1e-12);
+    }
+}
+
+#[test]
+fn hemisphere_samples_stay_above_the_surface() {
+    for f in [uniform_hemisphere, cosine_hemisphere] {
+        assert!(samples(2, f).iter().all(|d| d.z >= 0.0));
+    }
+}
+
+#[test]
+fn uniform_sphere_is_uniform() {
+    let dirs = samples(3, uniform_sphere);
+    check_uniform(&dirs, |d| (1.0 - d.z) * 0.5);
+    // 任意方向的球冠所占比例等于其面积之比 (1 - c) / 2
+    let mut rng = StdRng::seed_from_u64(4);
+    for _ in 0..8 {
+        let axis = uniform_sphere((rng.gen(), rng.gen()));
+        for &c in &[-0.5, 0.0, 0.3, 0.9] {
+            assert_close(cap_fraction(&dirs, &axis, c), (1.0 - c) * 0.5, 0.006);
+        }
+    }
+    let mean = dirs.iter().fold(Vector3::zeros(), |s, d| s + d) / N as f64;
+    assert!(mean.norm() < 0.01);
+}
+
+#[test]
+fn uniform_hemisphere_is_uniform() {
+    let dirs = samples(5, uniform_hemisphere);
+    check_uniform(&dirs, |d| 1.0 - d.z);
+    for &c in &[0.1, 0.5, 0.9] {
+        assert_close(cap_fraction(&dirs, &Vector3::new(0.0, 0.0, 1.0), c), 1.0 - c, 0.006);
+    }
+    // E[cos] = 1 / 2
+    assert_close(dirs.iter().map(|d| d.z).sum::<f64>() / N as f64, 0.5, 0.005);
+}
+
+#[test]
+fn cosine_hemisphere_is_cosine_weighted() {
+    let dirs = samples(6, cosine_hemisphere);
+    // cos²θ 在 [0, 1) 中均匀分布
+    check_uniform(&dirs, |d| 1.0 - d.z * d.z);
+    for &c in &[0.1, 0.5, 0.9] {
+        assert_close(cap_fraction(&dirs, &Vector3::new(0.0, 0.0, 1.0), c), 1.0 - c * c, 0.006);
+    }
+    // E[cos] = 2 / 3
+    assert_close(dirs.iter().map(|d| d.z).sum::<f64>() / N as f64, 2.0 / 3.0, 0.005);
+}
+
+// 用各采样函数及其概率密度做蒙特卡洛积分，检验概率密度与分布一致
+#[test]
+fn pdfs_match_distributions() {
+    let estimate = |dirs: &[Vector3], f: &dyn Fn(&Vector3) -> f64, pdf: &dyn Fn(&Vector3) -> f64| {
+        dirs.iter().map(|d| f(d) / pdf(d)).sum::<f64>() / dirs.len() as f64
+    };
+    // 球面面积 4π
+    let sphere = samples(7, uniform_sphere);
+    assert_close(estimate(&sphere, &|_| 1.0, &|_| uniform_sphere_pdf()), 4.0 * PI, 1e-9);
+    // ∫ z² dω 在球面上为 4π / 3
+    assert_close(estimate(&sphere, &|d| d.z * d.z, &|_| uniform_sphere_pdf()), 4.0 * PI / 3.0, 0.03);
+    // ∫ cos dω 在半球上为 π
+    let hemi = samples(8, uniform_hemisphere);
+    assert_close(estimate(&hemi, &|d| d.z, &|_| uniform_hemisphere_pdf()), PI, 0.02);
+    // ∫ cos² dω 在半球上为 2π / 3，余弦采样时 cos / pdf 恒为 π
+    let cosine = samples(9, cosine_hemisphere);
+    assert_close(estimate(&cosine, &|d| d.z, &|d| cosine_hemisphere_pdf(d.z)), PI, 1e-9);
+    assert_close(estimate(&cosine, &|d| d.z * d.z, &|d| cosine_hemisphere_pdf(d.z)), 2.0 * PI / 3.0, 0.01);
+}
+
+#[test]
+fn frame_is_orthonormal() {
+    let mut rng = StdRng::seed_from_u64(10);
+    let mut normals: Vec<Vector3> = (0..100).map(|_| uniform_sphere((rng.gen(), rng.gen()))).collect();
+    normals.push(Vector3::new(0.0, 0.0, 1.0));
+    normals.push(Vector3::new(0.0, 0.0, -1.0));
+    for n in normals {
+        let frame = Frame::new(&n);
+        let (x, y, z) = (
+            frame.to_world(&Vector3::new(1.0, 0.0, 0.0)),
+            frame.to_world(&Vector3::new(0.0, 1.0, 0.0)),
+            frame.to_world(&Vector3::new(0.0, 0.0, 1.0)),
+        );
+        assert!((z - n).norm() < 1e-12);
+        assert!(x.dot(&y).abs() < 1e-12 && x.dot(&z).abs() < 1e-12 && y.dot(&z).abs() < 1e-12);
+        assert!((x.norm() - 1.0).abs() < 1e-12 && (y.norm() - 1.0).abs() < 1e-12);
+        let v = cosine_hemisphere((rng.gen(), rng.gen()));
+        let w = frame.to_world(&v);
+        assert!(w.dot(&n) >= -1e-12);
+        assert!((frame.to_local(&w) - v).norm() < 1e-12);
+    }
+}
+
+#[test]
+fn random_vector_is_uniform_on_sphere() {
+    let mut sampler = Sampler::new(SamplerKind::Independent, 11);
+    let dirs: Vec<Vector3> = (0..N).map(|_| Vector3::random(&mut sampler)).collect();
+    assert!(dirs.iter().all(|d| (d.norm() - 1.0).abs() < 1e-12));
+    check_uniform(&dirs, |d| (1.0 - d.z) * 0.5);
+    // 立方体采样后归一化会偏向对角线方向
+    let diagonal = Vector3::new(1.0, 1.0, 1.0).normalize();
+    assert_close(cap_fraction(&dirs, &diagonal, 0.9), 0.05, 0.004);
+}
+
+const KINDS: [SamplerKind; 4] = [SamplerKind::Independent, SamplerKind::Stratified, SamplerKind::Halton, SamplerKind::Sobol];
+
+// 取出一个序列中全部样本的前 dims 个一维值
+fn sequence_1d(sampler: &mut Sampler, key: u64, count: u64, dims: usize) -> Vec<Vec<f64>> {
+    sampler.start_sequence(key, count);
+    (0..count)
+        .map(|i| {
+            sampler.start_sample(i);
+            (0..dims).map(|_| sampler.get_1d()).collect()
+        })
+        .collect()
+}
+
+#[test]
+fn sampler_values_are_in_unit_interval() {
+    for kind in KINDS {
+        let mut sampler = Sampler::new(kind, 12);
+        for key in 0..20 {
+            for sample in sequence_1d(&mut sampler, key, 100, 40) {
+                assert!(sample.iter().all(|&u| (0.0..1.0).contains(&u)), "{:?}: {:?}", kind, sample);
+            }
+        }
+        // 不在序列中时同样有效
+        let (u, v) = Sampler::new(kind, 12).get_2d();
+        assert!((0.0..1.0).contains(&u) && (0.0..1.0).contains(&v));
+    }
+}
+
+#[test]
+fn same_seed_gives_same_samples() {
+    for kind in KINDS {
+        let a = sequence_1d(&mut Sampler::derive(kind, 3, 1, 2), 5, 16, 8);
+        let b = sequence_1d(&mut Sampler::derive(kind, 3, 1, 2), 5, 16, 8);
+        let c = sequence_1d(&mut Sampler::derive(kind, 4, 1, 2), 5, 16, 8);
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+}
+
+// 2 的幂个样本在每一维上恰好各落在一个长为 1 / count 的区间中
+#[test]
+fn low_discrepancy_samples_are_stratified_in_1d() {
+    let count = 64;
+    for kind in [SamplerKind::Stratified, SamplerKind::Halton, SamplerKind::Sobol] {
+        let mut sampler = Sampler::new(kind, 13);
+        // Halton 序列只有以 2 为底的第一维满足该性质
+        let dims = if kind == SamplerKind::Halton { 1 } else { 6 };
+        let samples = sequence_1d(&mut sampler, 7, count, dims);
+        for d in 0..dims {
+            let mut bins = vec![0; count as usize];
+            for s in &samples {
+                bins[(s[d] * count as f64) as usize] += 1;
+            }
+            assert!(bins.iter().all(|&b| b == 1), "{:?} dim {}: {:?}", kind, d, bins);
+        }
+    }
+}
+
+#[test]
+fn low_discrepancy_samples_are_stratified_in_2d() {
+    let count = 64;
+    for kind in [SamplerKind::Stratified, SamplerKind::Sobol] {
+        let mut sampler = Sampler::new(kind, 14);
+        sampler.start_sequence(9, count);
+        let mut cells = vec![[0; 8]; 8];
+        let mut dims = vec![Vec::new(); 3];
+        for i in 0..count {
+            sampler.start_sample(i);
+            for d in dims.iter_mut() {
+                d.push(sampler.get_2d());
+            }
+        }
+        for points in dims {
+            for row in cells.iter_mut() {
+                *row = [0; 8];
+            }
+            for (u, v) in points {
+                cells[(u * 8.0) as usize][(v * 8.0) as usize] += 1;
+            }
+            assert!(cells.iter().flatten().all(|&c| c == 1), "{:?}: {:?}", kind, cells);
+        }
+    }
+}
+
+// 用各采样器估计单位正方形中四分之一圆的面积，低差异序列的误差应当明显更小
+#[test]
+fn low_discrepancy_samplers_reduce_integration_error() {
+    let rmse = |kind: SamplerKind| {
+        let mut sampler = Sampler::new(kind, 15);
+        let trials = 200;
+        let count = 64;
+        let mut err2 = 0.0;
+        for key in 0..trials {
+            sampler.start_sequence(key, count);
+            let mut hits = 0;
+            for i in 0..count {
+                sampler.start_sample(i);
+                sampler.get_1d(); // 跳过一维，检验后面的维度
+                let (u, v) = sampler.get_2d();
+                if u * u + v * v < 1.0 {
+                    hits += 1;
+                }
+            }
+            err2 += (hits as f64 / count as f64 - PI / 4.0).powi(2);
+        }
+        (err2 / trials as f64).sqrt()
+    };
+    let independent = rmse(SamplerKind::Independent);
+    for kind in [SamplerKind::Stratified, SamplerKind::Halton, SamplerKind::Sobol] {
+        let err = rmse(kind);
+        assert!(err < independent * 0.7, "{:?}: {} vs independent {}", kind, err, independent);
+    }
+}
@@ -0,0 +1,207 @@
+extern crate ppm;
+
+use ppm::scene::{Scene, SceneError};
+use ppm::util::*;
+use std::fs;
+use std::path::Path;
+
+// 每种指令都至少出现一次的场景，图像和模型放在场景文件旁边
+const FULL: &str = "
+# 注释与空行被忽略
+camera position 0 0 50  direction 0 0 -1  size 4 3
+
+texture red      image  file red.png  wrap clamp  filter nearest  scale 2 2  offset 0.5 0.5
+texture checker  checker  even 0.9 0.9 0.9  odd 0.1 0.1 0.1  scale 0.1 0.1
+texture granite  noise  low 0 0 0  high 1 1 1  scale 0.05  octaves 3  seed 2
+texture marble   marble  low 0 0 0  high 1 1 1  scale 0.02  turbulence 4  octaves 2  seed 5
+texture green    gradient  from 0 1 0  to 0 1 0  start 0 0 0  end 0 0 1
+
+material floor   color 1 1 1  texture checker
+material glass   color 1 1 1  diffuse 0  refraction 1  rindex 1.5  roughness 0.2  texture green
+material metal   color 0.9 0.8 0.7  diffuse 0.2  specular 0.8  texture red
+material stone   color 1 1 1  texture granite
+material veined  color 1 1 1  texture marble   # 行尾注释
+
+plane     normal 0 0 1  distance 0  material floor
+sphere    center 20 0 10  radius 5  material glass
+triangle  v0 38 -2 20  v1 42 -2 20  v2 40 2 20  material metal
+profile   tube  2 0  2 1  2 2  2 3
+bezier    position 60 0 0  material stone  profile tube  axis 0 0 1  scale 2
+mesh      file tri.obj  material veined  scale 2  translate 80 0 0
+mesh      file tri.obj  translate 100 0 0
+
+area_light  position -5 -5 40  dx 1 0 0  dy 0 1 0  normal 0 0 -1  color 5 5 5  width 10  height 10
+point_light position 0 0 45  color 1 1 1
+";
+
+fn load_full() -> Scene {
+    let dir = std::env::temp_dir().join(format!("ppm-scene-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let mut red = Framebuffer::new(2, 2);
+    for j in 0..2 {
+        for i in 0..2 {
+            red.set(i, j, Color::new(1.0, 0.0, 0.0));
+        }
+    }
+    red.save(&dir.join("red.png"), ImageFormat::Png { bit_depth: 8 }).unwrap();
+    fs::write(dir.join("tri.obj"), "v -1 -1 5\nv 1 -1 5\nv 0 1 5\nf 1 2 3\n").unwrap();
+    let path = dir.join("test.scene");
+    fs::write(&path, FULL).unwrap();
+    let scene = Scene::from_file(&path);
+    fs::remove_dir_all(&dir).unwrap();
+    scene.unwrap_or_else(|e| panic!("{}", e))
+}
+
+// 从 (x, y, 100) 竖直向下的射线击中的物体
+fn drop(scene: &Scene, x: f64, y: f64) -> Collider {
+    scene.intersect(&Ray::new(Vector3::new(x, y, 100.0), Vector3::new(0.0, 0.0, -1.0))).unwrap()
+}
+
+fn rgb(c: Color) -> [f64; 3] {
+    [c.r, c.g, c.b]
+}
+
+#[test]
+fn every_directive_is_loaded() {
+    let scene = load_full();
+    let camera = scene.get_camera().unwrap();
+    assert_eq!((camera.width, camera.height), (4, 3));
+    assert_eq!(scene.get_light_num(), 2);
+    assert!(!scene.is_bounded());
+
+    let floor = drop(&scene, -50.0, 0.0);
+    assert!((floor.distance - 100.0).abs() < 1e-9);
+    assert!(rgb(floor.color) == [0.9; 3] || rgb(floor.color) == [0.1; 3]);
+
+    let sphere = drop(&scene, 20.0, 0.0);
+    assert!((sphere.distance - 85.0).abs() < 1e-9);
+    assert_eq!(rgb(sphere.color), [0.0, 1.0, 0.0]);
+    assert_eq!(sphere.material.rindex, 1.5);
+    assert_eq!(sphere.material.refraction, 1.0);
+    assert_eq!(sphere.material.roughness(), 0.2);
+
+    let triangle = drop(&scene, 40.0, 0.0);
+    assert!((triangle.distance - 80.0).abs() < 1e-9);
+    assert_eq!(rgb(triangle.color), [1.0, 0.0, 0.0]);
+    assert_eq!((triangle.material.diffuse, triangle.material.specular), (0.2, 0.8));
+
+    // 半径为 2 的圆柱放大 2 倍，从侧面击中
+    let side = Ray::new(Vector3::new(60.0, -100.0, 3.0), Vector3::new(0.0, 1.0, 0.0));
+    let bezier = scene.intersect(&side).unwrap();
+    assert!((bezier.distance - 96.0).abs() < 1e-6, "bezier at {}", bezier.distance);
+    assert!((bezier.norm_vec.y + 1.0).abs() < 1e-6);
+
+    // 模型放大 2 倍后平移，未指定材质时使用默认材质
+    let mesh = drop(&scene, 80.0, 0.0);
+    assert!((mesh.distance - 90.0).abs() < 1e-9);
+    let plain = drop(&scene, 100.0, 0.0);
+    assert!((plain.distance - 95.0).abs() < 1e-9);
+    assert_eq!(rgb(plain.color), [0.75; 3]);
+
+    // 不同物体的 hash 互不相同
+    let hashes = [floor.hash_value, sphere.hash_value, triangle.hash_value, bezier.hash_value, mesh.hash_value, plain.hash_value];
+    for i in 0..hashes.len() {
+        for j in 0..i {
+            assert_ne!(hashes[i], hashes[j]);
+        }
+    }
+}
+
+// 解析错误的行号和信息
+fn parse_error(text: &str) -> (usize, String) {
+    match Scene::parse(text, Path::new(".")) {
+        Err(SceneError::Parse { line, msg }) => (line, msg),
+        Err(e) => panic!("unexpected error {}", e),
+        Ok(_) => panic!("`{}` should be rejected", text),
+    }
+}
+
+fn check(text: &str, line: usize, msg: &str) {
+    let (got_line, got_msg) = parse_error(text);
+    assert_eq!(got_line, line, "`{}`: {}", text, got_msg);
+    assert!(got_msg.contains(msg), "`{}`: `{}` does not mention `{}`", text, got_msg, msg);
+}
+
+const MATERIAL: &str = "material m  color 1 1 1\n";
+
+#[test]
+fn unknown_directives_and_keys_are_rejected() {
+    check("sphre center 0 0 0  radius 1  material m", 1, "unknown directive `sphre`");
+    check(&format!("{}sphere center 0 0 0  radius 1  material m  colour 1 1 1", MATERIAL), 2, "unknown key `colour` for `sphere`");
+    check("texture t  wood  low 0 0 0", 1, "unknown texture kind `wood`");
+    check("texture t  checker  even 0 0 0  odd 1 1 1  seed 3", 1, "unknown key `seed` for `texture`");
+    check("camera position 0 0 0  direction 1 0 0  size 4 3  fov 40", 1, "unknown key `fov`");
+}
+
+#[test]
+fn missing_arguments_are_rejected() {
+    // 键后面的值按个数读取，缺少的值由下一个键补上，剩下的值被当作未知的键
+    check(&format!("{}sphere center 0 0  radius 1  material m", MATERIAL), 2, "unknown key `1` for `sphere`");
+    check(&format!("{}sphere radius 1  material m  center 0 0 m", MATERIAL), 2, "`center` expects three numbers, found `m`");
+    check(&format!("{}sphere radius 1  material m  center 0 0", MATERIAL), 2, "`center` expects 3 value(s), found 2");
+    check(&format!("{}sphere center 0 0 0  material m", MATERIAL), 2, "`sphere` is missing `radius`");
+    check("sphere center 0 0 0  radius 1", 1, "`sphere` is missing `material`");
+    check("material", 1, "`material` needs a name");
+    check("profile", 1, "`profile` needs a name");
+    check("texture t", 1, "`texture` needs a kind");
+    check("camera position 0 0 0  direction 1 0 0", 1, "`camera` is missing `size`");
+    check("camera position 0 0 0  direction 1 0 0  size 4", 1, "`size` expects 2 value(s), found 1");
+    check("material m  diffuse 1", 1, "`material` is missing `color`");
+    check("point_light position 0 0 0", 1, "`point_light` is missing `color`");
+}
+
+#[test]
+fn extra_or_invalid_arguments_are_rejected() {
+    check(&format!("{}sphere center 0 0 0 7  radius 1  material m", MATERIAL), 2, "unknown key `7`");
+    check(&format!("{}sphere center 0 0 0  radius 1 2  material m", MATERIAL), 2, "unknown key `2`");
+    check(&format!("{}sphere center 0 0 0  radius 1  radius 2  material m", MATERIAL), 2, "duplicate key `radius`");
+    check(&format!("{}sphere center 0 0 0  radius one  material m", MATERIAL), 2, "`radius` expects a number, found `one`");
+    check(&format!("{}sphere center 0 0 0  radius 0  material m", MATERIAL), 2, "sphere radius must be positive");
+    check(&format!("{}plane normal 0 0 0  distance 1  material m", MATERIAL), 2, "`normal` must not be a zero vector");
+    check("camera position 0 0 0  direction 1 0 0  size 4 -3", 1, "`size` expects a positive integer, found `-3`");
+    check("camera position 0 0 0  direction 1 0 0  size 4 0", 1, "camera size must be positive");
+    check("material m  color 1 1 1  roughness 2", 1, "material roughness must be in [0, 1]");
+    check("profile p  1 0  1 1  1 2", 1, "`profile` expects 3n + 1 (radius, height) pairs, found 6 numbers");
+    check("profile p  1 0  1 1  1 2  1", 1, "found 7 numbers");
+    check(&format!("{}bezier position 0 0 0  material m  scale -1", MATERIAL), 2, "bezier scale must be positive");
+    check(&format!("{}mesh file a.obj  material m  scale 0", MATERIAL), 2, "mesh scale must be finite and not zero");
+    // NaN 与无穷大不能通过检查，否则会污染层次包围盒
+    check(&format!("{}sphere center 0 0 0  radius nan  material m", MATERIAL), 2, "sphere radius must be positive and finite");
+    check(&format!("{}sphere center 0 0 0  radius inf  material m", MATERIAL), 2, "sphere radius must be positive and finite");
+    check(&format!("{}bezier position 0 0 0  material m  scale inf", MATERIAL), 2, "bezier scale must be positive and finite");
+    check(&format!("{}mesh file a.obj  material m  scale inf", MATERIAL), 2, "mesh scale must be finite and not zero");
+    check(&format!("{}mesh file a.obj  material m  scale NaN", MATERIAL), 2, "mesh scale must be finite and not zero");
+    let light = "area_light position 0 0 0  dx 1 0 0  dy 0 1 0  normal 0 0 -1  color 1 1 1";
+    for size in ["width 0  height 1", "width 1  height -2", "width nan  height 1", "width 1  height inf"] {
+        check(&format!("{}  {}", light, size), 1, "area light width and height must be positive and finite");
+    }
+    check("texture t  noise  low 0 0 0  high 1 1 1  octaves 1.5", 1, "`octaves` expects a positive integer");
+}
+
+#[test]
+fn references_and_redeclarations_are_checked() {
+    check("sphere center 0 0 0  radius 1  material m", 1, "undefined material `m`");
+    check("material m  color 1 1 1  texture t", 1, "undefined texture `t`");
+    check(&format!("{}bezier position 0 0 0  material m  profile p", MATERIAL), 2, "undefined profile `p`");
+    check(&format!("{}{}", MATERIAL, MATERIAL), 2, "material `m` is declared twice");
+    check("texture t gradient from 0 0 0  to 1 1 1  start 0 0 0  end 0 0 1\ntexture t gradient from 0 0 0  to 1 1 1  start 0 0 0  end 0 0 1", 2, "texture `t` is declared twice");
+    check("profile p 0 0 1 1 1 2 0 3\nprofile p 0 0 1 1 1 2 0 3", 2, "profile `p` is declared twice");
+    check("camera position 0 0 0  direction 1 0 0  size 4 3\ncamera position 0 0 0  direction 1 0 0  size 4 3", 2, "camera is declared twice");
+    check("texture t  image  file missing.png", 1, "does not exist");
+}
+
+#[test]
+fn errors_report_the_line_number() {
+    // 注释、空行和只有空白的行同样计入行号
+    let text = "# header\n\n   \nmaterial m  color 1 1 1  # trailing\nsphere center 0 0 0  radius 1  material m\n\n# bad line below\nsphere center 0 0 0  radius -1  material m\n";
+    let (line, msg) = parse_error(text);
+    assert_eq!(line, 8, "{}", msg);
+    let err = Scene::parse(text, Path::new(".")).err().unwrap();
+    assert_eq!(err.to_string(), "line 8: sphere radius must be positive and finite");
+    // 找不到引用的模型文件时报告该文件的路径
+    match Scene::parse("mesh file does-not-exist.obj", Path::new(".")) {
+        Err(SceneError::Io(path, _)) => assert!(path.ends_with("does-not-exist.obj")),
+        Err(e) => panic!("unexpected error {}", e),
+        Ok(_) => panic!("a missing model should be rejected"),
+    }
+}
@@ -0,0 +1,211 @@
+extern crate ppm;
+extern crate rand;
+extern crate lodepng;
+
+use ppm::scene::texture::*;
+use ppm::scene::texture::Filter;
+use ppm::util::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+fn rgb(c: Color) -> [f64; 3] {
+    [c.r, c.g, c.b]
+}
+
+fn close(a: Color, b: Color) -> bool {
+    (a.r - b.r).abs() < 1e-9 && (a.g - b.g).abs() < 1e-9 && (a.b - b.b).abs() < 1e-9
+}
+
+// 场景范围内的随机位置，包含负坐标
+fn random_points(seed: u64, count: usize) -> Vec<Vector3> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..count)
+        .map(|_| Vector3::new(rng.gen_range(-50.0, 50.0), rng.gen_range(-50.0, 50.0), rng.gen_range(-50.0, 50.0)))
+        .collect()
+}
+
+// 只依赖曲面参数的纹理在 uv 处的颜色
+fn sample(texture: &dyn Texture, u: f64, v: f64) -> Color {
+    texture.value((u, v), &Vector3::new(0.0, 0.0, 0.0))
+}
+
+#[test]
+fn checker_alternates_across_cell_boundaries() {
+    let (even, odd) = (Color::new(1.0, 1.0, 1.0), Color::new(0.0, 0.0, 0.0));
+    let mut checker = CheckerTexture::new(even, odd);
+    checker.set_scale(2.0, 4.0);
+    // 格子大小为 0.5 x 0.25，包括负坐标在内，相邻格子颜色不同
+    for i in -4..4 {
+        for j in -4..4 {
+            let center = ((i as f64 + 0.5) * 0.5, (j as f64 + 0.5) * 0.25);
+            let expect = if (i + j) % 2 == 0 { even } else { odd };
+            assert!(close(sample(&checker, center.0, center.1), expect), "cell ({}, {})", i, j);
+        }
+    }
+    // 紧挨着边界的两侧
+    let eps = 1e-9;
+    assert!(close(sample(&checker, 0.5 - eps, 0.1), even));
+    assert!(close(sample(&checker, 0.5 + eps, 0.1), odd));
+    assert!(close(sample(&checker, -eps, 0.1), odd));
+    assert!(close(sample(&checker, 0.1, 0.25 - eps), even));
+    assert!(close(sample(&checker, 0.1, 0.25 + eps), odd));
+    assert!(close(sample(&checker, 0.5 + eps, 0.25 + eps), even));
+}
+
+#[test]
+fn gradient_is_clamped_at_both_ends() {
+    let (from, to) = (Color::new(1.0, 0.0, 0.0), Color::new(0.0, 0.0, 1.0));
+    let gradient = GradientTexture::new(from, to, Vector3::new(0.0, 0.0, 2.0), Vector3::new(0.0, 0.0, 12.0));
+    let at = |x: f64, z: f64| gradient.value((0.0, 0.0), &Vector3::new(x, 3.0, z));
+    assert!(close(at(0.0, 2.0), from));
+    assert!(close(at(0.0, 12.0), to));
+    assert!(close(at(5.0, 7.0), Color::new(0.5, 0.0, 0.5)));
+    // 两端之外保持端点的颜色
+    assert!(close(at(0.0, -100.0), from));
+    assert!(close(at(-7.0, 1.9), from));
+    assert!(close(at(0.0, 12.1), to));
+    assert!(close(at(3.0, 1e9), to));
+}
+
+#[test]
+fn noise_stays_in_range_and_is_reproducible() {
+    let (low, high) = (Color::new(0.0, 0.0, 0.0), Color::new(1.0, 1.0, 1.0));
+    let mut texture = NoiseTexture::new(low, high, 7);
+    texture.set_scale(0.3);
+    let same = {
+        let mut t = NoiseTexture::new(low, high, 7);
+        t.set_scale(0.3);
+        t
+    };
+    let mut other = NoiseTexture::new(low, high, 8);
+    other.set_scale(0.3);
+
+    let perlin = Perlin::new(7);
+    let mut differs = false;
+    let (mut min, mut max) = (f64::INFINITY, f64::NEG_INFINITY);
+    for p in random_points(1, 5000) {
+        let n = perlin.noise(&p);
+        assert!((-1.0..=1.0).contains(&n), "noise {} at {:?}", n, p);
+        let c = texture.value((0.0, 0.0), &p);
+        assert!(rgb(c).iter().all(|v| (0.0..=1.0).contains(v)), "{:?} at {:?}", rgb(c), p);
+        assert_eq!(rgb(c), rgb(same.value((0.0, 0.0), &p)));
+        differs |= rgb(c) != rgb(other.value((0.0, 0.0), &p));
+        min = min.min(c.r);
+        max = max.max(c.r);
+    }
+    assert!(differs, "different seeds should give different noise");
+    // 噪声不是常数
+    assert!(max - min > 0.3, "noise only spans [{}, {}]", min, max);
+    // 整数格点处噪声为 0
+    assert_eq!(perlin.noise(&Vector3::new(3.0, -4.0, 17.0)), 0.0);
+}
+
+#[test]
+fn marble_stays_between_its_colours() {
+    let (low, high) = (Color::new(0.1, 0.6, 0.3), Color::new(0.9, 0.2, 0.3));
+    let mut marble = MarbleTexture::new(low, high, 3);
+    marble.set_scale(0.2);
+    marble.set_turbulence(4.0);
+    let (mut min, mut max) = (f64::INFINITY, f64::NEG_INFINITY);
+    for p in random_points(2, 5000) {
+        let c = marble.value((0.0, 0.0), &p);
+        for (v, (a, b)) in rgb(c).iter().zip(rgb(low).iter().zip(rgb(high).iter())) {
+            assert!(*v >= a.min(*b) - 1e-12 && *v <= a.max(*b) + 1e-12, "{:?} at {:?}", rgb(c), p);
+        }
+        min = min.min(c.r);
+        max = max.max(c.r);
+    }
+    // 条纹在两种颜色之间完整地变化
+    assert!(min < 0.15 && max > 0.85, "marble only spans [{}, {}]", min, max);
+}
+
+// 3 x 2 的图像，像素 (x, y) 的颜色为 (x, y, 0)，y 从图像顶部开始计
+fn index_image() -> ImageTexture {
+    let pixels = (0..2).flat_map(|y| (0..3).map(move |x| Color::new(x as f64, y as f64, 0.0))).collect();
+    ImageTexture::new(3, 2, pixels)
+}
+
+// 像素 (x, y) 中心的纹理坐标，下标可以超出图像范围
+fn texel_center(x: i64, y: i64) -> (f64, f64) {
+    ((x as f64 + 0.5) / 3.0, 1.0 - (y as f64 + 0.5) / 2.0)
+}
+
+#[test]
+fn wrap_modes_outside_the_unit_square() {
+    // 列下标 -4..=6 与行下标 -3..=4 在各模式下对应的像素
+    let cases = [
+        (WrapMode::Repeat, [2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0], [1, 0, 1, 0, 1, 0, 1, 0]),
+        (WrapMode::Mirror, [2, 2, 1, 0, 0, 1, 2, 2, 1, 0, 0], [1, 1, 0, 0, 1, 1, 0, 0]),
+        (WrapMode::Clamp, [0, 0, 0, 0, 0, 1, 2, 2, 2, 2, 2], [0, 0, 0, 0, 1, 1, 1, 1]),
+    ];
+    for (wrap, columns, rows) in cases.iter() {
+        for filter in [Filter::Nearest, Filter::Bilinear] {
+            let mut texture = index_image();
+            texture.set_wrap(*wrap);
+            texture.set_filter(filter);
+            for (i, column) in columns.iter().enumerate() {
+                for (j, row) in rows.iter().enumerate() {
+                    let (u, v) = texel_center(i as i64 - 4, j as i64 - 3);
+                    let expect = Color::new(*column as f64, *row as f64, 0.0);
+                    let got = sample(&texture, u, v);
+                    assert!(close(got, expect), "{:?} {:?} at ({}, {}): {:?}", wrap, filter, u, v, rgb(got));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn bilinear_interpolates_between_texel_centers() {
+    let mut texture = index_image();
+    // 像素中心处得到原像素，与最近邻一致
+    for y in 0..2 {
+        for x in 0..3 {
+            let (u, v) = texel_center(x, y);
+            assert!(close(sample(&texture, u, v), Color::new(x as f64, y as f64, 0.0)));
+        }
+    }
+    // 两个像素中心的中点取平均值
+    assert!(close(sample(&texture, 1.0 / 3.0, 0.75), Color::new(0.5, 0.0, 0.0)));
+    assert!(close(sample(&texture, 1.0 / 3.0, 0.5), Color::new(0.5, 0.5, 0.0)));
+    assert!(close(sample(&texture, 0.25, 0.75), Color::new(0.25, 0.0, 0.0)));
+    // 边缘处按环绕方式取相邻像素
+    assert!(close(sample(&texture, 0.0, 0.75), Color::new(1.0, 0.0, 0.0)));
+    texture.set_wrap(WrapMode::Clamp);
+    assert!(close(sample(&texture, 0.0, 0.75), Color::new(0.0, 0.0, 0.0)));
+}
+
+#[test]
+fn scale_and_offset_transform_the_coordinates() {
+    let mut texture = index_image();
+    texture.set_filter(Filter::Nearest);
+    texture.set_scale(2.0, -1.0);
+    texture.set_offset(0.25, 1.0);
+    let mut plain = index_image();
+    plain.set_filter(Filter::Nearest);
+    for (u, v) in [(0.0, 0.0), (0.3, 0.2), (0.6, 0.9), (-0.4, 1.7)] {
+        let expect = sample(&plain, u * 2.0 + 0.25, 1.0 - v);
+        assert!(close(sample(&texture, u, v), expect), "at ({}, {})", u, v);
+    }
+    // u = 0.3 变换为 0.85，落在第 2 列；v = 0.2 变换为 0.8，落在顶部一行
+    assert!(close(sample(&texture, 0.3, 0.2), Color::new(2.0, 0.0, 0.0)));
+}
+
+#[test]
+fn png_is_decoded_from_srgb() {
+    let path = std::env::temp_dir().join(format!("ppm-texture-{}.png", std::process::id()));
+    // 2 x 1 的 RGB 图像
+    let bytes: [u8; 6] = [0, 128, 255, 10, 10, 10];
+    lodepng::encode24_file(&path, &bytes, 2, 1).unwrap();
+    let texture = ImageTexture::open(&path);
+    std::fs::remove_file(&path).unwrap();
+    let mut texture = texture.unwrap();
+    assert_eq!((texture.width(), texture.height()), (2, 1));
+    texture.set_filter(Filter::Nearest);
+    let left = sample(&texture, 0.25, 0.5);
+    assert_eq!((left.r, left.b), (0.0, 1.0));
+    assert!((left.g - 0.215861).abs() < 1e-6, "{}", left.g);
+    // 较暗的值落在线性段
+    let right = sample(&texture, 0.75, 0.5);
+    assert!((right.r - 10.0 / 255.0 / 12.92).abs() < 1e-12, "{}", right.r);
+}
@@ -0,0 +1,68 @@
+extern crate ppm;
+
+use ppm::util::tone_map::{luminance, srgb_decode, srgb_encode};
+use ppm::util::*;
+
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-4
+}
+
+#[test]
+fn srgb_curve_is_continuous_and_invertible() {
+    assert_eq!(srgb_encode(0.0), 0.0);
+    assert!(close(srgb_encode(1.0), 1.0));
+    assert!(close(srgb_encode(0.18), 0.4614));
+    // 线性段与幂函数段在分界处相接
+    assert!(close(srgb_encode(0.0031308), 1.055 * 0.0031308f64.powf(1.0 / 2.4) - 0.055));
+    for i in 0..=100 {
+        let v = i as f64 / 100.0;
+        assert!(close(srgb_decode(srgb_encode(v)), v));
+    }
+    assert_eq!(Color::new(1.0, 0.5, 0.0).to_u8(), (255, 188, 0));
+}
+
+#[test]
+fn exposure_scales_radiance_in_stops() {
+    let c = Color::new(0.1, 0.2, 0.05);
+    let m = ToneMapping::new(1.0, ToneMapper::Clamp).map(c);
+    assert!(close(m.r, 0.2) && close(m.g, 0.4) && close(m.b, 0.1));
+    let m = ToneMapping::new(-2.0, ToneMapper::Clamp).map(c);
+    assert!(close(m.r, 0.025) && close(m.g, 0.05) && close(m.b, 0.0125));
+}
+
+#[test]
+fn operators_compress_highlights_monotonically() {
+    for operator in [ToneMapper::Reinhard, ToneMapper::Aces] {
+        let tone = ToneMapping::new(0.0, operator);
+        let mut last = 0.0;
+        for k in -10..=20 {
+            let l = 2f64.powi(k);
+            let m = tone.map(Color::new(l, l, l));
+            assert!(m.r <= 1.0 && m.g <= 1.0 && m.b <= 1.0, "{:?} exceeds 1 at {}", operator, l);
+            assert!(m.g >= last, "{:?} is not monotonic at {}", operator, l);
+            last = m.g;
+        }
+        assert!(last > 0.99, "{:?} does not approach white", operator);
+        // 明亮的饱和颜色不会溢出(ACES 会把它推向白色)，负值和 NaN 映射为黑色
+        let m = tone.map(Color::new(500.0, 20.0, 1.0));
+        assert!(m.r <= 1.0 && m.r >= m.g && m.g >= m.b);
+        let m = tone.map(Color::new(-1.0, f64::NAN, 0.0));
+        assert_eq!((m.r, m.g, m.b), (0.0, 0.0, 0.0));
+    }
+}
+
+#[test]
+fn reinhard_preserves_hue() {
+    let c = Color::new(1.2, 0.6, 0.12);
+    let m = ToneMapping::new(0.0, ToneMapper::Reinhard).map(c);
+    assert!(close(m.g / m.r, 0.5) && close(m.b / m.r, 0.1));
+    assert!(close(luminance(&m), luminance(&c) / (1.0 + luminance(&c))));
+}
+
+#[test]
+fn tone_mapper_names() {
+    assert_eq!("clamp".parse::<ToneMapper>(), Ok(ToneMapper::Clamp));
+    assert_eq!("reinhard".parse::<ToneMapper>(), Ok(ToneMapper::Reinhard));
+    assert_eq!("aces".parse::<ToneMapper>(), Ok(ToneMapper::Aces));
+    assert!("filmic".parse::<ToneMapper>().is_err());
+}